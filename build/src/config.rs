@@ -1,59 +1,276 @@
 use crate::prelude::*;
+
+use crate::engine::BuildConfigurationOverrides;
 use byte_unit::Byte;
+use ide_ci::goodie::GoodieDatabase;
+use ide_ci::goodies;
 use ide_ci::program;
+use ide_ci::programs;
+use semver::Op;
 use semver::VersionReq;
 
+/// Name of the build configuration file, placed in the repository root.
+pub const FILENAME: &str = "build-config.yaml";
+
 pub fn load_yaml(yaml_text: &str) -> Result<Config> {
     let raw = serde_yaml::from_str::<ConfigRaw>(yaml_text)?;
     raw.try_into()
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, strum::EnumString)]
+/// The lowest version matching the requirement, if it is given by a single lower bound.
+pub fn lowest_matching(requirement: &VersionReq) -> Option<Version> {
+    match requirement.comparators.as_slice() {
+        [comparator] => match comparator.op {
+            Op::Exact | Op::Caret | Op::Tilde | Op::GreaterEq | Op::Wildcard => Some(Version::new(
+                comparator.major,
+                comparator.minor.unwrap_or(0),
+                comparator.patch.unwrap_or(0),
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Program whose version can be required in the configuration.
+///
+/// The recognized programs are checked through their [`Program`] implementations, which know how
+/// to query and parse their versions. Any other program is asked for `--version`.
+#[derive(
+    Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, strum::AsRefStr, strum::EnumString,
+)]
+#[strum(serialize_all = "kebab-case")]
 pub enum RecognizedProgram {
+    Cargo,
+    Flatc,
+    Go,
+    /// The GraalVM version, if the Java is GraalVM's.
+    Java,
+    Node,
+    Npm,
+    Sbt,
+    WasmOpt,
+    WasmPack,
     #[strum(default)]
     Other(String),
 }
 
+impl Display for RecognizedProgram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecognizedProgram::Other(program) => write!(f, "{program}"),
+            known => write!(f, "{}", known.as_ref()),
+        }
+    }
+}
+
 impl RecognizedProgram {
     pub async fn version(&self) -> Result<Version> {
         match self {
+            RecognizedProgram::Cargo => programs::Cargo.version().await,
+            RecognizedProgram::Flatc => programs::Flatc.version().await,
+            RecognizedProgram::Go => programs::Go.version().await,
+            RecognizedProgram::Java => programs::Java.version().await,
+            RecognizedProgram::Node => programs::Node.version().await,
+            RecognizedProgram::Npm => programs::Npm.version().await,
+            RecognizedProgram::Sbt => programs::Sbt.version().await,
+            RecognizedProgram::WasmOpt => programs::wasm_opt::WasmOpt.version().await,
+            RecognizedProgram::WasmPack => programs::WasmPack.version().await,
             RecognizedProgram::Other(program) => program::Unknown(program.clone()).version().await,
         }
     }
+
+    /// Check that [`RecognizedProgram::install`] can install a version satisfying the
+    /// requirement.
+    ///
+    /// SBT is installed only in the [version](goodies::sbt::VERSION) that the build uses.
+    pub fn check_installable(&self, requirement: &VersionReq) -> Result {
+        match self {
+            RecognizedProgram::Sbt => ensure!(
+                requirement.matches(&goodies::sbt::VERSION),
+                "Only SBT {} can be installed automatically, which does not satisfy \
+                 `{requirement}`.",
+                goodies::sbt::VERSION
+            ),
+            RecognizedProgram::WasmPack => {
+                lowest_matching(requirement).with_context(|| {
+                    format!("Cannot pick the wasm-pack version satisfying `{requirement}`.")
+                })?;
+            }
+            other => bail!("Program {other} cannot be installed automatically."),
+        }
+        Ok(())
+    }
+
+    /// Install the program as a goodie and add it to the environment.
+    pub async fn install(&self, requirement: &VersionReq) -> Result {
+        self.check_installable(requirement)?;
+        let database = GoodieDatabase::new()?;
+        match self {
+            RecognizedProgram::Sbt => database.require(&goodies::sbt::Sbt).await,
+            RecognizedProgram::WasmPack => {
+                let version = lowest_matching(requirement).with_context(|| {
+                    format!("Cannot pick the wasm-pack version satisfying `{requirement}`.")
+                })?;
+                let goodie =
+                    goodies::wasm_pack::WasmPack { version, os: TARGET_OS, arch: TARGET_ARCH };
+                database.require(&goodie).await
+            }
+            other => bail!("Program {other} cannot be installed automatically."),
+        }
+    }
+
+    /// Check the program against the requirement, installing it first if needed and requested.
+    pub async fn check(&self, requirement: &Requirement) -> ProgramStatus {
+        let matches = |found: &Option<Version>| {
+            found.as_ref().map_or(false, |found| requirement.version.matches(found))
+        };
+        let mut found = self.version().await.inspect_err(|e| debug!("{self}: {e:?}")).ok();
+        let mut installed = false;
+        if requirement.install && !matches(&found) {
+            match self.install(&requirement.version).await {
+                Ok(()) => {
+                    installed = true;
+                    found = self.version().await.ok();
+                }
+                Err(e) => warn!("Failed to install {self}: {e:?}"),
+            }
+        }
+        let status = match &found {
+            None => Status::Missing,
+            found if !matches(found) => Status::Mismatched,
+            _ if installed => Status::Installed,
+            _ => Status::Satisfied,
+        };
+        ProgramStatus {
+            program: self.clone(),
+            required: requirement.version.clone(),
+            found,
+            status,
+        }
+    }
+}
+
+/// Version requirement of a program, as given in the configuration file.
+///
+/// Either just the requirement, like `^0.10.2`, or a map with the `version` requirement and the
+/// `install` flag.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequirementRaw {
+    Version(String),
+    Detailed {
+        version: String,
+        #[serde(default)]
+        install: bool,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Requirement {
+    pub version: VersionReq,
+    /// Whether the program should be installed if it is missing or does not match.
+    pub install: bool,
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.version)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Status {
+    Satisfied,
+    /// The program was installed and now satisfies the requirement.
+    Installed,
+    Mismatched,
+    Missing,
+}
+
+impl Status {
+    pub fn is_ok(self) -> bool {
+        matches!(self, Status::Satisfied | Status::Installed)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ProgramStatus {
+    pub program:  RecognizedProgram,
+    pub required: VersionReq,
+    pub found:    Option<Version>,
+    pub status:   Status,
+}
+
+/// Result of checking all the required programs.
+#[derive(Clone, Debug)]
+pub struct ProgramsReport {
+    pub programs: Vec<ProgramStatus>,
+}
+
+impl ProgramsReport {
+    pub fn failures(&self) -> impl Iterator<Item = &ProgramStatus> {
+        self.programs.iter().filter(|program| !program.status.is_ok())
+    }
+
+    /// Summary of the check as a Markdown table.
+    pub fn to_markdown(&self) -> String {
+        let mut ret = String::from("| Program | Required | Found | Status |\n");
+        ret += "|---|---|---|---|\n";
+        for ProgramStatus { program, required, found, status } in &self.programs {
+            let found = found.as_ref().map_or_else(|| "—".to_string(), |found| found.to_string());
+            let status = match status {
+                status if !status.is_ok() => format!("⚠️ {status}"),
+                status => status.to_string(),
+            };
+            ret += &format!("| {program} | {required} | {found} | {status} |\n");
+        }
+        ret
+    }
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
-#[serde(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case", default)]
 pub struct ConfigRaw {
     pub wasm_size_limit:   Option<String>,
-    pub required_versions: HashMap<String, String>,
+    pub required_versions: HashMap<String, RequirementRaw>,
+    pub engine_presets:    BTreeMap<String, BuildConfigurationOverrides>,
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct Config {
     pub wasm_size_limit:   Option<Byte>,
-    pub required_versions: HashMap<RecognizedProgram, VersionReq>,
+    pub required_versions: HashMap<RecognizedProgram, Requirement>,
+    /// Named sets of the engine build configuration flags.
+    pub engine_presets:    BTreeMap<String, BuildConfigurationOverrides>,
 }
 
 impl Config {
-    pub async fn check_programs(&self) -> Result {
-        for (program, version_req) in &self.required_versions {
-            let found = program.version().await?;
-            if !version_req.matches(&found) {
-                bail!(
-                    "Found program {:?} in version {} that does not fulfill requirement {}.",
-                    program,
-                    found,
-                    version_req
-                );
+    pub fn engine_preset(&self, name: &str) -> Result<&BuildConfigurationOverrides> {
+        self.engine_presets.get(name).with_context(|| {
+            if self.engine_presets.is_empty() {
+                format!("Unknown engine build preset `{name}`: no presets are defined.")
             } else {
-                info!(
-                    "Found program {:?} in supported version {} (required {}).",
-                    program, found, version_req
-                );
+                let known = self.engine_presets.keys().join(", ");
+                format!("Unknown engine build preset `{name}`. Known presets: {known}.")
             }
+        })
+    }
+
+    /// Check all the required programs, installing the ones that request it.
+    pub async fn check_programs(&self) -> Result<ProgramsReport> {
+        let required =
+            self.required_versions.iter().sorted_by_key(|(program, _)| program.to_string());
+        let mut programs = Vec::new();
+        for (program, requirement) in required {
+            programs.push(program.check(requirement).await);
         }
-        Ok(())
+        let report = ProgramsReport { programs };
+        info!("Required programs:\n{}", report.to_markdown());
+        let failures = report.failures().map(|failure| failure.program.to_string()).join(", ");
+        ensure!(failures.is_empty(), "Programs not fulfilling the requirements: {failures}.");
+        Ok(report)
     }
 }
 
@@ -62,11 +279,17 @@ impl TryFrom<ConfigRaw> for Config {
 
     fn try_from(value: ConfigRaw) -> std::result::Result<Self, Self::Error> {
         let mut required_versions = HashMap::new();
-        for (program, version_req) in value.required_versions {
-            required_versions.insert(
-                <RecognizedProgram as FromString>::from_str(&program)?,
-                <VersionReq as FromString>::from_str(&version_req)?,
-            );
+        for (program, requirement) in value.required_versions {
+            let program = <RecognizedProgram as FromString>::from_str(&program)?;
+            let (version, install) = match requirement {
+                RequirementRaw::Version(version) => (version, false),
+                RequirementRaw::Detailed { version, install } => (version, install),
+            };
+            let version = <VersionReq as FromString>::from_str(&version)?;
+            if install {
+                program.check_installable(&version)?;
+            }
+            required_versions.insert(program, Requirement { version, install });
         }
 
         Ok(Self {
@@ -75,6 +298,7 @@ impl TryFrom<ConfigRaw> for Config {
                 .map(|limit_text| <Byte as FromString>::from_str(&limit_text))
                 .transpose()?,
             required_versions,
+            engine_presets: value.engine_presets,
         })
     }
 }
@@ -102,4 +326,135 @@ required-versions:
 
         Ok(())
     }
+
+    #[test]
+    fn versions_from_requirements() {
+        let lowest = |requirement: &str| lowest_matching(&VersionReq::parse(requirement).unwrap());
+        assert_eq!(lowest("=16.15.0"), Some(Version::new(16, 15, 0)));
+        assert_eq!(lowest("^0.10.2"), Some(Version::new(0, 10, 2)));
+        assert_eq!(lowest(">=1.12"), Some(Version::new(1, 12, 0)));
+        assert_eq!(lowest("<2.0.0"), None);
+        assert_eq!(lowest(">=1.0.0, <2.0.0"), None);
+    }
+
+    #[test]
+    fn requirements() -> Result {
+        let config = load_yaml(
+            r#"
+required-versions:
+  wasm-opt: ">=108"
+  wasm-pack:
+    version: ^0.10.2
+    install: true
+  cmake: ^3.0
+"#,
+        )?;
+        let wasm_pack = &config.required_versions[&RecognizedProgram::WasmPack];
+        assert_eq!(wasm_pack, &Requirement {
+            version: VersionReq::parse("^0.10.2")?,
+            install: true,
+        });
+        assert!(!config.required_versions[&RecognizedProgram::WasmOpt].install);
+        assert!(config.required_versions.contains_key(&RecognizedProgram::Other("cmake".into())));
+        assert_eq!(RecognizedProgram::WasmPack.to_string(), "wasm-pack");
+
+        let error = load_yaml("required-versions: { go: { version: ^1.18, install: true } }")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Program go cannot be installed automatically."));
+        load_yaml("required-versions: { sbt: { version: ^1.5.0, install: true } }")?;
+        let error = load_yaml("required-versions: { sbt: { version: ^1.6.0, install: true } }")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Only SBT 1.5.5 can be installed automatically"));
+        load_yaml("required-versions: { sbt: ^1.6.0 }")?;
+
+        let report = ProgramsReport {
+            programs: vec![
+                ProgramStatus {
+                    program:  RecognizedProgram::Node,
+                    required: VersionReq::parse("=16.15.0")?,
+                    found:    Some(Version::new(16, 15, 0)),
+                    status:   Status::Satisfied,
+                },
+                ProgramStatus {
+                    program:  RecognizedProgram::Sbt,
+                    required: VersionReq::parse("^1.5.5")?,
+                    found:    None,
+                    status:   Status::Missing,
+                },
+            ],
+        };
+        let table = report.to_markdown();
+        assert!(table.contains("| node | =16.15.0 | 16.15.0 | satisfied |\n"));
+        assert!(table.contains("| sbt | ^1.5.5 | — | ⚠️ missing |\n"));
+        assert_eq!(report.failures().count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn engine_presets() -> Result {
+        let config = load_yaml(
+            r#"
+engine-presets:
+  quick:
+    test-scala: false
+    test-parallelism: task-parallelism
+    sbt-batch-memory-threshold: 8 GiB
+  bundles:
+    build-launcher-bundle: true
+    build-engine-package: true
+"#,
+        )?;
+        let mut flags = crate::engine::DEV;
+        config.engine_preset("quick")?.apply(&mut flags);
+        assert!(!flags.test_scala);
+        assert!(flags.test_standard_library);
+        assert_eq!(flags.test_parallelism, ide_ci::future::AsyncPolicy::TaskParallelism);
+        assert_eq!(flags.sbt_batch_memory_threshold, <Byte as FromString>::from_str("8 GiB")?);
+        flags.validate()?;
+
+        let mut flags = crate::engine::NIGHTLY;
+        config.engine_preset("bundles")?.apply(&mut flags);
+        flags.validate()?;
+        assert!(flags.build_launcher_package());
+        flags.test_retries = 2;
+        let error = flags.validate().unwrap_err().to_string();
+        assert!(error.contains("`test_retries` is set to 2 but no tests are run"));
+        flags.test_retries = 0;
+        flags.fail_on_benchmark_regression = true;
+        let error = flags.validate().unwrap_err().to_string();
+        assert!(error.contains("`fail_on_benchmark_regression` has no effect"));
+        flags.run_benchmarks = true;
+        let error = flags.validate().unwrap_err().to_string();
+        assert!(error.contains("`run_benchmarks` needs the benchmarks to compile"));
+        flags.benchmark_compilation = true;
+        flags.validate()?;
+
+        // The command line disables the package that the preset's bundle needs.
+        let command_line = BuildConfigurationOverrides {
+            build_launcher_package: Some(false),
+            build_engine_package: Some(false),
+            ..default()
+        };
+        let overrides = config.engine_preset("bundles")?.then(&command_line);
+        let mut flags = crate::engine::NIGHTLY;
+        overrides.apply(&mut flags);
+        let error = overrides.validate(&flags).unwrap_err().to_string();
+        assert!(error.contains(
+            "`build_launcher_package` is set to false, but it is needed by `build_launcher_bundle`"
+        ));
+        assert!(error.contains("`build_engine_package` is set to false"));
+        let command_line =
+            BuildConfigurationOverrides { build_launcher_bundle: Some(false), ..command_line };
+        let overrides = config.engine_preset("bundles")?.then(&command_line);
+        let mut flags = crate::engine::NIGHTLY;
+        overrides.apply(&mut flags);
+        overrides.validate(&flags)?;
+
+        let error = config.engine_preset("slow").unwrap_err().to_string();
+        assert!(error.contains("Known presets: bundles, quick."));
+        assert!(load_yaml("engine-presets: { typo: { test-scal: false } }").is_err());
+        Ok(())
+    }
 }
@@ -2,19 +2,31 @@ use crate::prelude::*;
 
 use crate::paths::ComponentPaths;
 use crate::paths::Paths;
+use byte_unit::Byte;
 
 use ide_ci::future::AsyncPolicy;
 use ide_ci::models::config::RepoContext;
+use ide_ci::models::test_report::history::FlakinessHistory;
+use ide_ci::models::test_report::TestReport;
 
+pub mod benchmark;
 pub mod bundle;
 pub mod context;
 pub mod env;
 pub mod sbt;
+pub mod step;
+pub mod verify;
 
 pub use context::RunContext;
 
-const FLATC_VERSION: Version = Version::new(1, 12, 0);
+/// The flatc version used to generate the engine's protocol sources.
+pub const FLATC_VERSION: Version = Version::new(1, 12, 0);
 const PARALLEL_ENSO_TESTS: AsyncPolicy = AsyncPolicy::Sequential;
+/// How many of the slowest tests are listed in the test summary.
+pub const SLOWEST_TESTS_SHOWN: usize = 10;
+/// Memory of the GitHub-hosted macOS runners. Machines with more memory can build all the packages
+/// in a single sbt invocation.
+pub const DEFAULT_SBT_BATCH_MEMORY_THRESHOLD: Byte = Byte::from_bytes(15_032_385 * 1024);
 
 pub async fn download_project_templates(client: reqwest::Client, enso_root: PathBuf) -> Result {
     // Download Project Template Files
@@ -51,7 +63,8 @@ pub async fn download_project_templates(client: reqwest::Client, enso_root: Path
     Ok(())
 }
 
-#[derive(Clone, Copy, Debug, Display, PartialEq)]
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, clap::ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum BuildMode {
     Development,
     NightlyRelease,
@@ -73,11 +86,32 @@ pub struct BuildConfigurationFlags {
     /// Note that this does not run the benchmarks, only ensures that they are buildable.
     pub benchmark_compilation: bool,
     pub build_js_parser: bool,
+    /// Whether the runtime and the standard library benchmarks are run and compared against the
+    /// baseline results, see [`env::BenchmarkBaseline`].
+    pub run_benchmarks: bool,
+    /// Slowdown of a benchmark, in percent, that is considered a regression.
+    pub benchmark_regression_threshold: f64,
+    /// Whether a benchmark regression fails the build, rather than just being reported.
+    pub fail_on_benchmark_regression: bool,
+    /// How the standard library test projects are run with respect to each other.
+    pub test_parallelism: AsyncPolicy,
+    /// How many times the failed test suites are rerun before the build fails. Tests that pass
+    /// only when rerun are reported as flaky.
+    pub test_retries: usize,
     pub build_engine_package: bool,
     pub build_launcher_package: bool,
     pub build_project_manager_package: bool,
     pub build_launcher_bundle: bool,
     pub build_project_manager_bundle: bool,
+    /// If the machine has more memory, all the packages are built in a single batch of
+    /// concurrent sbt tasks.
+    pub sbt_batch_memory_threshold: Byte,
+    /// Whether the sbt commands of the sequential (low memory) build are sent to a single sbt
+    /// server, rather than each spawning a new sbt process.
+    ///
+    /// Off in the built-in presets, enable it with `--sbt-server` or in a `build-config.yaml`
+    /// preset.
+    pub sbt_server: bool,
 }
 
 impl From<BuildConfigurationFlags> for BuildConfigurationResolved {
@@ -101,7 +135,7 @@ impl BuildConfigurationResolved {
             config.build_engine_package = true;
         }
 
-        if config.test_standard_library {
+        if config.test_standard_library || config.run_benchmarks {
             config.build_engine_package = true;
         }
 
@@ -115,6 +149,7 @@ impl BuildConfigurationFlags {
             || self.build_launcher_bundle
             || self.build_project_manager_bundle
             || self.test_standard_library
+            || self.run_benchmarks
     }
 
     pub fn build_project_manager_package(&self) -> bool {
@@ -124,6 +159,53 @@ impl BuildConfigurationFlags {
     pub fn build_launcher_package(&self) -> bool {
         self.build_launcher_package || self.build_launcher_bundle
     }
+
+    /// Check for flag combinations that are inconsistent, e.g. because they were partially
+    /// overridden by a preset.
+    ///
+    /// The bundles need not enable their packages, as they imply them (see e.g.
+    /// [`Self::build_launcher_package`]). Disabling such a package explicitly is caught by
+    /// [`BuildConfigurationOverrides::validate`].
+    pub fn validate(&self) -> Result {
+        let mut problems = vec![];
+        let any_tests = self.test_scala || self.test_standard_library;
+        if self.test_retries > 0 && !any_tests {
+            problems.push(format!(
+                "`test_retries` is set to {} but no tests are run; enable `test_scala` or \
+                `test_standard_library`, or set `test_retries` to 0.",
+                self.test_retries
+            ));
+        }
+        if self.benchmark_regression_threshold < 0.0 {
+            problems.push(format!(
+                "`benchmark_regression_threshold` must not be negative, got {}.",
+                self.benchmark_regression_threshold
+            ));
+        }
+        if self.run_benchmarks && !self.benchmark_compilation {
+            problems.push(
+                "`run_benchmarks` needs the benchmarks to compile; enable \
+                `benchmark_compilation`."
+                    .into(),
+            );
+        }
+        if self.fail_on_benchmark_regression && !self.run_benchmarks {
+            problems.push(
+                "`fail_on_benchmark_regression` has no effect without `run_benchmarks`; enable \
+                `run_benchmarks` or disable `fail_on_benchmark_regression`."
+                    .into(),
+            );
+        }
+        report_problems(problems)
+    }
+}
+
+fn report_problems(problems: Vec<String>) -> Result {
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        bail!("Inconsistent build configuration:\n  * {}", problems.join("\n  * "))
+    }
 }
 
 pub const DEV: BuildConfigurationFlags = BuildConfigurationFlags {
@@ -133,11 +215,18 @@ pub const DEV: BuildConfigurationFlags = BuildConfigurationFlags {
     test_standard_library: true,
     benchmark_compilation: true,
     build_js_parser: matches!(TARGET_OS, OS::Linux),
+    run_benchmarks: false,
+    benchmark_regression_threshold: benchmark::DEFAULT_REGRESSION_THRESHOLD,
+    fail_on_benchmark_regression: false,
+    test_parallelism: PARALLEL_ENSO_TESTS,
+    test_retries: 0,
     build_engine_package: false,
     build_launcher_package: false,
     build_project_manager_package: false,
     build_launcher_bundle: false,
     build_project_manager_bundle: false,
+    sbt_batch_memory_threshold: DEFAULT_SBT_BATCH_MEMORY_THRESHOLD,
+    sbt_server: false,
 };
 
 pub const NIGHTLY: BuildConfigurationFlags = BuildConfigurationFlags {
@@ -147,13 +236,163 @@ pub const NIGHTLY: BuildConfigurationFlags = BuildConfigurationFlags {
     test_standard_library: false,
     benchmark_compilation: false,
     build_js_parser: false,
+    run_benchmarks: false,
+    benchmark_regression_threshold: benchmark::DEFAULT_REGRESSION_THRESHOLD,
+    fail_on_benchmark_regression: false,
+    test_parallelism: PARALLEL_ENSO_TESTS,
+    test_retries: 0,
     build_engine_package: false,
     build_launcher_package: false,
     build_project_manager_package: false,
     build_launcher_bundle: false,
     build_project_manager_bundle: false,
+    sbt_batch_memory_threshold: DEFAULT_SBT_BATCH_MEMORY_THRESHOLD,
+    sbt_server: false,
 };
 
+/// Changes to the build configuration flags, coming from a named preset or the command line.
+///
+/// The flags that are not set keep their value from the base configuration.
+#[derive(clap::Args, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct BuildConfigurationOverrides {
+    #[clap(long)]
+    pub clean_repo: Option<bool>,
+    #[clap(long, arg_enum)]
+    pub mode: Option<BuildMode>,
+    #[clap(long)]
+    pub test_scala: Option<bool>,
+    #[clap(long)]
+    pub test_standard_library: Option<bool>,
+    #[clap(long)]
+    pub benchmark_compilation: Option<bool>,
+    #[clap(long)]
+    pub build_js_parser: Option<bool>,
+    #[clap(long)]
+    pub run_benchmarks: Option<bool>,
+    #[clap(long)]
+    pub benchmark_regression_threshold: Option<f64>,
+    #[clap(long)]
+    pub fail_on_benchmark_regression: Option<bool>,
+    #[clap(long, arg_enum)]
+    pub test_parallelism: Option<AsyncPolicy>,
+    #[clap(long)]
+    pub test_retries: Option<usize>,
+    #[clap(long)]
+    pub build_engine_package: Option<bool>,
+    #[clap(long)]
+    pub build_launcher_package: Option<bool>,
+    #[clap(long)]
+    pub build_project_manager_package: Option<bool>,
+    #[clap(long)]
+    pub build_launcher_bundle: Option<bool>,
+    #[clap(long)]
+    pub build_project_manager_bundle: Option<bool>,
+    /// Memory size, like `16GiB`.
+    #[clap(long)]
+    pub sbt_batch_memory_threshold: Option<Byte>,
+    #[clap(long)]
+    pub sbt_server: Option<bool>,
+}
+
+impl BuildConfigurationOverrides {
+    pub fn apply(&self, config: &mut BuildConfigurationFlags) {
+        macro_rules! apply {
+            ($($flag:ident),*) => {
+                $(if let Some(value) = self.$flag {
+                    config.$flag = value;
+                })*
+            };
+        }
+        apply!(
+            clean_repo,
+            mode,
+            test_scala,
+            test_standard_library,
+            benchmark_compilation,
+            build_js_parser,
+            run_benchmarks,
+            benchmark_regression_threshold,
+            fail_on_benchmark_regression,
+            test_parallelism,
+            test_retries,
+            build_engine_package,
+            build_launcher_package,
+            build_project_manager_package,
+            build_launcher_bundle,
+            build_project_manager_bundle,
+            sbt_batch_memory_threshold,
+            sbt_server
+        );
+    }
+
+    /// Combine with the overrides given later, like the command line ones after a preset. The
+    /// later ones take precedence.
+    pub fn then(mut self, later: &Self) -> Self {
+        macro_rules! then {
+            ($($flag:ident),*) => {
+                $(if later.$flag.is_some() {
+                    self.$flag = later.$flag;
+                })*
+            };
+        }
+        then!(
+            clean_repo,
+            mode,
+            test_scala,
+            test_standard_library,
+            benchmark_compilation,
+            build_js_parser,
+            run_benchmarks,
+            benchmark_regression_threshold,
+            fail_on_benchmark_regression,
+            test_parallelism,
+            test_retries,
+            build_engine_package,
+            build_launcher_package,
+            build_project_manager_package,
+            build_launcher_bundle,
+            build_project_manager_bundle,
+            sbt_batch_memory_threshold,
+            sbt_server
+        );
+        self
+    }
+
+    /// Check that no package is explicitly disabled while the final configuration needs it.
+    ///
+    /// Such package would be built anyway, see [`BuildConfigurationFlags::build_engine_package`].
+    pub fn validate(&self, config: &BuildConfigurationFlags) -> Result {
+        let mut problems = vec![];
+        let mut check = |package: &str, disabled: bool, needed_by: &[(&str, bool)]| {
+            let needed_by = needed_by.iter().filter(|(_, needs)| *needs).map(|(flag, _)| flag);
+            let needed_by = needed_by.map(|flag| format!("`{flag}`")).join(", ");
+            if disabled && !needed_by.is_empty() {
+                problems.push(format!(
+                    "`{package}` is set to false, but it is needed by {needed_by}; do not \
+                    disable it or disable these too."
+                ));
+            }
+        };
+        check("build_launcher_package", self.build_launcher_package == Some(false), &[(
+            "build_launcher_bundle",
+            config.build_launcher_bundle,
+        )]);
+        check(
+            "build_project_manager_package",
+            self.build_project_manager_package == Some(false),
+            &[("build_project_manager_bundle", config.build_project_manager_bundle)],
+        );
+        check("build_engine_package", self.build_engine_package == Some(false), &[
+            ("build_launcher_bundle", config.build_launcher_bundle),
+            ("build_project_manager_bundle", config.build_project_manager_bundle),
+            ("test_standard_library", config.test_standard_library),
+            ("run_benchmarks", config.run_benchmarks),
+        ]);
+        report_problems(problems)
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum ReleaseCommand {
     Upload,
@@ -239,6 +478,15 @@ impl IntoIterator for BuiltBundleArtifacts {
     }
 }
 
+/// Print the summary of the test results, note the flaky tests in the local history and export the
+/// results to a JSON file.
+pub fn report_tests(report: &TestReport, paths: &Paths) -> Result {
+    println!("{}", report.summary(SLOWEST_TESTS_SHOWN));
+    let history = FlakinessHistory::update_file(paths.flakiness_history(), report)?;
+    print!("{history}");
+    report.write_json(paths.test_report())
+}
+
 pub async fn create_packages(paths: &Paths) -> Result<Vec<PathBuf>> {
     let mut ret = Vec::new();
     if paths.launcher.root.exists() {
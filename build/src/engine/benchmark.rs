@@ -0,0 +1,322 @@
+//! Running the engine benchmarks and comparing their results against a baseline.
+//!
+//! The runtime benchmarks are JMH-based and report their results in the JMH table format. The
+//! standard library benchmarks print the average time of each benchmark.
+
+use crate::prelude::*;
+
+use regex::Regex;
+use std::str::FromStr;
+
+
+
+/// Prefix of the baseline source denoting a CI artifact, rather than a local file.
+pub const ARTIFACT_PREFIX: &str = "artifact:";
+
+/// Default tolerance of the benchmark slowdown, in percent.
+pub const DEFAULT_REGRESSION_THRESHOLD: f64 = 10.0;
+
+lazy_static! {
+    /// A result row of the JMH table, e.g.
+    /// `[info] AtomBenchmarks.benchGenerateList  avgt  5  12.345 ± 0.678  ms/op`.
+    static ref JMH_RESULT: Regex = Regex::new(
+        r"^(?:\[info\]\s+)?(\S+)\s+(thrpt|avgt|sample|ss)\s+(?:\d+\s+)?([\d.,]+)\s+(?:±\s+([\d.,]+)\s+)?(\S+)$"
+    )
+    .unwrap();
+    /// A standard library benchmark summary, e.g. `Vector.sum average: 12.34ms`.
+    static ref STDLIB_RESULT: Regex = Regex::new(r"^(.+) average: ([\d.]+)ms$").unwrap();
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    pub score:           f64,
+    /// Measurement error, if reported.
+    pub error:           Option<f64>,
+    pub unit:            String,
+    /// False for the throughput benchmarks.
+    pub lower_is_better: bool,
+}
+
+impl Display for Measurement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.3}", self.score)?;
+        if let Some(error) = self.error {
+            write!(f, " ± {error:.3}")?;
+        }
+        write!(f, " {}", self.unit)
+    }
+}
+
+/// Results of a benchmark run, keyed by the benchmark names.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResults {
+    pub benchmarks: BTreeMap<String, Measurement>,
+}
+
+impl BenchmarkResults {
+    /// Collect the results from the output of the JMH benchmarks.
+    pub fn from_jmh_output(output: &str) -> Self {
+        let parse_number = |text: &str| text.replace(',', ".").parse::<f64>().ok();
+        let mut ret = Self::default();
+        for line in output.lines() {
+            if let Some(captures) = JMH_RESULT.captures(line.trim()) {
+                if let Some(score) = parse_number(&captures[3]) {
+                    let measurement = Measurement {
+                        score,
+                        error: captures.get(4).and_then(|error| parse_number(error.as_str())),
+                        unit: captures[5].into(),
+                        lower_is_better: &captures[2] != "thrpt",
+                    };
+                    ret.benchmarks.insert(captures[1].into(), measurement);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Collect the results from the output of the standard library benchmarks.
+    pub fn from_stdlib_output(output: &str) -> Self {
+        let mut ret = Self::default();
+        for line in output.lines() {
+            if let Some(captures) = STDLIB_RESULT.captures(line.trim()) {
+                if let Ok(score) = captures[2].parse() {
+                    let measurement = Measurement {
+                        score,
+                        error: None,
+                        unit: "ms".into(),
+                        lower_is_better: true,
+                    };
+                    ret.benchmarks.insert(captures[1].trim().into(), measurement);
+                }
+            }
+        }
+        ret
+    }
+
+    pub fn merge(&mut self, other: BenchmarkResults) {
+        self.benchmarks.extend(other.benchmarks);
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        path.read_to_json()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result {
+        ide_ci::fs::create_parent_dir_if_missing(&path)?;
+        path.write_as_json(self)
+    }
+}
+
+/// Where the baseline benchmark results come from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BaselineSource {
+    File(PathBuf),
+    /// Name of an artifact uploaded earlier in the same CI workflow run.
+    Artifact(String),
+}
+
+impl FromStr for BaselineSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.strip_prefix(ARTIFACT_PREFIX) {
+            Some("") => bail!("Missing artifact name in the benchmark baseline `{s}`."),
+            Some(name) => Ok(Self::Artifact(name.into())),
+            None => Ok(Self::File(s.into())),
+        }
+    }
+}
+
+impl Display for BaselineSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BaselineSource::File(path) => write!(f, "{}", path.display()),
+            BaselineSource::Artifact(name) => write!(f, "{ARTIFACT_PREFIX}{name}"),
+        }
+    }
+}
+
+impl BaselineSource {
+    pub async fn fetch(&self) -> Result<BenchmarkResults> {
+        match self {
+            BaselineSource::File(path) => BenchmarkResults::load(path),
+            BaselineSource::Artifact(name) => {
+                let tempdir = tempfile::tempdir()?;
+                let path = tempdir.path().join("baseline.json");
+                ide_ci::actions::artifacts::download_single_file_artifact(name, &path).await?;
+                BenchmarkResults::load(&path)
+            }
+        }
+        .with_context(|| format!("Failed to get the baseline benchmark results from {self}."))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Status {
+    Unchanged,
+    Improved,
+    Regressed,
+    /// Not present in the baseline.
+    Added,
+    /// Present only in the baseline.
+    Removed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub name:     String,
+    pub baseline: Option<Measurement>,
+    pub current:  Option<Measurement>,
+    /// Relative change of the score, in percent.
+    pub change:   Option<f64>,
+    pub status:   Status,
+}
+
+/// Current benchmark results compared against the baseline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub rows:      Vec<Row>,
+    /// Tolerance of the benchmark slowdown, in percent.
+    pub threshold: f64,
+}
+
+impl Comparison {
+    pub fn new(baseline: &BenchmarkResults, current: &BenchmarkResults, threshold: f64) -> Self {
+        let names = baseline.benchmarks.keys().chain(current.benchmarks.keys()).sorted().dedup();
+        let rows = names
+            .map(|name| {
+                let baseline = baseline.benchmarks.get(name).cloned();
+                let current = current.benchmarks.get(name).cloned();
+                let (change, status) = match (&baseline, &current) {
+                    (Some(baseline), Some(current)) if baseline.score != 0.0 => {
+                        let change = (current.score - baseline.score) / baseline.score * 100.0;
+                        let slowdown = if current.lower_is_better { change } else { -change };
+                        let status = if slowdown > threshold {
+                            Status::Regressed
+                        } else if slowdown < -threshold {
+                            Status::Improved
+                        } else {
+                            Status::Unchanged
+                        };
+                        (Some(change), status)
+                    }
+                    (Some(_), Some(_)) => (None, Status::Unchanged),
+                    (None, _) => (None, Status::Added),
+                    (_, None) => (None, Status::Removed),
+                };
+                Row { name: name.clone(), baseline, current, change, status }
+            })
+            .collect();
+        Self { rows, threshold }
+    }
+
+    pub fn regressions(&self) -> impl Iterator<Item = &Row> {
+        self.rows.iter().filter(|row| row.status == Status::Regressed)
+    }
+
+    /// Summary of the comparison as a Markdown table.
+    pub fn to_markdown(&self) -> String {
+        let show = |measurement: &Option<Measurement>| {
+            measurement.as_ref().map_or_else(|| "—".to_string(), |m| m.to_string())
+        };
+        let mut ret = String::from("| Benchmark | Baseline | Current | Change | Status |\n");
+        ret += "|---|---:|---:|---:|---|\n";
+        for row in &self.rows {
+            let change = row.change.map_or_else(|| "—".to_string(), |c| format!("{c:+.1}%"));
+            let status = match row.status {
+                Status::Regressed => format!("⚠️ {}", row.status),
+                status => status.to_string(),
+            };
+            ret += &format!(
+                "| {} | {} | {} | {change} | {status} |\n",
+                row.name,
+                show(&row.baseline),
+                show(&row.current)
+            );
+        }
+        let regressions = self.regressions().count();
+        ret += &format!(
+            "\n{regressions} of {} benchmarks regressed by more than {}%.\n",
+            self.rows.len(),
+            self.threshold
+        );
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let jmh = r#"
+[info] Benchmark                               Mode  Cnt     Score    Error  Units
+[info] AtomBenchmarks.benchGenerateList        avgt    5    12.345 ±  0.678  ms/op
+[info] CallableBenchmarks.benchSumTCOfromCall  thrpt   5  1234,500 ± 12,000  ops/s
+[info] StartupBenchmark.startup                  ss         5.000           s/op
+"#;
+        let results = BenchmarkResults::from_jmh_output(jmh);
+        assert_eq!(results.benchmarks.len(), 3);
+        let list = &results.benchmarks["AtomBenchmarks.benchGenerateList"];
+        assert_eq!(list.score, 12.345);
+        assert_eq!(list.error, Some(0.678));
+        assert_eq!(list.unit, "ms/op");
+        assert!(list.lower_is_better);
+        let call = &results.benchmarks["CallableBenchmarks.benchSumTCOfromCall"];
+        assert_eq!(call.score, 1234.5);
+        assert!(!call.lower_is_better);
+        assert_eq!(results.benchmarks["StartupBenchmark.startup"].error, None);
+
+        let stdlib = "Vector.sum/iteration:1: 15.2ms\nVector.sum average: 12.5ms\n";
+        let results = BenchmarkResults::from_stdlib_output(stdlib);
+        assert_eq!(results.benchmarks.len(), 1);
+        assert_eq!(results.benchmarks["Vector.sum"].score, 12.5);
+
+        assert_eq!(
+            "artifact:bench".parse::<BaselineSource>().unwrap(),
+            BaselineSource::Artifact("bench".into())
+        );
+        assert_eq!(
+            "bench.json".parse::<BaselineSource>().unwrap(),
+            BaselineSource::File("bench.json".into())
+        );
+        assert!("artifact:".parse::<BaselineSource>().is_err());
+    }
+
+    #[test]
+    fn comparing() {
+        let results = |entries: &[(&str, f64, bool)]| BenchmarkResults {
+            benchmarks: entries
+                .iter()
+                .map(|(name, score, lower_is_better)| {
+                    let unit = if *lower_is_better { "ms/op" } else { "ops/s" };
+                    let measurement = Measurement {
+                        score:           *score,
+                        error:           None,
+                        unit:            unit.into(),
+                        lower_is_better: *lower_is_better,
+                    };
+                    (name.to_string(), measurement)
+                })
+                .collect(),
+        };
+        let baseline = results(&[("fast", 10.0, true), ("many", 100.0, false), ("old", 1.0, true)]);
+        let current = results(&[("fast", 12.0, true), ("many", 150.0, false), ("new", 1.0, true)]);
+        let comparison = Comparison::new(&baseline, &current, 10.0);
+        let statuses = comparison.rows.iter().map(|row| (row.name.as_str(), row.status));
+        assert_eq!(statuses.collect_vec(), [
+            ("fast", Status::Regressed),
+            ("many", Status::Improved),
+            ("new", Status::Added),
+            ("old", Status::Removed),
+        ]);
+        assert_eq!(comparison.regressions().count(), 1);
+
+        let table = comparison.to_markdown();
+        assert!(table.contains("| fast | 10.000 ms/op | 12.000 ms/op | +20.0% | ⚠️ regressed |"));
+        assert!(table.contains("| new | — | 1.000 ms/op | — | added |"));
+        assert!(table.ends_with("1 of 4 benchmarks regressed by more than 10%.\n"));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::prelude::*;
 
+use byte_unit::Byte;
 use ide_ci::actions::workflow::is_in_env;
 use ide_ci::env::Variable;
 use sysinfo::SystemExt;
@@ -7,32 +8,41 @@ use sysinfo::SystemExt;
 use crate::engine::download_project_templates;
 use crate::engine::env;
 use crate::engine::BuildConfigurationResolved;
-use crate::engine::BuildMode;
 use crate::engine::BuiltArtifacts;
 use crate::engine::ComponentPathExt;
 use crate::engine::Operation;
 use crate::engine::ReleaseCommand;
 use crate::engine::ReleaseOperation;
 use crate::engine::FLATC_VERSION;
-use crate::engine::PARALLEL_ENSO_TESTS;
 use crate::get_graal_version;
 use crate::get_java_major_version;
 use crate::paths::cache_directory;
+use crate::paths::ComponentPaths;
 use crate::paths::Paths;
 use crate::project::ProcessWrapper;
 use crate::retrieve_github_access_token;
 
+use crate::engine::benchmark::BenchmarkResults;
+use crate::engine::benchmark::Comparison;
+use crate::engine::bundle;
 use crate::engine::bundle::Bundle;
 use crate::engine::sbt::verify_generated_package;
+use crate::engine::step::Decision;
+use crate::engine::step::Plan;
+use crate::engine::step::Step;
+use crate::engine::step::StepSelection;
+use crate::engine::verify::Expectations;
 use crate::enso::BuiltEnso;
 use crate::enso::IrCaches;
 
 use ide_ci::goodie::GoodieDatabase;
 use ide_ci::goodies;
 use ide_ci::goodies::graalvm;
+use ide_ci::models::test_report::TestReport;
 use ide_ci::platform::DEFAULT_SHELL;
 use ide_ci::program::with_cwd::WithCwd;
 use ide_ci::programs::graal;
+use ide_ci::programs::sbt;
 use ide_ci::programs::Flatc;
 use ide_ci::programs::Git;
 use ide_ci::programs::Sbt;
@@ -45,6 +55,7 @@ pub struct RunContext {
     pub paths:     Paths,
     pub goodies:   GoodieDatabase,
     pub operation: Operation,
+    pub steps:     StepSelection,
 }
 
 impl RunContext {
@@ -107,7 +118,12 @@ impl RunContext {
             }
 
             ide_ci::programs::Conda
-                .call_args(["install", "-y", "--freeze-installed", "flatbuffers=1.12.0"])
+                .call_args([
+                    "install",
+                    "-y",
+                    "--freeze-installed",
+                    &format!("flatbuffers={FLATC_VERSION}"),
+                ])
                 .await?;
             ide_ci::programs::Flatc.lookup()?;
         }
@@ -158,10 +174,76 @@ impl RunContext {
         Ok(())
     }
 
+    /// Load the JUnit reports written by the sbt tests.
+    pub fn sbt_test_report(&self) -> Result<TestReport> {
+        let mut report = TestReport::default();
+        for pattern in self.paths.sbt_test_reports() {
+            report.merge(TestReport::from_junit_glob(pattern.as_str())?);
+        }
+        Ok(report)
+    }
+
+    /// Remove the sbt test reports, so they won't be mixed with the ones of the next run.
+    fn remove_sbt_test_reports(&self) -> Result {
+        for pattern in self.paths.sbt_test_reports() {
+            for report in glob::glob(pattern.as_str())? {
+                ide_ci::fs::remove_file_if_exists(report?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the sbt tests, rerunning the failed suites up to the configured number of times.
+    ///
+    /// The results of all the attempts are merged into the `report`, even if the tests fail.
+    async fn run_sbt_tests(&self, sbt: &WithCwd<Sbt>, report: &mut TestReport) -> Result {
+        const SETUP: &str = "set Global / parallelExecution := false";
+        self.remove_sbt_test_reports()?;
+        let mut result = sbt.call_arg(format!("{SETUP}; test")).await;
+        let mut sbt_report = self.sbt_test_report()?;
+        for attempt in 1..=self.config.test_retries {
+            if result.is_ok() {
+                break;
+            }
+            let failed_suites = sbt_report.failed_suites().into_iter().join(" ");
+            if failed_suites.is_empty() {
+                // Nothing to rerun, e.g. the tests did not compile.
+                break;
+            }
+            let retries = self.config.test_retries;
+            warn!("Rerunning failed sbt test suites: {failed_suites} (retry {attempt}/{retries}).");
+            self.remove_sbt_test_reports()?;
+            result = sbt.call_arg(format!("{SETUP}; testOnly {failed_suites}")).await;
+            sbt_report.apply_rerun(self.sbt_test_report()?);
+        }
+        report.merge(sbt_report);
+        result
+    }
+
     pub async fn build(&self) -> Result<BuiltArtifacts> {
+        // Validated only here, after all the overrides, e.g. the bundles enabled for an upload.
+        self.config.validate()?;
+        let mut report = TestReport::default();
+        let result =
+            self.build_artifacts(&mut report).instrument(info_span!("Building the engine.")).await;
+        // The test results are reported even if the build failed, as that is when they matter most.
+        if self.config.test_scala || self.config.test_standard_library {
+            if let Err(e) = crate::engine::report_tests(&report, &self.paths) {
+                warn!("Failed to report the test results: {e:?}");
+            }
+        }
+        result
+    }
+
+    /// Decide which of the build steps are run, given the configuration and the step selection.
+    pub fn plan(&self) -> Result<Plan> {
+        Plan::new(&self.config, &self.paths, &self.steps)
+    }
+
+    async fn build_artifacts(&self, report: &mut TestReport) -> Result<BuiltArtifacts> {
         let mut ret = BuiltArtifacts::default();
 
-        self.prepare_build_env().await?;
+        self.prepare_build_env().instrument(info_span!("Preparing the build environment.")).await?;
         if ide_ci::ci::run_in_ci() {
             // On CI we remove IR caches. They might contain invalid or outdated data, as are using
             // engine version as part of the key. As such, any change made to engine that does not
@@ -170,40 +252,203 @@ impl RunContext {
             ide_ci::fs::remove_dir_if_exists(cache_directory())?;
         }
 
-        if self.config.test_standard_library {
+        let plan = self.plan()?;
+        info!("Engine build steps:\n{plan}");
+        let stdlib_tests = [Step::TestStandardLibrary, Step::TestStandardLibraryWithIrCaches];
+        if plan.to_run().any(|step| stdlib_tests.contains(&step)) {
             // If we run tests, make sure that old and new results won't end up mixed together.
             ide_ci::fs::reset_dir(&self.paths.test_results)?;
         }
 
-        let git = Git::new(&self.paths.repo_root);
-        if self.config.clean_repo {
-            git.cmd()?.nice_clean().run_ok().await?;
-            let lib_src = PathBuf::from_iter(["distribution", "lib"]);
-            git.args(["checkout"])?.arg(lib_src).run_ok().await?;
+        for (step, decision) in plan.steps {
+            match decision {
+                Decision::Run => {
+                    // The marker is removed first, so the step is not considered complete if it
+                    // fails.
+                    ide_ci::fs::remove_file_if_exists(step.marker_path(&self.paths))?;
+                    let key = step.key(&self.config, &self.paths)?;
+                    let span = info_span!("Running build step.", %step);
+                    self.run_step(step, report, &mut ret).instrument(span).await?;
+                    key.write(step, &self.paths)?;
+                }
+                Decision::UpToDate | Decision::NotSelected => {
+                    debug!("Skipping build step {step}: {decision}.");
+                    self.reuse_step_outputs(step, &mut ret);
+                }
+                Decision::Disabled => {}
+            }
         }
+        self.expectations()?
+            .verify(&ret)
+            .instrument(info_span!("Verifying the built artifacts."))
+            .await?;
+        Ok(ret)
+    }
 
-        // Download Project Template Files
-        let client = reqwest::Client::new();
-        download_project_templates(client.clone(), self.paths.repo_root.clone()).await?;
+    /// What the artifacts built in this context should look like.
+    pub fn expectations(&self) -> Result<Expectations> {
+        let build_sbt_content = ide_ci::fs::read_to_string(self.paths.build_sbt())?;
+        Ok(Expectations {
+            version:       self.paths.version().clone(),
+            graal_version: get_graal_version(&build_sbt_content)?,
+        })
+    }
 
+    /// Fill in the artifacts that a skipped step would have built, if they are present.
+    fn reuse_step_outputs(&self, step: Step, ret: &mut BuiltArtifacts) {
+        let existing = |paths: &ComponentPaths| paths.dir.exists().then(|| paths.clone());
+        match step {
+            Step::BuildPackages => {
+                if self.config.build_engine_package() {
+                    ret.packages.engine = existing(&self.paths.engine);
+                }
+                if self.config.build_project_manager_package() {
+                    ret.packages.project_manager = existing(&self.paths.project_manager);
+                }
+                if self.config.build_launcher_package() {
+                    ret.packages.launcher = existing(&self.paths.launcher);
+                }
+            }
+            Step::BundleLauncher => {
+                ret.bundles.launcher = existing(&bundle::Launcher::suggest_paths(&self.paths));
+            }
+            Step::BundleProjectManager => {
+                ret.bundles.project_manager =
+                    existing(&bundle::ProjectManager::suggest_paths(&self.paths));
+            }
+            _ => {}
+        }
+    }
+
+    async fn run_step(
+        &self,
+        step: Step,
+        report: &mut TestReport,
+        ret: &mut BuiltArtifacts,
+    ) -> Result {
         let sbt = WithCwd::new(Sbt, &self.paths.repo_root);
+        let enso = BuiltEnso { paths: self.paths.clone() };
+        match step {
+            Step::CleanRepo => {
+                let git = Git::new(&self.paths.repo_root);
+                git.cmd()?.nice_clean().run_ok().await?;
+                let lib_src = PathBuf::from_iter(["distribution", "lib"]);
+                git.args(["checkout"])?.arg(lib_src).run_ok().await?;
+            }
+            Step::DownloadTemplates => {
+                let client = reqwest::Client::new();
+                download_project_templates(client, self.paths.repo_root.clone()).await?;
+            }
+            Step::Bootstrap => {
+                debug!("Bootstrapping Enso project.");
+                sbt.call_arg("bootstrap").await?;
+            }
+            Step::BuildPackages => self.build_packages(&sbt, ret).await?,
+            Step::TestScala => {
+                self.run_sbt_tests(&sbt, report)
+                    .instrument(info_span!("Running sbt tests."))
+                    .await?;
+            }
+            Step::GenerateDocs => {
+                // Build the docs from standard library sources.
+                sbt.call_arg("docs-generator/run").await?;
+            }
+            Step::BuildJsParser => {
+                // Build the Parser JS Bundle
+                sbt.call_arg("syntaxJS/fullOptJS").await?;
+                ide_ci::fs::copy_to(
+                    self.paths.target.join("scala-parser.js"),
+                    self.paths.target.join("parser-upload"),
+                )?;
+            }
+            Step::TestStandardLibrary | Step::TestStandardLibraryWithIrCaches => {
+                let ir_caches =
+                    if step == Step::TestStandardLibrary { IrCaches::No } else { IrCaches::Yes };
+                enso.run_tests_with_retries(
+                    &default(),
+                    ir_caches,
+                    self.config.test_parallelism,
+                    self.config.test_retries,
+                    report,
+                )
+                .instrument(info_span!("Running standard library tests.", ?ir_caches))
+                .await?;
+            }
+            Step::CompileStandardLibraries => {
+                let std_libs = self.paths.engine.dir.join("lib").join("Standard");
+                // Compile the Standard Libraries (Unix)
+                debug!("Compiling standard libraries under {}", std_libs.display());
+                for entry in ide_ci::fs::read_dir(&std_libs)? {
+                    let entry = entry?;
+                    let target = entry.path().join(self.paths.version().to_string());
+                    let span = info_span!("Compiling standard library.", library = %entry.path().display());
+                    enso.compile_lib(target)?.run_ok().instrument(span).await?;
+                }
+            }
+            Step::RunBenchmarks => self.run_benchmarks(&sbt, &enso).await?,
+            Step::VerifyPackages => self.verify_packages(&sbt).await?,
+            Step::UploadEngineArtifacts => {
+                if TARGET_OS == OS::Linux && ide_ci::ci::run_in_ci() {
+                    self.paths.upload_edition_file_artifact().await?;
+                }
 
+                let schema_dir = self.paths.repo_root.join_iter([
+                    "engine",
+                    "language-server",
+                    "src",
+                    "main",
+                    "schema",
+                ]);
+                if is_in_env() {
+                    ide_ci::actions::artifacts::upload_compressed_directory(
+                        &schema_dir,
+                        "fbs-schema",
+                    )
+                    .await?;
+                }
+            }
+            Step::BundleLauncher => {
+                ret.bundles.launcher = Some(bundle::Launcher::create(&self.paths).await?);
+            }
+            Step::BundleProjectManager => {
+                ret.bundles.project_manager =
+                    Some(bundle::ProjectManager::create(&self.paths).await?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the sbt commands one by one in a new session, which is finished afterwards.
+    async fn in_sbt_session(&self, commands: impl IntoIterator<Item = &str>) -> Result {
+        let mut session = if self.config.sbt_server {
+            sbt::Session::start(&self.paths.repo_root).await
+        } else {
+            sbt::Session::process(&self.paths.repo_root)
+        };
+        let mut result = Ok(());
+        for command in commands {
+            result = session.call_arg(command).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        let finished = session.finish().await;
+        result.and(finished)
+    }
+
+    async fn build_packages(&self, sbt: &WithCwd<Sbt>, ret: &mut BuiltArtifacts) -> Result {
         let mut system = sysinfo::System::new();
         system.refresh_memory();
         debug!("Total memory: {}", system.total_memory());
         debug!("Available memory: {}", system.available_memory());
         debug!("Used memory: {}", system.used_memory());
         debug!("Free memory: {}", system.free_memory());
-
-        // Build packages.
-        debug!("Bootstrapping Enso project.");
-        sbt.call_arg("bootstrap").await?;
-
         // If we have much memory, we can try building everything in a single batch. Reducing number
         // of SBT invocations significantly helps build time. However, it is more memory heavy, so
         // we don't want to call this in environments like GH-hosted runners.
-        let github_hosted_macos_memory = 15_032_385;
-        if system.total_memory() > github_hosted_macos_memory {
+        // Note that `sysinfo` reports memory in kilobytes.
+        let total_memory = Byte::from_bytes(u128::from(system.total_memory()) * 1024);
+        if total_memory > self.config.sbt_batch_memory_threshold {
             let mut tasks = vec![];
 
             if self.config.build_engine_package() {
@@ -242,162 +487,143 @@ impl RunContext {
             let build_stuff = Sbt::concurrent_tasks(tasks);
             sbt.call_arg(build_stuff).await?;
         } else {
-            // Compile
-            sbt.call_arg("compile").await?;
-
-            // Build the Runner & Runtime Uberjars
-            sbt.call_arg("engine-runner/assembly").await?;
+            // Build the Runner & Runtime Uberjars, and the Launcher and PM ones for their native
+            // images.
+            self.in_sbt_session([
+                "compile",
+                "engine-runner/assembly",
+                "launcher/assembly",
+                "project-manager/assembly",
+            ])
+            .await?;
+
+            // The native images are built in separate sbt processes, so their memory usage can be
+            // limited. The sbt server is not running meanwhile, so it does not take the memory
+            // and the processes do not compete for the build directory.
 
             // Build the Launcher Native Image
-            sbt.call_arg("launcher/assembly").await?;
             sbt.call_args(["--mem", "1536", "launcher/buildNativeImage"]).await?;
 
             // Build the PM Native Image
-            sbt.call_arg("project-manager/assembly").await?;
             sbt.call_args(["--mem", "1536", "project-manager/buildNativeImage"]).await?;
 
-            // Prepare Launcher Distribution
-            //create_launcher_package(&paths)?;
-            sbt.call_arg("buildLauncherDistribution").await?;
-
-            // Prepare Engine Distribution
-            sbt.call_arg("buildEngineDistribution").await?;
-
-            // Prepare Project Manager Distribution
-            sbt.call_arg("buildProjectManagerDistribution").await?;
-
+            // Prepare Launcher, Engine and Project Manager Distributions
+            let mut commands = vec![
+                "buildLauncherDistribution",
+                "buildEngineDistribution",
+                "buildProjectManagerDistribution",
+            ];
             if self.config.benchmark_compilation {
-                // Check Runtime Benchmark Compilation
-                sbt.call_arg("runtime/Benchmark/compile").await?;
-
-                // Check Language Server Benchmark Compilation
-                sbt.call_arg("language-server/Benchmark/compile").await?;
-
-                // Check Searcher Benchmark Compilation
-                sbt.call_arg("searcher/Benchmark/compile").await?;
+                // Check Runtime, Language Server and Searcher Benchmark Compilation
+                commands.extend([
+                    "runtime/Benchmark/compile",
+                    "language-server/Benchmark/compile",
+                    "searcher/Benchmark/compile",
+                ]);
             }
+            self.in_sbt_session(commands).await?;
         }
-        if self.config.test_scala {
-            // Test Enso
-            sbt.call_arg("set Global / parallelExecution := false; test").await?;
-        }
+        Ok(())
+    }
 
-        // === Build Distribution ===
-        if self.config.mode == BuildMode::Development {
-            // FIXME [mwu]
-            //  docs-generator fails on Windows because it can't understand non-Unix-style paths.
-            if TARGET_OS != OS::Windows {
-                // Build the docs from standard library sources.
-                sbt.call_arg("docs-generator/run").await?;
-            }
-        }
+    /// Run the runtime and the standard library benchmarks, then compare their results with the
+    /// baseline.
+    async fn run_benchmarks(&self, sbt: &WithCwd<Sbt>, enso: &BuiltEnso) -> Result {
+        let results_path = self.paths.benchmark_results();
+        // Must be read before the results of the previous run are overwritten.
+        let baseline = if std::env::var_os(env::BenchmarkBaseline::NAME).is_some() {
+            Some(env::BenchmarkBaseline.fetch()?.fetch().await?)
+        } else if results_path.exists() {
+            Some(BenchmarkResults::load(&results_path)?)
+        } else {
+            None
+        };
 
-        if self.config.build_js_parser {
-            // Build the Parser JS Bundle
-            sbt.call_arg("syntaxJS/fullOptJS").await?;
-            ide_ci::fs::copy_to(
-                self.paths.target.join("scala-parser.js"),
-                self.paths.target.join("parser-upload"),
-            )?;
+        let logs = self.paths.target.join("benchmarks");
+        let runtime_output = sbt.cmd()?.arg("runtime/bench").run_stdout().await?;
+        ide_ci::fs::write(logs.join("runtime.log"), &runtime_output)?;
+        let mut results = BenchmarkResults::from_jmh_output(&runtime_output);
+        let stdlib_output = enso
+            .cmd()?
+            .arg(IrCaches::Yes)
+            .arg("--run")
+            .arg(self.paths.stdlib_test("Benchmarks"))
+            .run_stdout()
+            .await?;
+        ide_ci::fs::write(logs.join("stdlib.log"), &stdlib_output)?;
+        results.merge(BenchmarkResults::from_stdlib_output(&stdlib_output));
+        ensure!(!results.benchmarks.is_empty(), "No benchmark results found in the output.");
+        results.save(&results_path)?;
+        if is_in_env() {
+            let artifact_name = format!("benchmark-results-{TARGET_OS}");
+            ide_ci::actions::artifacts::upload_single_file(&results_path, artifact_name).await?;
         }
 
-
-        let enso = BuiltEnso { paths: self.paths.clone() };
-        if self.config.test_standard_library {
-            // Prepare Engine Test Environment
-            if let Ok(gdoc_key) = std::env::var("GDOC_KEY") {
-                let google_api_test_data_dir =
-                    self.paths.repo_root.join("test").join("Google_Api_Test").join("data");
-                ide_ci::fs::create_dir_if_missing(&google_api_test_data_dir)?;
-                ide_ci::fs::write(google_api_test_data_dir.join("secret.json"), &gdoc_key)?;
-            }
-            enso.run_tests(IrCaches::No, PARALLEL_ENSO_TESTS).await?;
+        let baseline = match baseline {
+            Some(baseline) => baseline,
+            None => {
+                info!("No baseline benchmark results, skipping the comparison.");
+                return Ok(());
+            }
+        };
+        let threshold = self.config.benchmark_regression_threshold;
+        let comparison = Comparison::new(&baseline, &results, threshold);
+        let table = comparison.to_markdown();
+        ide_ci::fs::write(self.paths.benchmark_comparison(), &table)?;
+        println!("{table}");
+        let regressions = comparison.regressions().map(|row| &row.name).join(", ");
+        if regressions.is_empty() {
+            Ok(())
+        } else if self.config.fail_on_benchmark_regression {
+            bail!("Benchmarks regressed by more than {threshold}%: {regressions}.")
+        } else {
+            warn!("Benchmarks regressed by more than {threshold}%: {regressions}.");
+            Ok(())
         }
+    }
+
+    /// Verify License Packages in Distributions
+    async fn verify_packages(&self, sbt: &WithCwd<Sbt>) -> Result {
+        /*  refversion=${{ env.ENSO_VERSION }}
+            binversion=${{ env.DIST_VERSION }}
+            engineversion=$(${{ env.ENGINE_DIST_DIR }}/bin/enso --version --json | jq -r '.version')
+            test $binversion = $refversion || (echo "Tag version $refversion and the launcher version $binversion do not match" && false)
+            test $engineversion = $refversion || (echo "Tag version $refversion and the engine version $engineversion do not match" && false)
+        */
 
         if self.config.build_engine_package() {
-            let std_libs = self.paths.engine.dir.join("lib").join("Standard");
-            // Compile the Standard Libraries (Unix)
-            debug!("Compiling standard libraries under {}", std_libs.display());
-            for entry in ide_ci::fs::read_dir(&std_libs)? {
-                let entry = entry?;
-                let target = entry.path().join(self.paths.version().to_string());
-                enso.compile_lib(target)?.run_ok().await?;
-            }
+            verify_generated_package(sbt, "engine", &self.paths.engine.dir).await?;
         }
-
-        if self.config.test_standard_library {
-            enso.run_tests(IrCaches::Yes, PARALLEL_ENSO_TESTS).await?;
+        if self.config.build_launcher_package() {
+            verify_generated_package(sbt, "launcher", &self.paths.launcher.dir).await?;
         }
-
-        // Verify License Packages in Distributions
-        // FIXME apparently this does not work on Windows due to some CRLF issues?
-        if self.config.mode == BuildMode::NightlyRelease && TARGET_OS != OS::Windows {
-            /*  refversion=${{ env.ENSO_VERSION }}
-                binversion=${{ env.DIST_VERSION }}
-                engineversion=$(${{ env.ENGINE_DIST_DIR }}/bin/enso --version --json | jq -r '.version')
-                test $binversion = $refversion || (echo "Tag version $refversion and the launcher version $binversion do not match" && false)
-                test $engineversion = $refversion || (echo "Tag version $refversion and the engine version $engineversion do not match" && false)
-            */
-
-            if self.config.build_engine_package() {
-                verify_generated_package(&sbt, "engine", &self.paths.engine.dir).await?;
-            }
-            if self.config.build_launcher_package() {
-                verify_generated_package(&sbt, "launcher", &self.paths.launcher.dir).await?;
-            }
-            if self.config.build_project_manager_package() {
-                verify_generated_package(&sbt, "project-manager", &self.paths.project_manager.dir)
-                    .await?;
-            }
-            if self.config.build_engine_package {
-                for libname in ["Base", "Table", "Image", "Database"] {
-                    verify_generated_package(
-                        &sbt,
-                        libname,
-                        self.paths
-                            .engine
-                            .dir
-                            .join_iter(["lib", "Standard"])
-                            .join(libname)
-                            .join(self.paths.version().to_string()),
-                    )
-                    .await?;
-                }
-            }
+        if self.config.build_project_manager_package() {
+            verify_generated_package(sbt, "project-manager", &self.paths.project_manager.dir)
+                .await?;
         }
-
         if self.config.build_engine_package {
-            if TARGET_OS == OS::Linux && ide_ci::ci::run_in_ci() {
-                self.paths.upload_edition_file_artifact().await?;
-            }
-
-            let schema_dir = self.paths.repo_root.join_iter([
-                "engine",
-                "language-server",
-                "src",
-                "main",
-                "schema",
-            ]);
-            if is_in_env() {
-                ide_ci::actions::artifacts::upload_compressed_directory(&schema_dir, "fbs-schema")
-                    .await?;
+            for libname in ["Base", "Table", "Image", "Database"] {
+                verify_generated_package(
+                    sbt,
+                    libname,
+                    self.paths
+                        .engine
+                        .dir
+                        .join_iter(["lib", "Standard"])
+                        .join(libname)
+                        .join(self.paths.version().to_string()),
+                )
+                .await?;
             }
         }
-
-        if self.config.build_launcher_bundle {
-            ret.bundles.launcher =
-                Some(crate::engine::bundle::Launcher::create(&self.paths).await?);
-        }
-
-        if self.config.build_project_manager_bundle {
-            ret.bundles.project_manager =
-                Some(crate::engine::bundle::ProjectManager::create(&self.paths).await?);
-        }
-
-        Ok(ret)
+        Ok(())
     }
 
     pub async fn execute(&self) -> Result {
+        if self.steps.list_steps {
+            print!("{}", self.plan()?);
+            return Ok(());
+        }
         match &self.operation {
             Operation::Release(ReleaseOperation { command, repo }) => match command {
                 ReleaseCommand::Upload => {
@@ -425,7 +651,9 @@ impl RunContext {
             },
             Operation::Run(run) => {
                 // Build environment preparations.
-                self.prepare_build_env().await?;
+                self.prepare_build_env()
+                    .instrument(info_span!("Preparing the build environment."))
+                    .await?;
                 let mut run = run.command_pieces.iter();
                 if let Some(program) = run.next() {
                     debug!("Spawning program {}.", program.to_str().unwrap());
@@ -10,6 +10,14 @@ impl Variable for CiTestTimeFactor {
     type Value = usize;
 }
 
+/// Baseline for the benchmark results: a path to the results file or `artifact:<name>` for a CI
+/// artifact. If not set, the results of the previous local run are used.
+pub struct BenchmarkBaseline;
+impl Variable for BenchmarkBaseline {
+    const NAME: &'static str = "ENSO_BENCHMARK_BASELINE";
+    type Value = crate::engine::benchmark::BaselineSource;
+}
+
 pub struct CiFlakyTestEnable;
 impl Variable for CiFlakyTestEnable {
     const NAME: &'static str = "CI_TEST_FLAKY_ENABLE";
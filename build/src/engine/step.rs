@@ -0,0 +1,363 @@
+//! The engine build as a sequence of named steps.
+//!
+//! Each completed step leaves a marker in the build directory, keyed by the digest of its inputs.
+//! This allows resuming an interrupted build, skipping the steps that are still up to date.
+
+use crate::prelude::*;
+
+use crate::engine::bundle;
+use crate::engine::bundle::Bundle;
+use crate::engine::BuildConfigurationFlags;
+use crate::engine::BuildMode;
+use crate::paths::Paths;
+use strum::IntoEnumIterator;
+
+
+
+/// Names of directories that are never considered as step inputs, as they contain build outputs
+/// and caches.
+pub const SKIPPED_INPUT_DIRS: [&str; 4] = ["target", ".enso", "node_modules", ".git"];
+
+/// Repository paths with the sources built by sbt.
+pub const SBT_SOURCES: [&str; 6] =
+    ["build.sbt", "project", "engine", "lib", "std-bits", "distribution"];
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    clap::ArgEnum,
+    strum::Display,
+    strum::EnumIter,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Step {
+    CleanRepo,
+    DownloadTemplates,
+    Bootstrap,
+    BuildPackages,
+    TestScala,
+    GenerateDocs,
+    BuildJsParser,
+    TestStandardLibrary,
+    CompileStandardLibraries,
+    TestStandardLibraryWithIrCaches,
+    RunBenchmarks,
+    VerifyPackages,
+    UploadEngineArtifacts,
+    BundleLauncher,
+    BundleProjectManager,
+}
+
+impl Step {
+    pub fn description(self) -> &'static str {
+        match self {
+            Step::CleanRepo => "Clean the repository.",
+            Step::DownloadTemplates => "Download the project templates.",
+            Step::Bootstrap => "Bootstrap the sbt project.",
+            Step::BuildPackages => "Compile and build the distribution packages.",
+            Step::TestScala => "Run the Scala tests.",
+            Step::GenerateDocs => "Generate the standard library docs.",
+            Step::BuildJsParser => "Build the JS parser bundle.",
+            Step::TestStandardLibrary => "Run the standard library tests.",
+            Step::CompileStandardLibraries => "Compile the standard libraries' IR caches.",
+            Step::TestStandardLibraryWithIrCaches =>
+                "Run the standard library tests with the IR caches.",
+            Step::RunBenchmarks => "Run the benchmarks and compare them against the baseline.",
+            Step::VerifyPackages => "Verify the generated packages.",
+            Step::UploadEngineArtifacts => "Upload the edition file and the schema.",
+            Step::BundleLauncher => "Create the launcher bundle.",
+            Step::BundleProjectManager => "Create the project manager bundle.",
+        }
+    }
+
+    /// Whether the step is part of the build with the given configuration.
+    pub fn is_enabled(self, config: &BuildConfigurationFlags) -> bool {
+        match self {
+            Step::CleanRepo => config.clean_repo,
+            Step::DownloadTemplates | Step::Bootstrap | Step::BuildPackages => true,
+            Step::TestScala => config.test_scala,
+            // FIXME [mwu]
+            //  docs-generator fails on Windows because it can't understand non-Unix-style paths.
+            Step::GenerateDocs => config.mode == BuildMode::Development && TARGET_OS != OS::Windows,
+            Step::BuildJsParser => config.build_js_parser,
+            Step::TestStandardLibrary | Step::TestStandardLibraryWithIrCaches =>
+                config.test_standard_library,
+            Step::CompileStandardLibraries => config.build_engine_package(),
+            Step::RunBenchmarks => config.run_benchmarks,
+            // FIXME apparently this does not work on Windows due to some CRLF issues?
+            Step::VerifyPackages =>
+                config.mode == BuildMode::NightlyRelease && TARGET_OS != OS::Windows,
+            Step::UploadEngineArtifacts => config.build_engine_package,
+            Step::BundleLauncher => config.build_launcher_bundle,
+            Step::BundleProjectManager => config.build_project_manager_bundle,
+        }
+    }
+
+    /// Files and directories read by the step, other than the outputs of the preceding steps.
+    pub fn inputs(self, paths: &Paths) -> Vec<PathBuf> {
+        let in_repo =
+            |relative: &[&str]| relative.iter().map(|p| paths.repo_root.join(p)).collect();
+        match self {
+            Step::Bootstrap => in_repo(&["build.sbt", "project"]),
+            Step::BuildPackages => in_repo(&SBT_SOURCES),
+            Step::TestStandardLibrary | Step::TestStandardLibraryWithIrCaches => in_repo(&["test"]),
+            Step::RunBenchmarks => in_repo(&["test/Benchmarks"]),
+            _ => default(),
+        }
+    }
+
+    /// Files and directories created by the step. The step is not up to date if any is missing.
+    pub fn outputs(self, config: &BuildConfigurationFlags, paths: &Paths) -> Vec<PathBuf> {
+        match self {
+            Step::DownloadTemplates => {
+                let resources = paths.repo_root.join_iter(["lib/scala/pkg/src/main/resources"]);
+                ["orders", "restaurants", "stargazers"].map(|name| resources.join(name)).to_vec()
+            }
+            Step::BuildPackages => {
+                let mut ret = vec![];
+                if config.build_engine_package() {
+                    ret.push(paths.engine.dir.clone());
+                }
+                if config.build_project_manager_package() {
+                    ret.push(paths.project_manager.dir.clone());
+                }
+                if config.build_launcher_package() {
+                    ret.push(paths.launcher.dir.clone());
+                }
+                ret
+            }
+            Step::BuildJsParser => vec![paths.target.join("parser-upload")],
+            Step::RunBenchmarks => vec![paths.benchmark_results()],
+            Step::BundleLauncher => vec![bundle::Launcher::suggest_paths(paths).dir],
+            Step::BundleProjectManager => vec![bundle::ProjectManager::suggest_paths(paths).dir],
+            _ => default(),
+        }
+    }
+
+    /// The configuration flags that affect the step's results, formatted for its [`Marker`].
+    ///
+    /// Flags that only change how the step is performed (like the test retries or the sbt server)
+    /// are left out, so changing them does not make the completed steps rerun.
+    pub fn relevant_configuration(self, config: &BuildConfigurationFlags) -> String {
+        let flag = |name: &str, value: &dyn Debug| format!("{name}: {value:?}");
+        let mode = || flag("mode", &config.mode);
+        let packages = || {
+            [
+                flag("build_engine_package", &config.build_engine_package()),
+                flag("build_project_manager_package", &config.build_project_manager_package()),
+                flag("build_launcher_package", &config.build_launcher_package()),
+            ]
+        };
+        let flags: Vec<String> = match self {
+            Step::CleanRepo | Step::DownloadTemplates | Step::UploadEngineArtifacts => vec![],
+            Step::Bootstrap
+            | Step::TestScala
+            | Step::GenerateDocs
+            | Step::BuildJsParser
+            | Step::TestStandardLibrary
+            | Step::CompileStandardLibraries
+            | Step::TestStandardLibraryWithIrCaches
+            | Step::BundleLauncher
+            | Step::BundleProjectManager => vec![mode()],
+            Step::BuildPackages => once(mode())
+                .chain(packages())
+                .chain([flag("benchmark_compilation", &config.benchmark_compilation)])
+                .collect(),
+            Step::RunBenchmarks => vec![
+                mode(),
+                flag("benchmark_regression_threshold", &config.benchmark_regression_threshold),
+                flag("fail_on_benchmark_regression", &config.fail_on_benchmark_regression),
+            ],
+            Step::VerifyPackages => packages()
+                .into_iter()
+                .chain([flag("verify_standard_libraries", &config.build_engine_package)])
+                .collect(),
+        };
+        flags.join(", ")
+    }
+
+    /// Identifies the state of the step's inputs. If it is the same as when the step last
+    /// completed, the step does not need to be rerun.
+    pub fn key(self, config: &BuildConfigurationFlags, paths: &Paths) -> Result<Marker> {
+        Ok(Marker {
+            configuration: self.relevant_configuration(config),
+            inputs:        ide_ci::fs::digest_contents(self.inputs(paths), &SKIPPED_INPUT_DIRS)?,
+        })
+    }
+
+    pub fn marker_path(self, paths: &Paths) -> PathBuf {
+        paths.build_step_markers().join(format!("{self}.json"))
+    }
+
+    /// Whether the step has completed with the same inputs before and its outputs are present.
+    pub fn is_up_to_date(self, config: &BuildConfigurationFlags, paths: &Paths) -> Result<bool> {
+        let marker_path = self.marker_path(paths);
+        if !marker_path.exists() || !self.outputs(config, paths).iter().all(|p| p.exists()) {
+            return Ok(false);
+        }
+        // A marker that can't be read (e.g. left by an older version) makes the step rerun.
+        let marker = marker_path.read_to_json::<Marker>();
+        let key = self.key(config, paths)?;
+        Ok(marker.map_or(false, |marker| marker == key))
+    }
+}
+
+/// Written when a step completes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Marker {
+    /// The configuration flags relevant to the step, see [`Step::relevant_configuration`].
+    pub configuration: String,
+    /// Digest of the step's inputs.
+    pub inputs:        String,
+}
+
+impl Marker {
+    pub fn write(&self, step: Step, paths: &Paths) -> Result {
+        ide_ci::fs::write_json(step.marker_path(paths), self)
+    }
+}
+
+/// Which of the build steps are run.
+#[derive(clap::Args, Clone, Debug, Default, PartialEq)]
+pub struct StepSelection {
+    /// Skip the steps that have completed in a previous build with the same inputs, as long as
+    /// their outputs are still present.
+    #[clap(long)]
+    pub resume:     bool,
+    /// Skip the build steps preceding the given one.
+    #[clap(long, arg_enum, conflicts_with = "only_step")]
+    pub from_step:  Option<Step>,
+    /// Run only the given build step. Can be repeated.
+    #[clap(long, arg_enum)]
+    pub only_step:  Vec<Step>,
+    /// Print the build steps to be run and exit.
+    #[clap(long)]
+    pub list_steps: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Decision {
+    Run,
+    #[strum(serialize = "up to date")]
+    UpToDate,
+    #[strum(serialize = "not selected")]
+    NotSelected,
+    Disabled,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    pub steps: Vec<(Step, Decision)>,
+}
+
+impl Plan {
+    pub fn new(
+        config: &BuildConfigurationFlags,
+        paths: &Paths,
+        selection: &StepSelection,
+    ) -> Result<Self> {
+        for step in selection.from_step.iter().chain(&selection.only_step) {
+            ensure!(step.is_enabled(config), "Step {step} is not part of this build.");
+        }
+        let mut steps = Vec::new();
+        let mut reached_from_step = selection.from_step.is_none();
+        // Once a step is run, the following ones must be rerun too, as their inputs might change.
+        let mut preceding_step_run = false;
+        for step in Step::iter() {
+            reached_from_step |= selection.from_step == Some(step);
+            let decision = if !step.is_enabled(config) {
+                Decision::Disabled
+            } else if !reached_from_step
+                || !(selection.only_step.is_empty() || selection.only_step.contains(&step))
+            {
+                Decision::NotSelected
+            } else if selection.resume
+                && !preceding_step_run
+                && step.is_up_to_date(config, paths)?
+            {
+                Decision::UpToDate
+            } else {
+                preceding_step_run = true;
+                Decision::Run
+            };
+            steps.push((step, decision));
+        }
+        Ok(Self { steps })
+    }
+
+    pub fn to_run(&self) -> impl Iterator<Item = Step> + '_ {
+        self.steps.iter().filter(|(_, decision)| *decision == Decision::Run).map(|(step, _)| *step)
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (step, decision) in &self.steps {
+            let (step_name, decision) = (step.to_string(), decision.to_string());
+            writeln!(f, "{step_name:<38} {decision:<13} {}", step.description())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn planning() -> Result {
+        let dir = tempfile::tempdir()?;
+        let paths = Paths::new_version(dir.path(), Version::new(2022, 1, 1))?;
+        let config = BuildConfigurationFlags {
+            clean_repo: false,
+            test_standard_library: true,
+            ..crate::engine::NIGHTLY
+        };
+        let plan = |selection: &StepSelection| -> Result<Vec<Step>> {
+            Ok(Plan::new(&config, &paths, selection)?.to_run().collect())
+        };
+
+        let everything = plan(&default())?;
+        assert_eq!(everything[0], Step::DownloadTemplates);
+        assert!(everything.contains(&Step::TestStandardLibrary));
+        assert!(!everything.contains(&Step::CleanRepo));
+
+        // Steps that have completed with the same inputs are skipped.
+        for step in [Step::DownloadTemplates, Step::Bootstrap] {
+            for output in step.outputs(&config, &paths) {
+                ide_ci::fs::create_dir_if_missing(output)?;
+            }
+            step.key(&config, &paths)?.write(step, &paths)?;
+        }
+        let resume = StepSelection { resume: true, ..default() };
+        assert_eq!(plan(&resume)?[0], Step::BuildPackages);
+
+        // Only the flags affecting the step's results make it rerun.
+        let first_run = |config: &BuildConfigurationFlags| -> Result<Option<Step>> {
+            Ok(Plan::new(config, &paths, &resume)?.to_run().next())
+        };
+        let retrying = BuildConfigurationFlags { test_retries: 2, sbt_server: true, ..config };
+        assert_eq!(first_run(&retrying)?, Some(Step::BuildPackages));
+        let development = BuildConfigurationFlags { mode: BuildMode::Development, ..config };
+        assert_eq!(first_run(&development)?, Some(Step::Bootstrap));
+
+        // Changed inputs make the step and all the following ones run.
+        ide_ci::fs::write(dir.path().join("build.sbt"), "lazy val enso = project")?;
+        assert_eq!(plan(&resume)?[0], Step::Bootstrap);
+
+        let from = StepSelection { from_step: Some(Step::TestStandardLibrary), ..default() };
+        assert_eq!(plan(&from)?[0], Step::TestStandardLibrary);
+        let only = StepSelection { only_step: vec![Step::Bootstrap], ..default() };
+        assert_eq!(plan(&only)?, [Step::Bootstrap]);
+        let disabled = StepSelection { only_step: vec![Step::TestScala], ..default() };
+        assert!(plan(&disabled).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+//! Verification of the built packages and bundles.
+//!
+//! All the checks are run and their problems are reported together, so a single build run reveals
+//! everything that is wrong with the artifacts.
+
+use crate::prelude::*;
+
+use crate::engine::BuiltArtifacts;
+use crate::paths::generated;
+use crate::paths::ComponentPaths;
+use ide_ci::extensions::os::OsExt;
+
+
+
+/// Name of the file marking the portable distribution in the bundle.
+pub const BUNDLE_MARKER: &str = ".enso.bundle";
+
+/// What the built artifacts are expected to contain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expectations {
+    /// Version of the Enso being built.
+    pub version:       Version,
+    /// Version of the GraalVM that the bundles should include, as given in `build.sbt`.
+    pub graal_version: Version,
+}
+
+impl Expectations {
+    /// Check the artifacts and fail with the description of all the problems found.
+    pub async fn verify(&self, artifacts: &BuiltArtifacts) -> Result {
+        let mut issues = self.check_files(artifacts);
+        if let Some(engine) = &artifacts.packages.engine {
+            if let Err(e) = self.check_engine_version(engine).await {
+                issues.push(format!("{e:#}"));
+            }
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            let list = issues.iter().map(|issue| format!(" * {issue}")).join("\n");
+            bail!("Found {} problems with the built artifacts:\n{list}", issues.len())
+        }
+    }
+
+    /// Describe the problems with the files of the artifacts. Does not run any of them.
+    pub fn check_files(&self, artifacts: &BuiltArtifacts) -> Vec<String> {
+        let mut issues = Vec::new();
+        let packages = &artifacts.packages;
+        if let Some(engine) = &packages.engine {
+            check_engine_package(&engine.dir, &mut issues);
+        }
+        if let Some(launcher) = &packages.launcher {
+            check_launcher_package(&launcher.dir, &mut issues);
+        }
+        if let Some(project_manager) = &packages.project_manager {
+            check_project_manager_package(&project_manager.dir, &mut issues);
+        }
+        if let Some(launcher) = &artifacts.bundles.launcher {
+            check_launcher_package(&launcher.dir, &mut issues);
+            self.check_bundle(launcher, &mut issues);
+        }
+        if let Some(project_manager) = &artifacts.bundles.project_manager {
+            check_project_manager_package(&project_manager.dir, &mut issues);
+            self.check_bundle(project_manager, &mut issues);
+        }
+        issues
+    }
+
+    /// Check the engine and the GraalVM placed in the bundle.
+    pub fn check_bundle(&self, bundle: &ComponentPaths, issues: &mut Vec<String>) {
+        let marker = bundle.dir.join(BUNDLE_MARKER);
+        if !marker.is_file() {
+            issues.push(format!("Missing the bundle marker {}.", marker.display()));
+        }
+        check_engine_package(bundle.dir.join_iter(["dist", &self.version.to_string()]), issues);
+
+        let runtime = bundle.dir.join("runtime");
+        let graal_dirs = ide_ci::fs::read_dir(&runtime)
+            .and_then(|entries| entries.map(|entry| Ok(entry?.path())).collect::<Result<Vec<_>>>());
+        match graal_dirs {
+            Ok(graal_dirs) if graal_dirs.len() == 1 => {
+                let release = graal_release_file(&graal_dirs[0]);
+                match read_graal_version(&release) {
+                    Ok(version) if is_graal_version(&version, &self.graal_version) => {}
+                    Ok(version) => issues.push(format!(
+                        "The bundled GraalVM in {} is {version}, while build.sbt requires {}.",
+                        runtime.display(),
+                        self.graal_version
+                    )),
+                    Err(e) => issues.push(format!("{e:#}")),
+                }
+            }
+            Ok(graal_dirs) => issues.push(format!(
+                "Expected exactly one GraalVM in {}, found {}.",
+                runtime.display(),
+                graal_dirs.len()
+            )),
+            Err(e) => issues.push(format!("{e:#}")),
+        }
+    }
+
+    /// Check that the engine reports the expected version.
+    pub async fn check_engine_version(&self, engine: &ComponentPaths) -> Result {
+        #[derive(Clone, Debug, Deserialize)]
+        struct VersionInfo {
+            version: String,
+        }
+
+        let binary = engine.dir.join_iter(["bin", "enso"]);
+        let output = ide_ci::platform::DEFAULT_SHELL
+            .run_script(&binary)?
+            .args(["--version", "--json"])
+            .run_stdout()
+            .await?;
+        let info = serde_json::from_str::<VersionInfo>(&output).with_context(|| {
+            format!("Failed to parse the version reported by {}.", binary.display())
+        })?;
+        ensure!(
+            info.version == self.version.to_string(),
+            "The engine reports version {}, while {} was expected.",
+            info.version,
+            self.version
+        );
+        Ok(())
+    }
+}
+
+/// Check that the file exists and, if it is a binary, that it can be executed.
+fn check_file(path: impl AsRef<Path>, executable: bool, issues: &mut Vec<String>) {
+    let path = path.as_ref();
+    if !path.is_file() {
+        issues.push(format!("Missing file {}.", path.display()));
+    } else if executable {
+        match ide_ci::fs::is_owner_executable(path) {
+            Ok(true) => {}
+            Ok(false) => issues.push(format!("File {} is not executable.", path.display())),
+            Err(e) => issues.push(format!("{e:#}")),
+        }
+    }
+}
+
+fn check_dir(path: impl AsRef<Path>, issues: &mut Vec<String>) {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        issues.push(format!("Missing directory {}.", path.display()));
+    }
+}
+
+/// Check the engine version package, see
+/// https://enso.org/docs/developer/enso/distribution/distribution.html#layout-of-an-enso-version-package
+fn check_engine_package(dir: impl AsRef<Path>, issues: &mut Vec<String>) {
+    let dir = dir.as_ref();
+    check_file(dir.join_iter(["bin", "enso"]), true, issues);
+    check_file(dir.join("manifest.yaml"), false, issues);
+    check_dir(dir.join("component"), issues);
+    check_dir(dir.join_iter(["lib", "Standard"]), issues);
+}
+
+fn check_launcher_package(dir: impl AsRef<Path>, issues: &mut Vec<String>) {
+    let binary = dir.as_ref().join_iter(["bin", "enso"]).with_extension(TARGET_OS.exe_extension());
+    check_file(binary, true, issues);
+}
+
+fn check_project_manager_package(dir: impl AsRef<Path>, issues: &mut Vec<String>) {
+    let package = generated::ProjectManager::new_root(dir.as_ref(), TARGET_OS.exe_suffix());
+    check_file(&package.bin.project_managerexe, true, issues);
+}
+
+/// The `release` file of the GraalVM distribution. On macOS it is placed within the app bundle.
+pub fn graal_release_file(graal_dir: impl AsRef<Path>) -> PathBuf {
+    let graal_dir = graal_dir.as_ref();
+    let macos_release = graal_dir.join_iter(["Contents", "Home", "release"]);
+    if macos_release.exists() {
+        macos_release
+    } else {
+        graal_dir.join("release")
+    }
+}
+
+/// Read the `GRAALVM_VERSION` from the GraalVM's `release` file.
+pub fn read_graal_version(release_file: impl AsRef<Path>) -> Result<String> {
+    let contents = ide_ci::fs::read_to_string(&release_file)?;
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("GRAALVM_VERSION="))
+        .map(|version| version.trim().trim_matches('"').to_string())
+        .with_context(|| format!("No GraalVM version in {}.", release_file.as_ref().display()))
+}
+
+/// Whether the version from the `release` file matches the expected one. The file may contain an
+/// additional patch component, like `21.3.0.1`.
+pub fn is_graal_version(found: &str, expected: &Version) -> bool {
+    let expected = expected.to_string();
+    found == expected || found.starts_with(&format!("{expected}."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checking_files() -> Result {
+        let dir = tempfile::tempdir()?;
+        let engine = ComponentPaths { dir: dir.path().join("engine"), ..default() };
+        let bundle = ComponentPaths { dir: dir.path().join("bundle"), ..default() };
+        let expectations = Expectations {
+            version:       Version::new(2022, 1, 1),
+            graal_version: Version::new(21, 3, 0),
+        };
+
+        let engine_binary = engine.dir.join_iter(["bin", "enso"]);
+        ide_ci::fs::write(&engine_binary, "")?;
+        ide_ci::fs::allow_owner_execute(&engine_binary)?;
+        ide_ci::fs::write(engine.dir.join("manifest.yaml"), "")?;
+        ide_ci::fs::create_dir_if_missing(engine.dir.join("component"))?;
+        ide_ci::fs::create_dir_if_missing(engine.dir.join_iter(["lib", "Standard"]))?;
+        ide_ci::fs::copy(&engine.dir, bundle.dir.join_iter(["dist", "2022.1.1"]))?;
+        let release = bundle.dir.join_iter(["runtime", "graalvm-ce-java11-21.3.0", "release"]);
+        ide_ci::fs::write(&release, "JAVA_VERSION=\"11.0.13\"\nGRAALVM_VERSION=\"21.3.0\"\n")?;
+
+        let artifacts = |bundle: &ComponentPaths| {
+            let mut ret = BuiltArtifacts::default();
+            ret.packages.engine = Some(engine.clone());
+            ret.bundles.launcher = Some(bundle.clone());
+            ret
+        };
+        let issues = expectations.check_files(&artifacts(&bundle));
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert!(issues[0].starts_with("Missing file"));
+        assert!(issues[1].starts_with("Missing the bundle marker"));
+
+        ide_ci::fs::write(bundle.dir.join(BUNDLE_MARKER), "")?;
+        ide_ci::fs::write(
+            bundle.dir.join_iter(["bin", "enso"]).with_extension(TARGET_OS.exe_extension()),
+            "",
+        )?;
+        ide_ci::fs::write(&release, "GRAALVM_VERSION=22.0.0.2\n")?;
+        let issues = expectations.check_files(&artifacts(&bundle));
+        assert_eq!(issues.len(), if TARGET_OS == OS::Windows { 1 } else { 2 }, "{issues:?}");
+        assert!(issues.last().unwrap().contains("GraalVM"));
+
+        assert!(is_graal_version("21.3.0.1", &Version::new(21, 3, 0)));
+        assert!(!is_graal_version("21.3.01", &Version::new(21, 3, 0)));
+        Ok(())
+    }
+}
@@ -1,18 +1,49 @@
 use crate::prelude::*;
 
+use ide_ci::env::new::RawVariable;
 use ide_ci::env::Variable;
 use ide_ci::future::AsyncPolicy;
+use ide_ci::models::test_report::TestReport;
+use ide_ci::programs::docker::service;
 use ide_ci::programs::docker::ContainerId;
+use std::str::FromStr;
+use std::time::Duration;
 
 use crate::paths::Paths;
+use crate::paths::DATABASE_TEST_LIBRARIES;
+use crate::paths::ENSO_TEST_JUNIT_DIR;
+use crate::paths::LIBRARIES_TO_TEST;
 use crate::postgres;
-use crate::postgres::EndpointConfiguration;
 use crate::postgres::Postgresql;
 
 ide_ci::define_env_var! {
     ENSO_JVM_OPTS, String
 }
 
+ide_ci::define_env_var! {
+    /// Regular expression selecting the test groups to be run by the `Test` library.
+    ///
+    /// Read by `Suite_Config.from_environment` in the `Test` library's sources, see
+    /// [`Paths::test_library_sources`]. Older versions of the library ignore it and run all the
+    /// groups, so [`supports_group_filter`] must be checked before relying on it.
+    TEST_ONLY_GROUP, String
+}
+
+/// Whether the `Test` library of the repository honors the [`TEST_ONLY_GROUP`] filter.
+pub fn supports_group_filter(paths: &Paths) -> Result<bool> {
+    let sources = paths.test_library_sources();
+    for entry in walkdir::WalkDir::new(&sources) {
+        let path = entry.with_context(|| format!("Failed to list {}.", sources.display()))?;
+        let path = path.path();
+        if path.extension().map_or(false, |extension| extension == "enso")
+            && ide_ci::fs::read_to_string(path)?.contains(TEST_ONLY_GROUP.name())
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum IrCaches {
     Yes,
@@ -34,6 +65,156 @@ impl AsRef<OsStr> for IrCaches {
     }
 }
 
+/// Database that the [database test libraries](DATABASE_TEST_LIBRARIES) are run against.
+///
+/// Written as `sqlite` or `postgres-<version>`, where the version is the tag of the Postgres
+/// image, like `postgres-14.3` or `postgres-latest`. Plain `postgres` means the latest version.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Database {
+    /// SQLite is embedded in the engine, so only the SQLite tests are run and no container is
+    /// needed.
+    Sqlite,
+    /// Postgres run in a Docker container. Only available on Linux.
+    Postgres { version: String },
+}
+
+impl Database {
+    pub fn postgres(version: impl Into<String>) -> Self {
+        Self::Postgres { version: version.into() }
+    }
+
+    /// The databases used if none are selected explicitly.
+    ///
+    /// Postgres containers can be run only on Linux, elsewhere just SQLite is tested.
+    pub fn defaults() -> Vec<Self> {
+        match TARGET_OS {
+            OS::Linux => vec![Self::postgres("latest")],
+            _ => vec![Self::Sqlite],
+        }
+    }
+}
+
+impl Display for Database {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sqlite => write!(f, "sqlite"),
+            Self::Postgres { version } => write!(f, "postgres-{version}"),
+        }
+    }
+}
+
+impl FromStr for Database {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sqlite" => Ok(Self::Sqlite),
+            "postgres" => Ok(Self::postgres("latest")),
+            _ => match s.strip_prefix("postgres-") {
+                Some(version) if !version.is_empty() => Ok(Self::postgres(version)),
+                _ => bail!(
+                    "Unknown database `{s}`, expected `sqlite`, `postgres` or \
+                    `postgres-<version>`."
+                ),
+            },
+        }
+    }
+}
+
+/// A single run of a test library.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TestRun {
+    /// Name of the test project.
+    pub library:  String,
+    /// Database to run against, for the [database test libraries](DATABASE_TEST_LIBRARIES).
+    pub database: Option<Database>,
+    /// Regular expression selecting the test groups to run. If not set, all groups are run.
+    pub filter:   Option<String>,
+}
+
+impl TestRun {
+    /// Identifies the run among the others, e.g. `Table_Tests-postgres-14`. It is also the name
+    /// of the directory with the run's JUnit reports.
+    pub fn name(&self) -> String {
+        match &self.database {
+            Some(database) => format!("{}-{database}", self.library),
+            None => self.library.clone(),
+        }
+    }
+}
+
+/// Which of the standard library tests should be run and how.
+#[derive(Clone, Debug)]
+pub struct TestSelection {
+    /// Names of the test projects, i.e. their directories in the repository's `test` directory.
+    pub libraries: Vec<String>,
+    /// Regular expression selecting the test groups to run. If not set, all groups are run.
+    pub filter:    Option<String>,
+    /// Time limit for running the tests of a single library.
+    pub timeout:   Option<Duration>,
+    /// The database test libraries are run once for each of these.
+    pub databases: Vec<Database>,
+}
+
+impl Default for TestSelection {
+    fn default() -> Self {
+        Self {
+            libraries: LIBRARIES_TO_TEST.map(ToString::to_string).to_vec(),
+            filter:    None,
+            timeout:   None,
+            databases: Database::defaults(),
+        }
+    }
+}
+
+impl TestSelection {
+    /// The runs of the selected libraries. The database test libraries are run for each of the
+    /// databases, the others just once.
+    pub fn runs(&self) -> Vec<TestRun> {
+        let mut ret = Vec::new();
+        for library in &self.libraries {
+            let run = |database| TestRun {
+                library: library.clone(),
+                database,
+                filter: self.filter.clone(),
+            };
+            if DATABASE_TEST_LIBRARIES.contains(&library.as_str()) {
+                ret.extend(self.databases.iter().cloned().map(|database| run(Some(database))));
+            } else {
+                ret.push(run(None));
+            }
+        }
+        ret
+    }
+
+    /// Check that all the selected libraries are present in the repository, that the filter is
+    /// supported by its `Test` library and that the databases can be run on this system.
+    pub fn validate(&self, paths: &Paths) -> Result {
+        let available = paths.stdlib_test_libraries()?;
+        let unknown = self.libraries.iter().filter(|library| !available.contains(library));
+        let unknown = unknown.collect_vec();
+        ensure!(
+            unknown.is_empty(),
+            "Unknown test libraries: {}. Available ones are: {}.",
+            unknown.iter().join(", "),
+            available.join(", ")
+        );
+        ensure!(
+            self.filter.is_none() || supports_group_filter(paths)?,
+            "The `Test` library in {} does not support filtering the test groups.",
+            paths.test_library_sources().display()
+        );
+        ensure!(!self.databases.is_empty(), "At least one database must be selected.");
+        let uses_postgres =
+            self.databases.iter().any(|database| matches!(database, Database::Postgres { .. }));
+        ensure!(
+            TARGET_OS == OS::Linux || !uses_postgres,
+            "Postgres can be tested only on Linux, use `sqlite` on {TARGET_OS}."
+        );
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BuiltEnso {
     pub paths: Paths,
@@ -51,6 +232,7 @@ impl BuiltEnso {
             .arg(ir_caches)
             .arg("--run")
             .arg(test_path)
+            .set_env(ENSO_TEST_JUNIT_DIR, &self.paths.test_results)?
             // This flag enables assertions in the JVM. Some of our stdlib tests had in the past
             // failed on Graal/Truffle assertions, so we want to have them triggered.
             .set_env(ENSO_JVM_OPTS, &ide_ci::programs::java::Option::EnableAssertions.as_ref())?;
@@ -68,55 +250,226 @@ impl BuiltEnso {
     }
 
     pub async fn run_tests(&self, ir_caches: IrCaches, async_policy: AsyncPolicy) -> Result {
-        let paths = &self.paths;
-        // Prepare Engine Test Environment
+        self.run_selected_tests(&default(), ir_caches, async_policy).await
+    }
+
+    pub async fn run_selected_tests(
+        &self,
+        selection: &TestSelection,
+        ir_caches: IrCaches,
+        async_policy: AsyncPolicy,
+    ) -> Result {
+        self.prepare_test_environment()?;
+        let failed = self
+            .run_libraries(
+                selection.runs(),
+                selection.timeout,
+                ir_caches,
+                async_policy,
+                &self.paths.test_results,
+            )
+            .await?;
+        ensure!(failed.is_empty(), "Tests failed for: {}.", failed.join(", "));
+        Ok(())
+    }
+
+    /// Run the selected tests, rerunning the failed ones up to `retries` times.
+    ///
+    /// Only the failed test groups are rerun, as known from the JUnit reports. If a library
+    /// failed without any failed group reported (e.g. it crashed), or its `Test` library does not
+    /// [support the filter](supports_group_filter), it is rerun whole. The results of all the
+    /// attempts are merged into the `report`, even if the tests fail eventually. Tests that passed
+    /// only when rerun are marked as flaky.
+    pub async fn run_tests_with_retries(
+        &self,
+        selection: &TestSelection,
+        ir_caches: IrCaches,
+        async_policy: AsyncPolicy,
+        retries: usize,
+        report: &mut TestReport,
+    ) -> Result {
+        self.prepare_test_environment()?;
+        let results_dir = &self.paths.test_results;
+        let mut failed = self
+            .run_libraries(
+                selection.runs(),
+                selection.timeout,
+                ir_caches,
+                async_policy,
+                results_dir,
+            )
+            .await?;
+        let mut reports = selection
+            .runs()
+            .into_iter()
+            .map(|run| Ok((run.name(), (run.clone(), run_test_report(results_dir, &run)?))))
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        let rerun_dir = self.paths.test_results_rerun();
+        let group_filter = retries > 0 && supports_group_filter(&self.paths)?;
+        for attempt in 1..=retries {
+            if failed.is_empty() {
+                break;
+            }
+            warn!("Rerunning failed tests of {} (retry {attempt}/{retries}).", failed.join(", "));
+            let runs = failed
+                .iter()
+                .map(|name| {
+                    let (run, run_report) = &reports[name];
+                    let failed_groups = run_report.failed_suites();
+                    let filter = if failed_groups.is_empty() || !group_filter {
+                        selection.filter.clone()
+                    } else {
+                        let groups = failed_groups.into_iter().map(regex::escape).join("|");
+                        Some(format!("^({groups})$"))
+                    };
+                    TestRun { filter, ..run.clone() }
+                })
+                .collect_vec();
+            ide_ci::fs::reset_dir(&rerun_dir)?;
+            failed = self
+                .run_libraries(runs, selection.timeout, ir_caches, async_policy, &rerun_dir)
+                .await?;
+            for (run, run_report) in reports.values_mut() {
+                run_report.apply_rerun(run_test_report(&rerun_dir, run)?);
+            }
+        }
+
+        for (run, mut run_report) in reports.into_values() {
+            // Tell apart the suites of the same library run against different databases.
+            if let Some(database) = &run.database {
+                for suite in &mut run_report.suites {
+                    suite.name = format!("{} [{database}]", suite.name);
+                }
+            }
+            report.merge(run_report);
+        }
+        ensure!(failed.is_empty(), "Tests failed for: {}.", failed.join(", "));
+        Ok(())
+    }
+
+    fn prepare_test_environment(&self) -> Result {
         if let Ok(gdoc_key) = std::env::var("GDOC_KEY") {
             let google_api_test_data_dir =
-                paths.repo_root.join("test").join("Google_Api_Test").join("data");
+                self.paths.repo_root.join("test").join("Google_Api_Test").join("data");
             ide_ci::fs::create_dir_if_missing(&google_api_test_data_dir)?;
             ide_ci::fs::write(google_api_test_data_dir.join("secret.json"), &gdoc_key)?;
         }
+        Ok(())
+    }
 
-        let _httpbin = crate::httpbin::get_and_spawn_httpbin_on_free_port().await?;
-        let _postgres = match TARGET_OS {
-            OS::Linux => {
-                let runner_context_string = crate::env::RunnerContainerName
-                    .fetch()
-                    .map(|name| name.0)
-                    .or_else(|_| ide_ci::actions::env::RunnerName.fetch())
-                    .unwrap_or_else(|_| Uuid::new_v4().to_string());
-                // GH-hosted runners are named like "GitHub Actions 10". Spaces are not allowed in
-                // the container name.
-                let container_name =
-                    iformat!("postgres-for-{runner_context_string}").replace(' ', "_");
-                let config = postgres::Configuration {
-                    postgres_container: ContainerId(container_name),
-                    database_name:      "enso_test_db".to_string(),
-                    user:               "enso_test_user".to_string(),
-                    password:           "enso_test_password".to_string(),
-                    endpoint:           EndpointConfiguration::deduce()?,
-                    version:            "latest".to_string(),
-                };
-                let postgres = Postgresql::start(config).await?;
-                Some(postgres)
+    /// Perform the given test runs. The JUnit reports are written to the subdirectories of
+    /// `results_dir`, named after the runs.
+    ///
+    /// Returns the names of the runs whose tests have failed.
+    async fn run_libraries(
+        &self,
+        runs: Vec<TestRun>,
+        timeout: Option<Duration>,
+        ir_caches: IrCaches,
+        async_policy: AsyncPolicy,
+        results_dir: &Path,
+    ) -> Result<Vec<String>> {
+        let runner_context_string = crate::env::RunnerContainerName
+            .fetch()
+            .map(|name| name.0)
+            .or_else(|_| ide_ci::actions::env::RunnerName.fetch())
+            .unwrap_or_else(|_| service::local_owner(&self.paths.repo_root));
+        if TARGET_OS == OS::Linux {
+            // The containers of a previous run, that has crashed, would waste the resources.
+            let orphans = service::remove_orphans(&runner_context_string);
+            if let Err(e) = orphans.await {
+                warn!("Failed to remove the orphaned test service containers: {e:?}");
             }
-            _ => None,
-        };
+        }
 
-        let futures = crate::paths::LIBRARIES_TO_TEST.map(ToString::to_string).map(|test| {
-            let command = self.run_test(test, ir_caches);
-            async move { command?.run_ok().await }
-        });
+        // Each test run gets its own httpbin and Postgres instance, so the runs can be performed
+        // concurrently without interfering with each other.
+        let futures = runs.into_iter().map(|run| {
+            let test = run.name();
+            let command = self.run_test(&run.library, ir_caches);
+            let results_dir = results_dir.to_path_buf();
+            // GH-hosted runners are named like "GitHub Actions 10". Spaces are not allowed in
+            // the container name.
+            let container_name =
+                iformat!("postgres-for-{runner_context_string}-{test}").replace(' ', "_");
+            let service_owner = runner_context_string.clone();
+            async move {
+                let result = async {
+                    let mut command = command?;
+                    command.log_prefix(&test);
+                    command.set_env(ENSO_TEST_JUNIT_DIR, &results_dir.join(&test))?;
+                    if let Some(filter) = &run.filter {
+                        command.set_env(TEST_ONLY_GROUP, filter)?;
+                    }
 
-        let _result = ide_ci::future::try_join_all(futures, async_policy).await?;
+                    let httpbin = crate::httpbin::spawn_on_free_port()?;
+                    command.env(crate::httpbin::env::Url::NAME, httpbin.url.as_str());
 
-        // We need to join all the test tasks here, as they require postgres and httpbin alive.
-        // Could share them with Arc but then scenario of multiple test runs being run in parallel
-        // should be handled, e.g. avoiding port collisions.
-        Ok(())
+                    // Without the Postgres connection configured, the tests use just SQLite.
+                    let postgres = match &run.database {
+                        Some(Database::Postgres { version }) => {
+                            let config = postgres::Configuration {
+                                postgres_container: ContainerId(container_name),
+                                database_name:      postgres::TEST_DATABASE_NAME.into(),
+                                user:               postgres::TEST_USER.into(),
+                                password:           postgres::TEST_PASSWORD.into(),
+                                endpoint:           postgres::deduce_isolated_endpoint()?,
+                                version:            version.clone(),
+                                owner:              service_owner,
+                            };
+                            let postgres = Postgresql::start(config).await?;
+                            postgres.config().apply_enso_test_env(&mut command)?;
+                            Some(postgres)
+                        }
+                        Some(Database::Sqlite) | None => None,
+                    };
+
+                    let run = command.kill_on_drop(true).run_ok();
+                    let result = match timeout {
+                        Some(timeout) => tokio::time::timeout(timeout, run)
+                            .await
+                            .with_context(|| {
+                                let timeout = humantime::format_duration(timeout);
+                                format!("Tests of {test} did not complete within {timeout}.")
+                            })
+                            .and_then(|result| result),
+                        None => run.await,
+                    };
+                    // The services must be kept alive until the tests are done.
+                    drop(httpbin);
+                    if let Some(postgres) = postgres {
+                        if let Err(e) = postgres.stop().await {
+                            warn!("Failed to stop the Postgres for {test}: {e:?}");
+                        }
+                    }
+                    result
+                }
+                .await;
+                Result::Ok((test, result))
+            }
+        });
+
+        // Let all the libraries run to completion, even if some of them fail, so the results are
+        // complete.
+        let results = ide_ci::future::try_join_all(futures, async_policy).await?;
+        let mut failed = Vec::new();
+        for (test, result) in results {
+            if let Err(e) = result {
+                error!("Tests of {test} failed: {e:?}");
+                failed.push(test);
+            }
+        }
+        Ok(failed)
     }
 }
 
+/// Load the JUnit reports written by the given run's tests to the `results_dir`.
+fn run_test_report(results_dir: &Path, run: &TestRun) -> Result<TestReport> {
+    let pattern = results_dir.join_iter([run.name().as_str(), "**", "*.xml"]);
+    TestReport::from_junit_glob(pattern.as_str())
+}
+
 #[async_trait]
 impl Program for BuiltEnso {
     fn executable_name(&self) -> &str {
@@ -144,3 +497,60 @@ impl Program for BuiltEnso {
         Ok(version.version)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_runs() -> Result {
+        assert_eq!(Database::from_str("sqlite")?, Database::Sqlite);
+        assert_eq!(Database::from_str("postgres")?, Database::postgres("latest"));
+        assert_eq!(Database::from_str("postgres-14.3")?, Database::postgres("14.3"));
+        assert!(Database::from_str("postgres-").is_err());
+        assert!(Database::from_str("mysql").is_err());
+
+        let selection = TestSelection {
+            libraries: vec!["Tests".into(), "Table_Tests".into()],
+            databases: vec![Database::Sqlite, Database::postgres("14")],
+            ..default()
+        };
+        let names = selection.runs().iter().map(TestRun::name).collect_vec();
+        assert_eq!(names, ["Tests", "Table_Tests-sqlite", "Table_Tests-postgres-14"]);
+        Ok(())
+    }
+
+    #[test]
+    fn selection_validation() -> Result {
+        let repo_root = tempfile::tempdir()?;
+        let paths = Paths::new_version(repo_root.path(), Version::new(0, 0, 0))?;
+        for library in ["Tests", "Table_Tests", "Geo_Tests"] {
+            ide_ci::fs::write(paths.stdlib_test(library).join("package.yaml"), "")?;
+        }
+        // Directories without a project are not test libraries.
+        ide_ci::fs::create_dir_if_missing(paths.stdlib_test("data"))?;
+        assert_eq!(paths.stdlib_test_libraries()?, ["Geo_Tests", "Table_Tests", "Tests"]);
+
+        let selection = TestSelection {
+            libraries: vec!["Tests".into(), "Table_Tests".into()],
+            databases: vec![Database::Sqlite],
+            ..default()
+        };
+        selection.validate(&paths)?;
+        let unknown = TestSelection { libraries: vec!["data".into()], ..selection.clone() };
+        assert!(unknown.validate(&paths).is_err());
+        let no_databases = TestSelection { databases: vec![], ..selection.clone() };
+        assert!(no_databases.validate(&paths).is_err());
+
+        // The filter is accepted only if the `Test` library reads it.
+        let filtered = TestSelection { filter: Some("^Vectors$".into()), ..selection };
+        let test_main = paths.test_library_sources().join("Main.enso");
+        ide_ci::fs::write(&test_main, "main = IO.println 1")?;
+        assert!(!supports_group_filter(&paths)?);
+        assert!(filtered.validate(&paths).is_err());
+        ide_ci::fs::write(&test_main, r#"only_group = Environment.get "TEST_ONLY_GROUP""#)?;
+        assert!(supports_group_filter(&paths)?);
+        filtered.validate(&paths)?;
+        Ok(())
+    }
+}
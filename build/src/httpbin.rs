@@ -1,8 +1,41 @@
+//! In-process stand-in for the [httpbin](https://httpbin.org) service, used by the standard library
+//! HTTP tests.
+//!
+//! Only the endpoints exercised by the tests are covered. Their responses follow the original
+//! service, so the tests can be run against either.
+
 use crate::prelude::*;
 
-use ide_ci::env::Variable;
-use ide_ci::programs::Go;
-use tokio::process::Child;
+use hyper::header::HeaderName;
+use hyper::header::AUTHORIZATION;
+use hyper::header::CONTENT_TYPE;
+use hyper::header::COOKIE;
+use hyper::header::HOST;
+use hyper::header::LOCATION;
+use hyper::header::SET_COOKIE;
+use hyper::header::WWW_AUTHENTICATE;
+use hyper::server::conn::AddrStream;
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::Body;
+use hyper::Method;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::Value;
+use std::collections::btree_map::Entry;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use tokio::task::JoinHandle;
+
+
+
+/// The largest response of the `/bytes/{n}` endpoint, same as in the original service.
+pub const MAX_BYTES: usize = 100 * 1024;
 
 pub mod env {
     /// Environment variable that stores URL under which spawned httpbin server is available.
@@ -13,55 +46,358 @@ pub mod env {
     }
 }
 
+/// The running server. It is stopped when dropped.
 #[derive(Debug)]
 pub struct Spawned {
-    pub process: Child,
-    pub url:     Url,
+    pub url:    Url,
+    pub server: JoinHandle<hyper::Result<()>>,
 }
 
-pub async fn get_and_spawn_httpbin(port: u16) -> Result<Spawned> {
-    Go.call_args(["install", "-v", "github.com/ahmetb/go-httpbin/cmd/httpbin@latest"]).await?;
-    let gopath = Go.cmd()?.args(["env", "GOPATH"]).run_stdout().await?;
-    let gopath = gopath.trim();
-    let gopath = PathBuf::from(gopath); // be careful of trailing newline!
-    let program = gopath.join("bin").join("httpbin");
-    debug!("Will spawn {}", program.display());
-    let process = Command::new(program) // TODO? wrap in Program?
-        .args(["-host", &format!(":{port}")])
-        .kill_on_drop(true)
-        .spawn_intercepting()
-        .anyhow_err()?;
+impl Drop for Spawned {
+    fn drop(&mut self) {
+        debug!("Stopping the httpbin server at {}.", self.url);
+        self.server.abort();
+    }
+}
 
-    let url_string = format!("http://localhost:{port}");
-    let url = Url::parse(&url_string)?;
-    env::Url.set(&url);
-    Ok(Spawned { url, process })
+/// Start the server on the given port (or on any free one, if it is 0).
+///
+/// The URL is not exported to the process environment, as several servers can run at once. Pass
+/// it through [`env::Url`] to the commands that use the server.
+pub fn spawn(port: u16) -> Result<Spawned> {
+    let address = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+    let make_service = make_service_fn(|connection: &AddrStream| {
+        let origin = connection.remote_addr().ip();
+        let service = service_fn(move |request: Request<Body>| async move {
+            let response = respond(request, origin).await.unwrap_or_else(|e| {
+                let mut response = Response::new(Body::from(format!("{e:#}")));
+                *response.status_mut() = StatusCode::BAD_REQUEST;
+                response
+            });
+            Ok::<_, Infallible>(response)
+        });
+        std::future::ready(Ok::<_, Infallible>(service))
+    });
+    let server = hyper::Server::try_bind(&address)?.serve(make_service);
+    let url = Url::parse(&format!("http://localhost:{}", server.local_addr().port()))?;
+    debug!("Started the httpbin server at {url}.");
+    Ok(Spawned { url, server: tokio::spawn(server) })
 }
 
-impl Drop for Spawned {
-    fn drop(&mut self) {
-        debug!("Dropping the httpbin wrapper.");
-        env::Url.remove();
+pub fn spawn_on_free_port() -> Result<Spawned> {
+    spawn(0)
+}
+
+/// Read the whole request and generate the response.
+pub async fn respond(request: Request<Body>, origin: IpAddr) -> Result<Response<Body>> {
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    route(&Request::from_parts(parts, body), origin)
+}
+
+pub fn route(request: &Request<Bytes>, origin: IpAddr) -> Result<Response<Body>> {
+    let path = request.uri().path().trim_matches('/');
+    let segments = path.split('/').collect_vec();
+    let method = request.method();
+    match segments.as_slice() {
+        ["get"] if method == Method::GET =>
+            json(StatusCode::OK, &Echo::new(request, origin, false)),
+        [endpoint @ ("post" | "put" | "patch" | "delete")]
+            if method.as_str().eq_ignore_ascii_case(endpoint) =>
+            json(StatusCode::OK, &Echo::new(request, origin, true)),
+        ["get" | "post" | "put" | "patch" | "delete"] => empty(StatusCode::METHOD_NOT_ALLOWED),
+        ["headers"] => json(StatusCode::OK, &serde_json::json!({ "headers": headers(request) })),
+        ["status", codes] => {
+            let codes =
+                codes.split(',').map(|code| code.parse::<u16>()).collect::<Result<Vec<_>, _>>()?;
+            let code = codes.choose(&mut rand::thread_rng()).context("No status code given.")?;
+            empty(StatusCode::from_u16(*code)?)
+        }
+        ["bytes", count] => {
+            let mut data = vec![0_u8; count.parse::<usize>()?.min(MAX_BYTES)];
+            rand::thread_rng().fill(data.as_mut_slice());
+            let response = Response::builder().header(CONTENT_TYPE, "application/octet-stream");
+            Ok(response.body(Body::from(data))?)
+        }
+        ["redirect", count] => {
+            let count = count.parse::<usize>()?;
+            let location =
+                if count > 1 { format!("/redirect/{}", count - 1) } else { "/get".into() };
+            let absolute = query_args(request).get("absolute") == Some(&Value::from("true"));
+            if absolute {
+                redirect(format!("http://{}{location}", host(request)))
+            } else {
+                redirect(location)
+            }
+        }
+        ["redirect-to"] => {
+            let args = query_args(request);
+            let location = args.get("url").and_then(Value::as_str).context("Missing `url`.")?;
+            let status = match args.get("status_code").and_then(Value::as_str) {
+                Some(code) => StatusCode::from_u16(code.parse()?)?,
+                None => StatusCode::FOUND,
+            };
+            let response = Response::builder().status(status).header(LOCATION, location);
+            Ok(response.body(Body::empty())?)
+        }
+        [kind @ ("basic-auth" | "hidden-basic-auth"), user, password] => {
+            let expected = format!("{user}:{password}");
+            if basic_auth_credentials(request).contains(&expected) {
+                json(StatusCode::OK, &serde_json::json!({ "authenticated": true, "user": user }))
+            } else if *kind == "hidden-basic-auth" {
+                empty(StatusCode::NOT_FOUND)
+            } else {
+                let response = Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .header(WWW_AUTHENTICATE, r#"Basic realm="Fake Realm""#);
+                Ok(response.body(Body::empty())?)
+            }
+        }
+        ["cookies"] => json(StatusCode::OK, &serde_json::json!({ "cookies": cookies(request) })),
+        ["cookies", "set", name, value] => set_cookies([(*name, *value)]),
+        ["cookies", "set"] => {
+            let args = query_args(request);
+            set_cookies(
+                args.iter().map(|(name, value)| (name.as_str(), value.as_str().unwrap_or(""))),
+            )
+        }
+        ["cookies", "delete"] => {
+            let mut response =
+                Response::builder().status(StatusCode::FOUND).header(LOCATION, "/cookies");
+            for name in query_args(request).keys() {
+                let expired =
+                    format!("{name}=; Expires=Thu, 01-Jan-1970 00:00:00 GMT; Max-Age=0; Path=/");
+                response = response.header(SET_COOKIE, expired);
+            }
+            Ok(response.body(Body::empty())?)
+        }
+        _ => empty(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Description of the request, returned by the method-named endpoints.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Echo {
+    pub args:    BTreeMap<String, Value>,
+    pub headers: BTreeMap<String, String>,
+    pub origin:  String,
+    pub url:     String,
+    /// Present only for the methods that can have a body.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Payload>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Payload {
+    /// The body, unless it is a form.
+    pub data:  String,
+    /// Multipart uploads are not supported, so this is always empty.
+    pub files: BTreeMap<String, Value>,
+    pub form:  BTreeMap<String, Value>,
+    /// The body parsed as JSON, or null if it is not a valid JSON.
+    pub json:  Value,
+}
+
+impl Echo {
+    pub fn new(request: &Request<Bytes>, origin: IpAddr, with_payload: bool) -> Self {
+        let path_and_query = request.uri().path_and_query().map_or("/", |p| p.as_str());
+        let payload = with_payload.then(|| {
+            let body = request.body();
+            let content_type = request.headers().get(CONTENT_TYPE);
+            let content_type = content_type.and_then(|value| value.to_str().ok()).unwrap_or("");
+            let json = serde_json::from_slice(body).unwrap_or(Value::Null);
+            if content_type.starts_with("application/x-www-form-urlencoded") {
+                Payload { form: parse_args(body), json, ..default() }
+            } else {
+                Payload { data: String::from_utf8_lossy(body).into(), json, ..default() }
+            }
+        });
+        Self {
+            args: query_args(request),
+            headers: headers(request),
+            origin: origin.to_string(),
+            url: format!("http://{}{path_and_query}", host(request)),
+            payload,
+        }
     }
 }
 
-pub async fn get_and_spawn_httpbin_on_free_port() -> Result<Spawned> {
-    get_and_spawn_httpbin(ide_ci::get_free_port()?).await
+/// Parse the URL-encoded arguments. The repeated ones are collected into arrays.
+pub fn parse_args(input: &[u8]) -> BTreeMap<String, Value> {
+    let mut ret = BTreeMap::<String, Value>::new();
+    for (name, value) in url::form_urlencoded::parse(input) {
+        let value = Value::String(value.into_owned());
+        match ret.entry(name.into_owned()) {
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+            Entry::Occupied(mut entry) => match entry.get_mut() {
+                Value::Array(values) => values.push(value),
+                previous => *previous = Value::Array(vec![previous.take(), value]),
+            },
+        }
+    }
+    ret
+}
+
+fn query_args<T>(request: &Request<T>) -> BTreeMap<String, Value> {
+    parse_args(request.uri().query().unwrap_or("").as_bytes())
+}
+
+fn host<T>(request: &Request<T>) -> &str {
+    request.headers().get(HOST).and_then(|host| host.to_str().ok()).unwrap_or("localhost")
+}
+
+/// Header name in the `Title-Case`, as the original service reports them.
+pub fn title_case(name: &HeaderName) -> String {
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+    };
+    name.as_str().split('-').map(capitalize).join("-")
+}
+
+/// The request headers, with the values of the repeated ones joined by commas.
+pub fn headers<T>(request: &Request<T>) -> BTreeMap<String, String> {
+    let mut ret = BTreeMap::<String, String>::new();
+    for (name, value) in request.headers() {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        match ret.entry(title_case(name)) {
+            Entry::Vacant(entry) => {
+                entry.insert(value.into());
+            }
+            Entry::Occupied(mut entry) => {
+                let joined = entry.get_mut();
+                joined.push(',');
+                joined.push_str(&value);
+            }
+        }
+    }
+    ret
+}
+
+pub fn cookies<T>(request: &Request<T>) -> BTreeMap<String, String> {
+    let headers = request.headers().get_all(COOKIE).iter();
+    let cookies =
+        headers.filter_map(|header| header.to_str().ok()).flat_map(|cookies| cookies.split(';'));
+    cookies
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Decoded `user:password` of the basic authorization, if present.
+pub fn basic_auth_credentials<T>(request: &Request<T>) -> Option<String> {
+    let header = request.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let encoded = header.strip_prefix("Basic ")?;
+    let decoded = data_encoding::BASE64.decode(encoded.trim().as_bytes()).ok()?;
+    String::from_utf8(decoded).ok()
+}
+
+fn set_cookies<'a>(
+    cookies: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<Response<Body>> {
+    let mut response = Response::builder().status(StatusCode::FOUND).header(LOCATION, "/cookies");
+    for (name, value) in cookies {
+        response = response.header(SET_COOKIE, format!("{name}={value}; Path=/"));
+    }
+    Ok(response.body(Body::empty())?)
+}
+
+fn json(status: StatusCode, body: &impl Serialize) -> Result<Response<Body>> {
+    let response = Response::builder().status(status).header(CONTENT_TYPE, "application/json");
+    Ok(response.body(Body::from(serde_json::to_vec_pretty(body)?))?)
+}
+
+fn empty(status: StatusCode) -> Result<Response<Body>> {
+    Ok(Response::builder().status(status).body(Body::empty())?)
+}
+
+fn redirect(location: impl AsRef<str>) -> Result<Response<Body>> {
+    let response =
+        Response::builder().status(StatusCode::FOUND).header(LOCATION, location.as_ref());
+    Ok(response.body(Body::empty())?)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::project::ProcessWrapper;
-
     use super::*;
 
+    #[tokio::test]
+    async fn echoing() -> Result {
+        let spawned = spawn_on_free_port()?;
+        let client = reqwest::Client::new();
+        let url = |path: &str| spawned.url.join(path);
+
+        let response =
+            client.get(url("get?a=1&b=2&b=3")?).header("X-Custom", "value").send().await?;
+        let echo: Echo = serde_json::from_str(&response.text().await?)?;
+        assert_eq!(echo.args["a"], "1");
+        assert_eq!(echo.args["b"], serde_json::json!(["2", "3"]));
+        assert_eq!(echo.headers["X-Custom"], "value");
+        assert_eq!(echo.payload, None);
+
+        let response = client
+            .post(url("post")?)
+            .header(CONTENT_TYPE.as_str(), "application/json")
+            .body(r#"{"x": 1}"#)
+            .send()
+            .await?;
+        let echo: Value = serde_json::from_str(&response.text().await?)?;
+        assert_eq!(echo["json"], serde_json::json!({ "x": 1 }));
+        assert_eq!(echo["data"], r#"{"x": 1}"#);
+
+        let response = client.put(url("put")?).form(&[("field", "value")]).send().await?;
+        let echo: Value = serde_json::from_str(&response.text().await?)?;
+        assert_eq!(echo["form"]["field"], "value");
+        assert_eq!(echo["data"], "");
+
+        let response = client.delete(url("delete")?).send().await?;
+        assert_eq!(response.status().as_u16(), 200);
+        let response = client.get(url("post")?).send().await?;
+        assert_eq!(response.status().as_u16(), 405);
+
+        let response = client.get(url("headers")?).header("X-Other", "other").send().await?;
+        let echo: Value = serde_json::from_str(&response.text().await?)?;
+        assert_eq!(echo["headers"]["X-Other"], "other");
+        Ok(())
+    }
 
     #[tokio::test]
-    #[ignore]
-    async fn spawn() -> Result {
-        let mut spawned = get_and_spawn_httpbin_on_free_port().await?;
-        dbg!(&spawned);
-        spawned.process.wait_ok().await?;
+    async fn other_endpoints() -> Result {
+        let spawned = spawn_on_free_port()?;
+        let client = reqwest::Client::new();
+        let url = |path: &str| spawned.url.join(path);
+
+        let response = client.get(url("status/418")?).send().await?;
+        assert_eq!(response.status().as_u16(), 418);
+        let response = client.get(url("bytes/16")?).send().await?;
+        assert_eq!(response.bytes().await?.len(), 16);
+        let response = client.get(url("redirect/3")?).send().await?;
+        assert_eq!(response.url().path(), "/get");
+
+        let response = client.get(url("basic-auth/user/secret")?).send().await?;
+        assert_eq!(response.status().as_u16(), 401);
+        let request = client.get(url("basic-auth/user/secret")?).basic_auth("user", Some("secret"));
+        let response = request.send().await?;
+        assert_eq!(response.status().as_u16(), 200);
+        let response = client.get(url("hidden-basic-auth/user/secret")?).send().await?;
+        assert_eq!(response.status().as_u16(), 404);
+
+        let response = client.get(url("cookies")?).header("Cookie", "a=1; b=2").send().await?;
+        let cookies: Value = serde_json::from_str(&response.text().await?)?;
+        assert_eq!(cookies, serde_json::json!({ "cookies": { "a": "1", "b": "2" } }));
+        let client =
+            reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build()?;
+        let response = client.get(url("cookies/set?name=value")?).send().await?;
+        assert_eq!(response.status().as_u16(), 302);
+        assert_eq!(response.headers()["set-cookie"], "name=value; Path=/");
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn header_names() {
+        assert_eq!(title_case(&CONTENT_TYPE), "Content-Type");
+        assert_eq!(title_case(&HeaderName::from_static("x-a-b")), "X-A-B");
+    }
+}
@@ -46,7 +46,9 @@ pub mod project;
 pub mod project_manager;
 pub mod release;
 pub mod repo;
+pub mod runner_image;
 pub mod source;
+pub mod test_environment;
 pub mod version;
 
 /// Get version of Enso from the `build.sbt` file contents.
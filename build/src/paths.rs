@@ -19,6 +19,9 @@ pub const EDITION_FILE_ARTIFACT_NAME: &str = "Edition File";
 pub const LIBRARIES_TO_TEST: [&str; 6] =
     ["Tests", "Table_Tests", "Geo_Tests", "Visualization_Tests", "Image_Tests", "Examples_Tests"];
 
+/// Test projects that use a database, so they are run once for each of the tested databases.
+pub const DATABASE_TEST_LIBRARIES: [&str; 2] = ["Table_Tests", "Database_Tests"];
+
 pub const ARCHIVE_EXTENSION: &str = match TARGET_OS {
     OS::Windows => "zip",
     _ => "tar.gz",
@@ -189,6 +192,52 @@ impl Paths {
         Ok(())
     }
 
+    /// Glob pattern matching the JUnit reports written by the standard library tests.
+    pub fn stdlib_test_reports(&self) -> PathBuf {
+        self.test_results.join_iter(["**", "*.xml"])
+    }
+
+    /// Where the JUnit reports of the rerun standard library tests are written. Kept apart from
+    /// [`Paths::test_results`], so the results of the first run are not overwritten.
+    pub fn test_results_rerun(&self) -> PathBuf {
+        self.target.join("test-results-rerun")
+    }
+
+    /// Glob patterns matching the JUnit reports written by the sbt tests of each project.
+    pub fn sbt_test_reports(&self) -> Vec<PathBuf> {
+        ["engine", "lib/scala"]
+            .into_iter()
+            .map(|projects| {
+                self.repo_root.join(projects).join_iter(["*", "target", "test-reports", "*.xml"])
+            })
+            .collect()
+    }
+
+    /// Where the consolidated report of all the test results is written.
+    pub fn test_report(&self) -> PathBuf {
+        self.target.join("test-report.json")
+    }
+
+    /// Where the results of the benchmarks are written.
+    pub fn benchmark_results(&self) -> PathBuf {
+        self.target.join("benchmark-results.json")
+    }
+
+    /// Where the Markdown comparison of the benchmark results against the baseline is written.
+    pub fn benchmark_comparison(&self) -> PathBuf {
+        self.target.join("benchmark-comparison.md")
+    }
+
+    /// Where the markers of the completed engine build steps are stored.
+    pub fn build_step_markers(&self) -> PathBuf {
+        self.target.join("build-steps")
+    }
+
+    /// History of the flaky tests, kept across the builds.
+    pub fn flakiness_history(&self) -> PathBuf {
+        cache_directory().join("test-flakiness.json")
+    }
+
     pub fn stdlib_tests(&self) -> PathBuf {
         self.repo_root.join("test")
     }
@@ -197,6 +246,24 @@ impl Paths {
         self.stdlib_tests().join(test_name)
     }
 
+    /// Names of the test projects present in the repository.
+    pub fn stdlib_test_libraries(&self) -> Result<Vec<String>> {
+        let mut ret = Vec::new();
+        for entry in ide_ci::fs::read_dir(self.stdlib_tests())? {
+            let path = entry?.path();
+            if path.join("package.yaml").exists() {
+                ret.extend(path.file_name().and_then(|name| name.to_str()).map(String::from));
+            }
+        }
+        ret.sort();
+        Ok(ret)
+    }
+
+    /// Sources of the standard library's `Test` library, that the test projects are run with.
+    pub fn test_library_sources(&self) -> PathBuf {
+        self.distribution().join_iter(["lib", "Standard", "Test", "0.0.0-dev", "src"])
+    }
+
     pub fn changelog(&self) -> PathBuf {
         root_to_changelog(&self.repo_root)
     }
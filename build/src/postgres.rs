@@ -1,21 +1,24 @@
 use crate::prelude::*;
 
 use ide_ci::env::new::RawVariable;
-use ide_ci::env::new::TypedVariable;
-use ide_ci::get_free_port;
+use ide_ci::env::Variable;
+use ide_ci::programs::docker::service::Endpoint;
+use ide_ci::programs::docker::service::Readiness;
+use ide_ci::programs::docker::service::Running;
+use ide_ci::programs::docker::service::Service;
 use ide_ci::programs::docker::ContainerId;
-use ide_ci::programs::docker::ImageId;
-use ide_ci::programs::docker::Network;
-use ide_ci::programs::docker::RunOptions;
-use ide_ci::programs::Docker;
-use std::process::Stdio;
-use tokio::io::AsyncBufReadExt;
-use tokio::io::AsyncRead;
-use tokio::io::BufReader;
-use tokio::process::Child;
 
 /// Port used by Postgres in its container.
-const POSTGRES_CONTAINER_DEFAULT_PORT: u16 = 5432;
+pub const POSTGRES_CONTAINER_DEFAULT_PORT: u16 = 5432;
+
+/// Database created for the tests.
+pub const TEST_DATABASE_NAME: &str = "enso_test_db";
+
+/// User the tests connect as.
+pub const TEST_USER: &str = "enso_test_user";
+
+/// Password of the [`TEST_USER`].
+pub const TEST_PASSWORD: &str = "enso_test_password";
 
 /// Environment variables used to configure the Postgres container.
 pub mod env {
@@ -35,34 +38,21 @@ pub mod env {
     }
 }
 
-#[derive(Clone, Debug)]
-pub enum EndpointConfiguration {
-    /// Used when the Postgres container is started directly from host (rather than Docker
-    /// container). In such case the Postgres will be exposed to host network on a given port.
-    Host { port: u16 },
-    /// Used when Postgres is spawned from a container. In such case it will be spawned in a owning
-    /// container's network on the default port.
-    Container { owner: ContainerId },
+/// Owning container, if we are running in one.
+fn owner() -> Option<ContainerId> {
+    crate::env::RunnerContainerName.fetch().ok()
 }
 
-impl EndpointConfiguration {
-    /// Tries to deduce what endpoint should be used for a spawned Postgres service.
-    pub fn deduce() -> Result<Self> {
-        if let Ok(container_name) = std::env::var("ENSO_RUNNER_CONTAINER_NAME") {
-            debug!("Assuming that I am in the Docker container named {container_name}.");
-            Ok(Self::Container { owner: ContainerId(container_name) })
-        } else {
-            // If we are running on the bare machine (i.e. not in container), we spawn postgres
-            // and expose it on a free host port. Then we can directly consume.
-            let port = if port_check::is_local_port_free(POSTGRES_CONTAINER_DEFAULT_PORT) {
-                // Prefer the usual port.
-                POSTGRES_CONTAINER_DEFAULT_PORT
-            } else {
-                get_free_port()?
-            };
-            Ok(Self::Host { port })
-        }
-    }
+/// Tries to deduce what endpoint should be used for a spawned Postgres service, preferring the
+/// usual Postgres port.
+pub fn deduce_endpoint() -> Result<Endpoint> {
+    Endpoint::deduce(owner(), Some(POSTGRES_CONTAINER_DEFAULT_PORT))
+}
+
+/// Like [`deduce_endpoint`] but never uses the default port, so multiple Postgres instances can be
+/// run side by side.
+pub fn deduce_isolated_endpoint() -> Result<Endpoint> {
+    Endpoint::deduce(owner(), None)
 }
 
 #[derive(Clone, Debug)]
@@ -71,86 +61,93 @@ pub struct Configuration {
     pub database_name:      String,
     pub user:               String,
     pub password:           String,
-    pub endpoint:           EndpointConfiguration,
+    pub endpoint:           Endpoint,
     pub version:            String,
+    /// Identifies the run that owns the container, so it can be removed if the run crashes.
+    pub owner:              String,
 }
 
 impl Configuration {
-    pub fn image_id(&self) -> ImageId {
-        ImageId(format!("postgres:{}", &self.version))
+    pub fn service(&self) -> Service {
+        let image = format!("postgres:{}", self.version);
+        // Postgres is started for the initialization first, so its logs are not reliable. Also,
+        // the initialization server does not listen on TCP, so connecting through it makes sure
+        // that the final server is up.
+        let listening_port = match &self.endpoint {
+            Endpoint::Host { .. } => POSTGRES_CONTAINER_DEFAULT_PORT,
+            Endpoint::Container { port, .. } => *port,
+        };
+        let readiness = Readiness::Exec {
+            command: vec![
+                "psql".into(),
+                "--host=127.0.0.1".into(),
+                format!("--port={listening_port}"),
+                format!("--username={}", self.user),
+                format!("--dbname={}", self.database_name),
+                "--command=SELECT 1".into(),
+            ],
+            env:     [("PGPASSWORD".to_string(), self.password.clone())].into_iter().collect(),
+        };
+        let service = Service::new(
+            self.postgres_container.as_str(),
+            image,
+            self.endpoint.clone(),
+            POSTGRES_CONTAINER_DEFAULT_PORT,
+            readiness,
+        )
+        .env(env::container::POSTGRES_DB.name(), &self.database_name)
+        .env(env::container::POSTGRES_USER.name(), &self.user)
+        .env(env::container::POSTGRES_PASSWORD.name(), &self.password)
+        .owned_by(&self.owner);
+        match &self.endpoint {
+            Endpoint::Host { .. } => service,
+            // The owning container's network may already have other Postgres instances.
+            Endpoint::Container { port, .. } =>
+                service.command(["postgres".into(), "-p".into(), port.to_string()]),
+        }
     }
 
-    pub fn set_enso_test_env(&self) -> Result {
-        env::tests::ENSO_DATABASE_TEST_DB_NAME.set(&self.database_name)?;
-        env::tests::ENSO_DATABASE_TEST_HOST.set(match &self.endpoint {
-            EndpointConfiguration::Host { port } => format!("localhost:{port}"),
-            EndpointConfiguration::Container { .. } =>
-                format!("localhost:{POSTGRES_CONTAINER_DEFAULT_PORT}"),
-        })?;
-        env::tests::ENSO_DATABASE_TEST_DB_USER.set(&self.user)?;
-        env::tests::ENSO_DATABASE_TEST_DB_PASSWORD.set(&self.password)?;
-        Ok(())
+    pub fn host(&self) -> String {
+        format!("localhost:{}", self.endpoint.port())
     }
 
-    pub fn clear_enso_test_env(&self) {
-        env::tests::ENSO_DATABASE_TEST_DB_NAME.remove();
-        env::tests::ENSO_DATABASE_TEST_HOST.remove();
-        env::tests::ENSO_DATABASE_TEST_DB_USER.remove();
-        env::tests::ENSO_DATABASE_TEST_DB_PASSWORD.remove();
+    /// Set the test environment for the given command only, rather than for the whole process.
+    ///
+    /// This is needed when multiple test runs, each with its own database, happen concurrently.
+    pub fn apply_enso_test_env(&self, command: &mut impl IsCommandWrapper) -> Result {
+        command
+            .set_env(env::tests::ENSO_DATABASE_TEST_DB_NAME, &self.database_name)?
+            .set_env(env::tests::ENSO_DATABASE_TEST_HOST, &self.host())?
+            .set_env(env::tests::ENSO_DATABASE_TEST_DB_USER, &self.user)?
+            .set_env(env::tests::ENSO_DATABASE_TEST_DB_PASSWORD, &self.password)?;
+        Ok(())
     }
+}
 
-    pub async fn cleanup(&self) -> Result {
-        Docker.remove_container(&self.postgres_container, true).await
-    }
+pub struct PostgresContainer {
+    service: Running,
+    config:  Configuration,
 }
 
-/// Retrieve input from asynchronous reader line by line and feed them into the given function.
-pub async fn process_lines<R: AsyncRead + Unpin>(reader: R, f: impl Fn(String)) -> Result<R> {
-    debug!("Started line processor.");
-    let mut reader = BufReader::new(reader);
-    let mut line = String::new();
-    while reader.read_line(&mut line).await? != 0 {
-        f(std::mem::take(&mut line));
+impl PostgresContainer {
+    pub fn config(&self) -> &Configuration {
+        &self.config
     }
-    Ok(reader.into_inner())
-}
 
-pub async fn process_lines_until<R: AsyncRead + Unpin>(
-    reader: R,
-    f: &impl Fn(&str) -> bool,
-) -> Result<R> {
-    let mut reader = BufReader::new(reader);
-    let mut line = String::new();
-    loop {
-        let bytes_read = reader.read_line(&mut line).await?;
-        ensure!(bytes_read != 0, "Postgresql container closed without being ready!");
-        if f(&line) {
-            break;
-        }
-        line.clear();
+    pub fn service(&self) -> &Running {
+        &self.service
     }
-    Ok(reader.into_inner())
-}
 
-pub struct PostgresContainer {
-    _docker_run: Child,
-    config:      Configuration,
+    /// Remove the container and release its port.
+    pub async fn stop(mut self) -> Result {
+        self.service.remove().await
+    }
 }
 
 impl Drop for PostgresContainer {
     fn drop(&mut self) {
-        self.config.clear_enso_test_env();
-
-        debug!("Will remove the postgres container");
-        let cleanup_future = self.config.cleanup();
-        if let Err(e) = futures::executor::block_on(cleanup_future) {
-            debug!(
-                "Failed to kill the Postgres container named {}: {}",
-                self.config.postgres_container, e
-            );
-        } else {
-            debug!("Postgres container killed.");
-        }
+        // The container itself is removed by the service guard.
+        ide_ci::release_port(self.config.endpoint.port());
     }
 }
 
@@ -158,45 +155,8 @@ pub struct Postgresql;
 
 impl Postgresql {
     pub async fn start(config: Configuration) -> Result<PostgresContainer> {
-        // Attempt cleanup in case previous script run crashed in the middle of this.
-        // Otherwise, postgres container names could collide.
-        let _ = config.cleanup().await;
-
-        let mut opts = RunOptions::new(config.image_id());
-        opts.env(&env::container::POSTGRES_DB, &*config.database_name)?;
-        opts.env(&env::container::POSTGRES_USER, &*config.user)?;
-        opts.env(&env::container::POSTGRES_PASSWORD, &*config.password)?;
-        match &config.endpoint {
-            EndpointConfiguration::Host { port } => {
-                opts.publish_port(*port, POSTGRES_CONTAINER_DEFAULT_PORT);
-            }
-            EndpointConfiguration::Container { owner } => {
-                opts.network = Some(Network::Container(owner.clone()));
-            }
-        }
-        opts.sig_proxy = Some(true);
-        opts.name = Some(config.postgres_container.to_string());
-
-        let mut cmd = Docker.run_cmd(&opts)?;
-        cmd.stderr(Stdio::piped());
-        cmd.kill_on_drop(true);
-        let mut child = cmd.spawn().anyhow_err()?;
-        let stderr = child
-            .stderr
-            .ok_or_else(|| anyhow!("Failed to access standard output of the spawned process!"))?;
-
-        // Wait until container is ready.
-        let check_line = |line: &str| {
-            debug!("ERR: {}", line);
-            line.contains("database system is ready to accept connections")
-        };
-        let stderr = process_lines_until(stderr, &check_line).await?;
-
-        // Put back stream we've been reading and pack the whole thing back for the caller.
-        child.stderr = Some(stderr);
-
-        config.set_enso_test_env()?;
-        Ok(PostgresContainer { _docker_run: child, config })
+        let service = config.service().start().await?;
+        Ok(PostgresContainer { service, config })
     }
 }
 
@@ -204,21 +164,32 @@ impl Postgresql {
 mod tests {
     use super::*;
 
+    #[test]
+    fn isolated_endpoints() -> Result {
+        let first = deduce_isolated_endpoint()?;
+        let second = deduce_isolated_endpoint()?;
+        assert_ne!(first.port(), POSTGRES_CONTAINER_DEFAULT_PORT);
+        assert_ne!(first.port(), second.port());
+        for endpoint in [first, second] {
+            ide_ci::release_port(endpoint.port());
+        }
+        Ok(())
+    }
+
     #[tokio::test]
     #[ignore]
     async fn start_postgres() -> Result {
         let config = Configuration {
             postgres_container: ContainerId("something".into()),
-            endpoint:           EndpointConfiguration::deduce()?,
+            endpoint:           deduce_endpoint()?,
             version:            "latest".into(),
             user:               "test".into(),
             password:           "test".into(),
             database_name:      "test".into(),
+            owner:              "test".into(),
         };
-        let child = Postgresql::start(config).await?;
-        // drop(child);
-        std::mem::forget(child);
-        Ok(())
+        let postgres = Postgresql::start(config).await?;
+        postgres.stop().await
     }
 
     #[tokio::test]
@@ -226,7 +197,7 @@ mod tests {
     async fn test_postgres() -> Result {
         // let config = Configuration {
         //     postgres_container: ContainerId("something".into()),
-        //     endpoint:           EndpointConfiguration::deduce()?,
+        //     endpoint:           deduce_endpoint()?,
         //     version:            "latest".into(),
         //     user:               "test".into(),
         //     password:           "test".into(),
@@ -24,6 +24,7 @@ pub mod engine;
 pub mod gui;
 pub mod ide;
 pub mod project_manager;
+pub mod runtime;
 pub mod wasm;
 
 pub use backend::Backend;
@@ -31,6 +32,7 @@ pub use engine::Engine;
 pub use gui::Gui;
 pub use ide::Ide;
 pub use project_manager::ProjectManager;
+pub use runtime::Runtime;
 pub use wasm::Wasm;
 
 // FIXME: this works for Project Manager bundle-style archives only, not all.
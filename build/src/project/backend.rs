@@ -38,6 +38,7 @@ impl BuildInput {
             config: config.into(),
             inner,
             paths,
+            steps: default(),
         };
         Ok(context)
     }
@@ -62,6 +62,7 @@ impl IsTarget for Engine {
                 .into(),
                 inner: context,
                 paths,
+                steps: default(),
             };
             let artifacts = context.build().await?;
             let engine_distribution =
@@ -61,6 +61,7 @@ impl IsTarget for ProjectManager {
                 .into(),
                 inner: context,
                 paths,
+                steps: default(),
             };
             let artifacts = context.build().await?;
             let engine_distribution =
@@ -0,0 +1,257 @@
+//! Docker image running the Project Manager bundle, so the backend can be deployed as a
+//! container.
+//!
+//! The image is built from a generated Dockerfile. Its healthcheck performs the WebSocket
+//! handshake of the Project Manager's JSON-RPC endpoint, which is also used to smoke test the
+//! freshly built image.
+
+use crate::prelude::*;
+
+use crate::project::backend::Backend;
+use crate::project::Context;
+use crate::project::IsArtifact;
+use crate::project::IsTarget;
+use crate::source::BuildTargetJob;
+use crate::source::GetTargetJob;
+use crate::source::WithDestination;
+use crate::version::Versions;
+
+use derivative::Derivative;
+use ide_ci::programs::docker::service::Endpoint;
+use ide_ci::programs::docker::service::Readiness;
+use ide_ci::programs::docker::service::Service;
+use ide_ci::programs::docker::BuildOptions;
+use ide_ci::programs::docker::ImageId;
+use ide_ci::programs::Docker;
+
+
+
+/// Image that the runtime image is based on.
+pub const BASE_IMAGE: &str = "ubuntu:20.04";
+
+/// Repository of the locally built images, tagged with the Enso version.
+pub const IMAGE_NAME: &str = "enso-runtime";
+
+/// Where the Project Manager bundle is placed in the image.
+pub const INSTALL_DIR: &str = "/opt/enso";
+
+/// Port of the Project Manager's JSON-RPC endpoint.
+pub const PROJECT_MANAGER_PORT: u16 = 30535;
+
+/// File in the artifact directory with the ID of the built image.
+pub const IMAGE_ID_FILE: &str = "image-id";
+
+/// Directory of the build context with the Project Manager bundle.
+const BUNDLE_DIR: &str = "project-manager";
+
+/// Script of the build context with the healthcheck.
+const HEALTHCHECK_SCRIPT: &str = "healthcheck.sh";
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct BuildInput {
+    pub versions: Versions,
+    /// The Linux Project Manager bundle to put into the image.
+    #[derivative(Debug = "ignore")]
+    pub backend:  GetTargetJob<Backend>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Artifact {
+    /// Build context of the image, with the Dockerfile and the Project Manager bundle.
+    pub context: PathBuf,
+    /// The image, as present in the local Docker.
+    pub image:   ImageId,
+}
+
+impl AsRef<Path> for Artifact {
+    fn as_ref(&self) -> &Path {
+        &self.context
+    }
+}
+
+impl IsArtifact for Artifact {}
+
+/// Image labels describing the Enso version and the bundled engines.
+pub fn labels(versions: &Versions, engine_versions: &[Version]) -> BTreeMap<String, String> {
+    [
+        ("org.opencontainers.image.title", "Enso Runtime".to_string()),
+        ("org.opencontainers.image.version", versions.version.to_string()),
+        ("org.enso.edition", versions.edition_name()),
+        ("org.enso.release-mode", versions.release_mode.to_string()),
+        ("org.enso.engine-versions", engine_versions.iter().join(",")),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect()
+}
+
+/// Dockerfile of the image. The GraalVM is given by its directory name in the bundle's `runtime`.
+pub fn dockerfile(labels: &BTreeMap<String, String>, graal_dir: &str) -> String {
+    let labels = labels.iter().map(|(name, value)| format!("{name}={value:?}")).join(" \\\n      ");
+    let healthcheck = "/usr/local/bin/enso-healthcheck";
+    format!(
+        r#"FROM {BASE_IMAGE}
+LABEL {labels}
+RUN useradd --create-home enso
+COPY --chown=enso:enso {BUNDLE_DIR} {INSTALL_DIR}
+COPY {HEALTHCHECK_SCRIPT} {healthcheck}
+RUN chmod 755 {healthcheck}
+ENV JAVA_HOME={INSTALL_DIR}/runtime/{graal_dir}
+ENV PATH={INSTALL_DIR}/bin:$JAVA_HOME/bin:$PATH
+USER enso
+EXPOSE {PROJECT_MANAGER_PORT}
+HEALTHCHECK --interval=10s --timeout=5s --start-period=120s --retries=3 CMD ["{healthcheck}"]
+ENTRYPOINT ["{INSTALL_DIR}/bin/project-manager"]
+CMD ["-Dproject-manager.server.host=0.0.0.0", "-Dproject-manager.server.port={PROJECT_MANAGER_PORT}"]
+"#
+    )
+}
+
+/// Script checking that the Project Manager accepts the WebSocket connections of JSON-RPC.
+pub fn healthcheck_script() -> String {
+    let request = [
+        "GET / HTTP/1.1",
+        "Host: localhost",
+        "Upgrade: websocket",
+        "Connection: Upgrade",
+        // The sample nonce from RFC 6455, the server's answer is not validated anyway.
+        "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==",
+        "Sec-WebSocket-Version: 13",
+    ]
+    .iter()
+    .map(|line| format!("{line}\\r\\n"))
+    .join("");
+    format!(
+        r#"#!/bin/bash
+set -e
+exec 3<>/dev/tcp/127.0.0.1/{PROJECT_MANAGER_PORT}
+printf '{request}\r\n' >&3
+read -r status <&3
+[[ "$status" == *" 101 "* ]]
+"#
+    )
+}
+
+/// Name of the single GraalVM directory in the bundle's `runtime` directory.
+pub fn bundled_graal_dir(bundle: &crate::paths::generated::ProjectManager) -> Result<String> {
+    let entries = ide_ci::fs::read_dir(&bundle.runtime)?
+        .map(|entry| Ok(entry?.file_name().as_str().to_string()))
+        .collect::<Result<Vec<_>>>()?;
+    match entries.as_slice() {
+        [graal_dir] => Ok(graal_dir.clone()),
+        _ => bail!(
+            "Expected exactly one GraalVM in {}, found: {}.",
+            bundle.runtime.display(),
+            entries.join(", ")
+        ),
+    }
+}
+
+/// Build the image from the prepared context and note its ID there.
+pub async fn build_image(context: &Path, tags: Vec<String>) -> Result<ImageId> {
+    let mut options = BuildOptions::new(context);
+    options.tags = tags;
+    let image = Docker.build(options).await?;
+    ide_ci::fs::write(context.join(IMAGE_ID_FILE), &image.0)?;
+    Ok(image)
+}
+
+/// Start a container of the image and wait until the Project Manager accepts the JSON-RPC
+/// connections, as reported by the image's healthcheck.
+pub async fn smoke_test(image: &ImageId) -> Result {
+    let name = format!("enso-runtime-smoke-test-{}", Uuid::new_v4());
+    let endpoint = Endpoint::Host { port: ide_ci::get_free_port()? };
+    let service =
+        Service::new(name, image.0.clone(), endpoint, PROJECT_MANAGER_PORT, Readiness::Healthy);
+    service.start().await?.stop().await
+}
+
+/// Docker image tag for the given version. Tags cannot contain the semver's `+`.
+pub fn image_tag(version: &Version) -> String {
+    format!("{IMAGE_NAME}:{}", version.to_string().replace('+', "-"))
+}
+
+/// The runtime Docker image. It is always a Linux image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Runtime;
+
+impl IsTarget for Runtime {
+    type BuildInput = BuildInput;
+    type Artifact = Artifact;
+
+    fn artifact_name(&self) -> String {
+        "runtime-image".into()
+    }
+
+    /// If the image is not present in the local Docker (e.g. the artifact was downloaded), it is
+    /// built from the context.
+    fn adapt_artifact(self, path: impl AsRef<Path>) -> BoxFuture<'static, Result<Self::Artifact>> {
+        let context = path.as_ref().to_path_buf();
+        async move {
+            let known_image = ide_ci::fs::read_to_string(context.join(IMAGE_ID_FILE))
+                .ok()
+                .map(|id| ImageId(id.trim().into()));
+            let is_present = match &known_image {
+                Some(image) => Docker.inspect_image(&image.0).await.is_ok(),
+                None => false,
+            };
+            let image = match known_image {
+                Some(image) if is_present => image,
+                _ => {
+                    info!("Building the runtime image from {}.", context.display());
+                    build_image(&context, default()).await?
+                }
+            };
+            Ok(Artifact { context, image })
+        }
+        .boxed()
+    }
+
+    fn build_internal(
+        &self,
+        context: Context,
+        job: BuildTargetJob<Self>,
+    ) -> BoxFuture<'static, Result<Self::Artifact>> {
+        let WithDestination { inner, destination } = job;
+        let BuildInput { versions, backend } = inner;
+        let this = *self;
+        async move {
+            ensure!(TARGET_OS == OS::Linux, "The runtime image can be built only on Linux.");
+            let backend = Backend { target_os: OS::Linux }.get(context, backend).await?;
+            ide_ci::fs::reset_dir(&destination)?;
+            ide_ci::fs::mirror_directory(&backend.path, destination.join(BUNDLE_DIR)).await?;
+
+            let labels = labels(&versions, &backend.engine_versions);
+            let graal_dir = bundled_graal_dir(&backend.path)?;
+            ide_ci::fs::write(destination.join("Dockerfile"), dockerfile(&labels, &graal_dir))?;
+            ide_ci::fs::write(destination.join(HEALTHCHECK_SCRIPT), healthcheck_script())?;
+
+            let image = build_image(&destination, vec![image_tag(&versions.version)]).await?;
+            smoke_test(&image).instrument(info_span!("Smoke testing the runtime image.")).await?;
+            this.adapt_artifact(destination).await
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_dockerfile() {
+        let versions = Versions::new(Version::parse("2022.1.1-nightly.2022-06-01").unwrap());
+        let labels = labels(&versions, &[versions.version.clone()]);
+        assert_eq!(labels["org.enso.release-mode"], "true");
+        let dockerfile = dockerfile(&labels, "graalvm-ce-java11-21.3.0");
+        assert!(dockerfile.contains(
+            "LABEL org.enso.edition=\"2022.1.1-nightly.2022-06-01\" \\\n      \
+             org.enso.engine-versions=\"2022.1.1-nightly.2022-06-01\""
+        ));
+        assert!(dockerfile.contains("ENV JAVA_HOME=/opt/enso/runtime/graalvm-ce-java11-21.3.0\n"));
+        assert!(dockerfile.contains("CMD [\"/usr/local/bin/enso-healthcheck\"]\n"));
+        assert!(healthcheck_script().contains("printf 'GET / HTTP/1.1\\r\\nHost: localhost\\r\\n"));
+        assert_eq!(image_tag(&Version::parse("1.0.0+abc").unwrap()), "enso-runtime:1.0.0-abc");
+    }
+}
@@ -0,0 +1,193 @@
+//! Docker image of the self-hosted runners, with the toolchains of the build preinstalled.
+//!
+//! Otherwise, [`RunContext::prepare_build_env`](crate::engine::context::RunContext) would
+//! download and install GraalVM, SBT, flatc and others in each job. GraalVM and SBT are placed
+//! in the goodie database, where the build looks for them; the other programs are put on `PATH`.
+
+use crate::prelude::*;
+
+use crate::config::lowest_matching;
+use crate::config::RecognizedProgram;
+use crate::engine::FLATC_VERSION;
+use crate::get_graal_version;
+use crate::get_java_major_version;
+
+use ide_ci::deploy::runner;
+use ide_ci::deploy::runner::DIRECTORY_WITH_RUNNER_PACKAGE;
+use ide_ci::goodies::graalvm::GraalVM;
+use ide_ci::goodies::sbt;
+
+
+
+/// Image that the runner image is based on.
+pub const BASE_IMAGE: &str = "ubuntu:20.04";
+
+/// The user running the runner in the image.
+pub const USER: &str = "runner";
+
+/// Home directory of the [`USER`].
+pub const HOME: &str = "/home/runner";
+
+/// Name of the goodie database directory in the home directory, see
+/// [`GoodieDatabase`](ide_ci::goodie::GoodieDatabase).
+pub const GOODIES_DIR: &str = ".enso-ci";
+
+/// Name of the Dockerfile stage with the toolchains but without the runner.
+pub const TOOLCHAINS_STAGE: &str = "toolchains";
+
+/// Used when `build-config.yaml` has no requirement for Node.
+pub const DEFAULT_NODE_VERSION: Version = Version::new(16, 15, 0);
+
+/// Used when `build-config.yaml` has no requirement for wasm-pack.
+pub const DEFAULT_WASM_PACK_VERSION: Version = Version::new(0, 10, 2);
+
+/// Versions and packages of the toolchains to install in the image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toolchains {
+    /// The Linux GraalVM package.
+    pub graal_url:         Url,
+    /// Name of the directory that the GraalVM package is extracted to.
+    pub graal_dir:         String,
+    pub node_version:      Version,
+    pub wasm_pack_version: Version,
+    pub flatc_version:     Version,
+}
+
+impl Toolchains {
+    /// Get the versions required by `build.sbt` and `build-config.yaml`.
+    pub async fn deduce(
+        octocrab: &Octocrab,
+        build_sbt: impl AsRef<Path>,
+        config: &crate::config::Config,
+    ) -> Result<Self> {
+        let build_sbt_content = ide_ci::fs::read_to_string(build_sbt)?;
+        let graalvm = GraalVM {
+            client:        octocrab,
+            graal_version: get_graal_version(&build_sbt_content)?,
+            java_version:  get_java_major_version(&build_sbt_content)?,
+            os:            OS::Linux,
+            arch:          Arch::X86_64,
+        };
+        let required = |program: RecognizedProgram, default: Version| -> Result<Version> {
+            match config.required_versions.get(&program) {
+                Some(requirement) => lowest_matching(&requirement.version).with_context(|| {
+                    format!("Cannot pick the {program} version satisfying `{requirement}`.")
+                }),
+                None => Ok(default),
+            }
+        };
+        Ok(Self {
+            graal_url:         graalvm.url().await?,
+            graal_dir:         graalvm.directory_name(),
+            node_version:      required(RecognizedProgram::Node, DEFAULT_NODE_VERSION)?,
+            wasm_pack_version: required(RecognizedProgram::WasmPack, DEFAULT_WASM_PACK_VERSION)?,
+            flatc_version:     required(RecognizedProgram::Flatc, FLATC_VERSION)?,
+        })
+    }
+}
+
+/// Dockerfile of the runner's image.
+///
+/// The [`TOOLCHAINS_STAGE`] installs the toolchains and the final stage, named after the
+/// runner's target, adds the runner package from the [`DIRECTORY_WITH_RUNNER_PACKAGE`].
+pub fn dockerfile(runner: &runner::Config, toolchains: &Toolchains) -> Result<String> {
+    ensure!(runner.os == OS::Linux, "Only Linux runner images can be generated.");
+    let Toolchains { graal_url, graal_dir, node_version, wasm_pack_version, flatc_version } =
+        toolchains;
+    let goodies = format!("{HOME}/{GOODIES_DIR}");
+    let packages = [
+        "build-essential",
+        "ca-certificates",
+        "curl",
+        "git",
+        "golang-go",
+        "libssl-dev",
+        "pkg-config",
+        "unzip",
+        "xz-utils",
+        "zip",
+    ]
+    .into_iter()
+    .chain(runner.runner.docker_access.then(|| "docker.io"))
+    .join(" ");
+    let node = format!("node-v{node_version}-linux-x64");
+    let wasm_pack = format!("wasm-pack-v{wasm_pack_version}-x86_64-unknown-linux-musl");
+    let target = &runner.runner.target;
+    let runner_dir = runner.guest_runner_dir();
+    let runner_dir = runner_dir.display();
+    Ok(format!(
+        r#"FROM {BASE_IMAGE} AS {TOOLCHAINS_STAGE}
+ENV DEBIAN_FRONTEND=noninteractive
+RUN apt-get update \
+ && apt-get install -y --no-install-recommends {packages} \
+ && rm -rf /var/lib/apt/lists/*
+RUN useradd --create-home --home-dir {HOME} {USER}
+USER {USER}
+WORKDIR {HOME}
+RUN mkdir -p {goodies}/cache \
+ && curl -fsSL {graal_url} | tar -xz -C {goodies} \
+ && curl -fsSL {sbt_url} | tar -xz -C {goodies}
+ENV JAVA_HOME={goodies}/{graal_dir}
+ENV GRAALVM_HOME=$JAVA_HOME
+RUN $JAVA_HOME/bin/gu install native-image python R
+RUN mkdir -p {HOME}/.node \
+ && curl -fsSL https://nodejs.org/dist/v{node_version}/{node}.tar.xz | tar -xJ --strip-components=1 -C {HOME}/.node
+RUN curl -fsSL https://sh.rustup.rs | sh -s -- -y --profile minimal --default-toolchain none
+RUN curl -fsSL https://github.com/rustwasm/wasm-pack/releases/download/v{wasm_pack_version}/{wasm_pack}.tar.gz | tar -xz --strip-components=1 -C {HOME}/.cargo/bin {wasm_pack}/wasm-pack
+RUN curl -fsSL -o miniconda.sh https://repo.anaconda.com/miniconda/Miniconda3-latest-Linux-x86_64.sh \
+ && sh miniconda.sh -b -p {HOME}/.conda \
+ && rm miniconda.sh \
+ && {HOME}/.conda/bin/conda install -y --freeze-installed flatbuffers={flatc_version}
+ENV PATH={HOME}/.cargo/bin:{HOME}/.node/bin:{HOME}/.conda/bin:{goodies}/sbt/bin:$JAVA_HOME/bin:$PATH
+
+FROM {TOOLCHAINS_STAGE} AS {target}
+COPY --chown={USER}:{USER} {DIRECTORY_WITH_RUNNER_PACKAGE} {runner_dir}
+USER root
+RUN {runner_dir}/bin/installdependencies.sh && rm -rf /var/lib/apt/lists/*
+USER {USER}
+WORKDIR {runner_dir}
+"#,
+        sbt_url = sbt::URL,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ide_ci::models::config::RepoContext;
+    use ide_ci::models::config::Runner;
+    use ide_ci::models::config::RunnerLocation;
+
+    #[test]
+    fn generated_dockerfile() -> Result {
+        let repo = RepoContext { owner: "enso-org".into(), name: "enso".into() };
+        let mut runner = runner::Config {
+            location:    RunnerLocation::Repository(repo),
+            runner:      Runner::new("engine"),
+            os:          OS::Linux,
+            server_name: "server".into(),
+            index:       0,
+        };
+        let toolchains = Toolchains {
+            graal_url:         "https://example.com/graalvm-ce-java11-linux-amd64-21.3.0.tar.gz"
+                .parse()?,
+            graal_dir:         "graalvm-ce-java11-21.3.0".into(),
+            node_version:      DEFAULT_NODE_VERSION,
+            wasm_pack_version: DEFAULT_WASM_PACK_VERSION,
+            flatc_version:     FLATC_VERSION,
+        };
+        let text = dockerfile(&runner, &toolchains)?;
+        assert!(text.starts_with("FROM ubuntu:20.04 AS toolchains\n"));
+        assert!(text.contains("ENV JAVA_HOME=/home/runner/.enso-ci/graalvm-ce-java11-21.3.0\n"));
+        assert!(text.contains("flatbuffers=1.12.0\n"));
+        assert!(text.contains("FROM toolchains AS runner\n"));
+        assert!(text.contains("COPY --chown=runner:runner runner /runner\n"));
+        assert!(!text.contains("docker.io"));
+
+        runner.runner.docker_access = true;
+        assert!(dockerfile(&runner, &toolchains)?.contains(" zip docker.io\n"));
+        runner.os = OS::Windows;
+        assert!(dockerfile(&runner, &toolchains).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! The services used by the standard library tests, as a Docker Compose stack.
+//!
+//! This allows developers to start the same services that the test runs use with just
+//! `docker compose up`, and then run the tests from their IDE or the Enso runner.
+//!
+//! The httpbin stand-in ([`crate::httpbin`]) is not part of the stack, as it runs in-process. The
+//! original httpbin image differs from it on some endpoints, so the stand-in is to be served
+//! separately, on [`HTTPBIN_PORT`], through the build script's `httpbin` command.
+
+use crate::prelude::*;
+
+use crate::postgres;
+use crate::project::runtime;
+
+use ide_ci::env::new::RawVariable;
+use ide_ci::env::Variable;
+use ide_ci::models::compose::Command;
+use ide_ci::models::compose::Compose;
+use ide_ci::models::compose::Healthcheck;
+use ide_ci::models::compose::Service;
+
+
+
+/// Name of the written Compose file.
+pub const COMPOSE_FILE: &str = "docker-compose.yml";
+
+/// Name of the written file with the environment for running the tests against the services.
+pub const TEST_ENV_FILE: &str = "test.env";
+
+/// Port on the host where the httpbin stand-in is expected.
+pub const HTTPBIN_PORT: u16 = 8080;
+
+pub const POSTGRES_SERVICE: &str = "postgres";
+pub const PROJECT_MANAGER_SERVICE: &str = "project-manager";
+
+#[derive(Clone, Debug)]
+pub struct Configuration {
+    /// Tag of the Postgres image, like `14`.
+    pub postgres_version:      String,
+    /// The runtime image, as built by [`runtime::Runtime`].
+    pub project_manager_image: String,
+}
+
+impl Configuration {
+    pub fn postgres(&self) -> Service {
+        let port = postgres::POSTGRES_CONTAINER_DEFAULT_PORT;
+        // Postgres is started for the initialization first, which listens only on the Unix
+        // socket. Checking the TCP connection makes sure that the final server is up.
+        let test = Command::exec([
+            "CMD".into(),
+            "pg_isready".into(),
+            "--host=127.0.0.1".into(),
+            format!("--username={}", postgres::TEST_USER),
+            format!("--dbname={}", postgres::TEST_DATABASE_NAME),
+        ]);
+        let healthcheck = Healthcheck {
+            test: Some(test),
+            interval: Some("2s".into()),
+            retries: Some(30),
+            ..default()
+        };
+        let environment = [
+            (postgres::env::container::POSTGRES_DB.name(), postgres::TEST_DATABASE_NAME),
+            (postgres::env::container::POSTGRES_USER.name(), postgres::TEST_USER),
+            (postgres::env::container::POSTGRES_PASSWORD.name(), postgres::TEST_PASSWORD),
+        ];
+        Service {
+            environment: environment
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            ports: vec![format!("{port}:{port}")],
+            healthcheck: Some(healthcheck),
+            ..Service::image(format!("postgres:{}", self.postgres_version))
+        }
+    }
+
+    /// The image defines its own healthcheck.
+    pub fn project_manager(&self) -> Service {
+        let port = runtime::PROJECT_MANAGER_PORT;
+        Service {
+            ports: vec![format!("{port}:{port}")],
+            ..Service::image(&self.project_manager_image)
+        }
+    }
+
+    pub fn compose(&self) -> Compose {
+        let services = [
+            (POSTGRES_SERVICE, self.postgres()),
+            (PROJECT_MANAGER_SERVICE, self.project_manager()),
+        ];
+        Compose {
+            services: services.into_iter().map(|(name, service)| (name.into(), service)).collect(),
+            ..default()
+        }
+    }
+
+    /// Environment that makes the tests use the services of the stack.
+    pub fn test_environment(&self) -> BTreeMap<String, String> {
+        use postgres::env::tests::*;
+        let postgres_host = format!("localhost:{}", postgres::POSTGRES_CONTAINER_DEFAULT_PORT);
+        [
+            (ENSO_DATABASE_TEST_DB_NAME.name(), postgres::TEST_DATABASE_NAME.into()),
+            (ENSO_DATABASE_TEST_HOST.name(), postgres_host),
+            (ENSO_DATABASE_TEST_DB_USER.name(), postgres::TEST_USER.into()),
+            (ENSO_DATABASE_TEST_DB_PASSWORD.name(), postgres::TEST_PASSWORD.into()),
+            (crate::httpbin::env::Url::NAME, format!("http://localhost:{HTTPBIN_PORT}/")),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+    }
+
+    /// Write the Compose file and the test environment file to the given directory.
+    pub fn write(&self, output_dir: &Path) -> Result {
+        ide_ci::fs::create_dir_if_missing(output_dir)?;
+        output_dir.join(COMPOSE_FILE).write_as_yaml(&self.compose())?;
+        let environment =
+            self.test_environment().into_iter().map(|(name, value)| format!("{name}={value}\n"));
+        ide_ci::fs::write(output_dir.join(TEST_ENV_FILE), environment.collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose_file() -> Result {
+        let config = Configuration {
+            postgres_version:      "14".into(),
+            project_manager_image: "enso-runtime:2022.1.1".into(),
+        };
+        let compose = serde_yaml::to_string(&config.compose())?;
+        let compose = serde_yaml::from_str::<Compose>(&compose)?;
+        let database = &compose.services[POSTGRES_SERVICE];
+        assert_eq!(database.image.as_deref(), Some("postgres:14"));
+        assert_eq!(database.ports, ["5432:5432"]);
+        assert_eq!(database.environment["POSTGRES_USER"], postgres::TEST_USER);
+        assert_eq!(compose.services.len(), 2);
+        assert_eq!(compose.services[PROJECT_MANAGER_SERVICE].ports, ["30535:30535"]);
+
+        let environment = config.test_environment();
+        assert_eq!(environment["ENSO_DATABASE_TEST_HOST"], "localhost:5432");
+        assert_eq!(environment["ENSO_HTTP_TEST_HTTPBIN_URL"], "http://localhost:8080/");
+        Ok(())
+    }
+}
@@ -4,6 +4,8 @@ use crate::actions::env;
 use std::io::Write;
 
 pub mod definition;
+pub mod graph;
+pub mod local;
 
 /// Check if we are running in an environment that looks like being spawned by GitHub Actions
 /// workflow.
@@ -0,0 +1,144 @@
+//! Metadata of custom actions, i.e. the contents of `action.yml` files.
+//!
+//! Only composite actions are modelled, as these are what we generate: they allow sharing a
+//! sequence of steps between jobs and workflows.
+
+use crate::prelude::*;
+
+use crate::actions::workflow::definition::Step;
+
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Action {
+    pub name:        String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs:      BTreeMap<String, Input>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub outputs:     BTreeMap<String, Output>,
+    pub runs:        Runs,
+}
+
+impl Action {
+    /// Create a composite action running the given steps.
+    ///
+    /// Fails if any of the `run` steps does not specify its shell, as composite actions require it.
+    pub fn new_composite(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        steps: Vec<Step>,
+    ) -> Result<Self> {
+        let name = name.into();
+        for step in &steps {
+            if let Some(run) = &step.run {
+                ensure!(
+                    step.shell.is_some(),
+                    "Step `{}` of composite action `{name}` does not specify a shell.",
+                    step.name.as_ref().unwrap_or(run)
+                );
+            }
+        }
+        Ok(Self {
+            name,
+            description: description.into(),
+            inputs: default(),
+            outputs: default(),
+            runs: Runs::Composite { steps },
+        })
+    }
+
+    pub fn add_input(&mut self, name: impl Into<String>, input: Input) {
+        self.inputs.insert(name.into(), input);
+    }
+
+    pub fn add_output(&mut self, name: impl Into<String>, output: Output) {
+        self.outputs.insert(name.into(), output);
+    }
+}
+
+/// Action's input. Its value is always a string.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Input {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required:    Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default:     Option<String>,
+}
+
+impl Input {
+    pub fn new(description: impl Into<String>, required: bool) -> Self {
+        Self {
+            description: Some(description.into()),
+            required:    Some(required),
+            default:     None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Output {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Typically an expression referring to an output of one of the action's steps.
+    pub value:       String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "using", rename_all = "kebab-case")]
+pub enum Runs {
+    Composite { steps: Vec<Step> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::workflow::definition::expression;
+    use crate::actions::workflow::definition::Shell;
+
+    const COMPOSITE_ACTION: &str = r#"
+name: Setup
+description: Prepare the toolchain.
+inputs:
+  token:
+    description: Token for the GitHub API.
+    required: true
+outputs:
+  version:
+    description: Installed version.
+    value: ${{ steps.install.outputs.version }}
+runs:
+  using: composite
+  steps:
+    - id: install
+      run: ./install.sh
+      shell: bash
+      env:
+        TOKEN: ${{ inputs.token }}
+"#;
+
+    #[test]
+    fn round_trip() -> Result {
+        let action = serde_yaml::from_str::<Action>(COMPOSITE_ACTION)?;
+        let Runs::Composite { steps } = &action.runs;
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].env["TOKEN"], expression::input("token").wrapped());
+        let expected = serde_yaml::from_str::<serde_json::Value>(COMPOSITE_ACTION)?;
+        let actual = serde_json::to_value(&action)?;
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn shell_is_required() {
+        let step = Step { run: Some("./install.sh".into()), ..default() };
+        assert!(Action::new_composite("Setup", "", vec![step.clone()]).is_err());
+        let step = Step { shell: Some(Shell::Bash), ..step };
+        assert!(Action::new_composite("Setup", "", vec![step]).is_ok());
+    }
+}
@@ -0,0 +1,827 @@
+//! Typed model of the GitHub Actions expressions, i.e. the contents of `${{ }}` blocks.
+//!
+//! Expressions can be built through the constructor functions in this module or parsed from text.
+//! Parsing validates context names, function names and function arities, so typos are caught when
+//! the workflow is generated rather than when it is run.
+//!
+//! See: <https://docs.github.com/en/actions/learn-github-actions/expressions>
+
+use crate::prelude::*;
+
+use serde::de::Error as _;
+use std::iter::Peekable;
+use std::str::Chars;
+
+pub mod evaluation;
+
+pub use evaluation::EvaluationContext;
+
+
+
+/// Wrap the given expression text into the `${{ }}` block.
+pub fn wrap(expression: impl Display) -> String {
+    format!("${{{{ {} }}}}", expression)
+}
+
+/// Strip the `${{ }}` block from the given text, if it is present.
+pub fn unwrap(text: &str) -> &str {
+    let trimmed = text.trim();
+    trimmed
+        .strip_prefix("${{")
+        .and_then(|rest| rest.strip_suffix("}}"))
+        .map_or(trimmed, |inner| inner.trim())
+}
+
+/// Named object that can be accessed in an expression.
+///
+/// See: <https://docs.github.com/en/actions/learn-github-actions/contexts>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Context {
+    Github,
+    Env,
+    Job,
+    Jobs,
+    Steps,
+    Runner,
+    Secrets,
+    Strategy,
+    Matrix,
+    Needs,
+    Inputs,
+}
+
+/// Built-in function that can be called in an expression.
+///
+/// See: <https://docs.github.com/en/actions/learn-github-actions/expressions#functions>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, strum::Display, strum::EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Function {
+    #[strum(serialize = "contains")]
+    Contains,
+    #[strum(serialize = "startsWith")]
+    StartsWith,
+    #[strum(serialize = "endsWith")]
+    EndsWith,
+    #[strum(serialize = "format")]
+    Format,
+    #[strum(serialize = "join")]
+    Join,
+    #[strum(serialize = "toJSON")]
+    ToJson,
+    #[strum(serialize = "fromJSON")]
+    FromJson,
+    #[strum(serialize = "hashFiles")]
+    HashFiles,
+    #[strum(serialize = "success")]
+    Success,
+    #[strum(serialize = "always")]
+    Always,
+    #[strum(serialize = "cancelled")]
+    Cancelled,
+    #[strum(serialize = "failure")]
+    Failure,
+}
+
+impl Function {
+    /// Range of argument counts accepted by the function.
+    pub fn arity(self) -> std::ops::RangeInclusive<usize> {
+        match self {
+            Function::Contains | Function::StartsWith | Function::EndsWith => 2..=2,
+            Function::Format | Function::HashFiles => 1..=usize::MAX,
+            Function::Join => 1..=2,
+            Function::ToJson | Function::FromJson => 1..=1,
+            Function::Success | Function::Always | Function::Cancelled | Function::Failure => 0..=0,
+        }
+    }
+
+    /// Check if the function can be called with the given number of arguments.
+    pub fn check_arity(self, argument_count: usize) -> Result {
+        let arity = self.arity();
+        ensure!(
+            arity.contains(&argument_count),
+            "Function `{self}` does not accept {argument_count} argument(s), expected between {} \
+            and {}.",
+            arity.start(),
+            arity.end()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, strum::Display)]
+pub enum Operator {
+    #[strum(serialize = "<")]
+    Less,
+    #[strum(serialize = "<=")]
+    LessOrEqual,
+    #[strum(serialize = ">")]
+    Greater,
+    #[strum(serialize = ">=")]
+    GreaterOrEqual,
+    #[strum(serialize = "==")]
+    Equal,
+    #[strum(serialize = "!=")]
+    NotEqual,
+    #[strum(serialize = "&&")]
+    And,
+    #[strum(serialize = "||")]
+    Or,
+}
+
+impl Operator {
+    /// Binding strength of the operator. Higher binds tighter.
+    pub fn precedence(self) -> u8 {
+        match self {
+            Operator::Less
+            | Operator::LessOrEqual
+            | Operator::Greater
+            | Operator::GreaterOrEqual => 4,
+            Operator::Equal | Operator::NotEqual => 3,
+            Operator::And => 2,
+            Operator::Or => 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Null => write!(f, "null"),
+            Literal::Bool(value) => write!(f, "{value}"),
+            Literal::Number(value) => write!(f, "{value}"),
+            // Single quotes are escaped by doubling them.
+            Literal::String(value) => write!(f, "'{}'", value.replace('\'', "''")),
+        }
+    }
+}
+
+/// A GitHub Actions expression.
+///
+/// `Display` renders the bare expression, as used in `if` conditions. Use [`Expression::wrapped`]
+/// or convert into `String` to get the `${{ }}` form, as used in other places.
+///
+/// Rust strings do not convert into expressions, as it would be ambiguous whether they are the
+/// expression text or a string literal. Use [`Expression::parse`] or [`Expression::literal`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Literal(Literal),
+    Context(Context),
+    /// Property dereference, e.g. `github.event`.
+    Property {
+        target: Box<Expression>,
+        name:   String,
+    },
+    /// Index dereference, e.g. `github['event']`.
+    Index {
+        target: Box<Expression>,
+        index:  Box<Expression>,
+    },
+    /// Logical negation, e.g. `!cancelled()`.
+    Not(Box<Expression>),
+    Binary {
+        lhs:      Box<Expression>,
+        operator: Operator,
+        rhs:      Box<Expression>,
+    },
+    Call {
+        function:  Function,
+        arguments: Vec<Expression>,
+    },
+}
+
+impl Expression {
+    /// Parse the expression text. The `${{ }}` wrapper is optional.
+    pub fn parse(text: &str) -> Result<Self> {
+        let expression = unwrap(text);
+        Parser::new(expression)
+            .and_then(|parser| parser.parse_all())
+            .with_context(|| format!("Failed to parse GitHub Actions expression: `{expression}`"))
+    }
+
+    /// String literal, e.g. `'dependabot'`.
+    pub fn literal(value: impl Into<String>) -> Self {
+        Literal::String(value.into()).into()
+    }
+
+    /// Call a built-in function, checking its arity.
+    pub fn call(
+        function: Function,
+        arguments: impl IntoIterator<Item: Into<Expression>>,
+    ) -> Result<Self> {
+        let arguments = arguments.into_iter().map(into).collect_vec();
+        function.check_arity(arguments.len())?;
+        Ok(Expression::Call { function, arguments })
+    }
+
+    /// Text of the expression wrapped in `${{ }}`.
+    pub fn wrapped(&self) -> String {
+        wrap(self)
+    }
+
+    /// Text to be placed in a field accepting expressions, like an environment variable or an
+    /// action input.
+    ///
+    /// Literals are placed as their value, unless it would be taken for an expression. Other
+    /// expressions are wrapped in `${{ }}`.
+    pub fn to_field_value(&self) -> String {
+        match self {
+            Expression::Literal(Literal::String(value)) if !value.contains("${{") => value.clone(),
+            Expression::Literal(literal @ (Literal::Bool(_) | Literal::Number(_))) =>
+                literal.to_string(),
+            _ => self.wrapped(),
+        }
+    }
+
+    pub fn property(self, name: impl Into<String>) -> Self {
+        Expression::Property { target: Box::new(self), name: name.into() }
+    }
+
+    pub fn index_by(self, index: impl Into<Expression>) -> Self {
+        Expression::Index { target: Box::new(self), index: Box::new(index.into()) }
+    }
+
+    pub fn binary(self, operator: Operator, rhs: impl Into<Expression>) -> Self {
+        Expression::Binary { lhs: Box::new(self), operator, rhs: Box::new(rhs.into()) }
+    }
+
+    pub fn and(self, rhs: impl Into<Expression>) -> Self {
+        self.binary(Operator::And, rhs)
+    }
+
+    pub fn or(self, rhs: impl Into<Expression>) -> Self {
+        self.binary(Operator::Or, rhs)
+    }
+
+    pub fn equal_to(self, rhs: impl Into<Expression>) -> Self {
+        self.binary(Operator::Equal, rhs)
+    }
+
+    pub fn not_equal_to(self, rhs: impl Into<Expression>) -> Self {
+        self.binary(Operator::NotEqual, rhs)
+    }
+
+    pub fn less_than(self, rhs: impl Into<Expression>) -> Self {
+        self.binary(Operator::Less, rhs)
+    }
+
+    pub fn greater_than(self, rhs: impl Into<Expression>) -> Self {
+        self.binary(Operator::Greater, rhs)
+    }
+
+    /// Join all given expressions with `||`. Returns `None` if there are none.
+    pub fn any(expressions: impl IntoIterator<Item = Expression>) -> Option<Self> {
+        expressions.into_iter().reduce(|lhs, rhs| lhs.or(rhs))
+    }
+
+    /// Join all given expressions with `&&`. Returns `None` if there are none.
+    pub fn all(expressions: impl IntoIterator<Item = Expression>) -> Option<Self> {
+        expressions.into_iter().reduce(|lhs, rhs| lhs.and(rhs))
+    }
+
+    /// Call the function on this expression and all of its subexpressions.
+    pub fn visit(&self, f: &mut impl FnMut(&Expression)) {
+        f(self);
+        match self {
+            Expression::Literal(_) | Expression::Context(_) => {}
+            Expression::Property { target, .. } => target.visit(f),
+            Expression::Index { target, index } => {
+                target.visit(f);
+                index.visit(f);
+            }
+            Expression::Not(operand) => operand.visit(f),
+            Expression::Binary { lhs, rhs, .. } => {
+                lhs.visit(f);
+                rhs.visit(f);
+            }
+            Expression::Call { arguments, .. } =>
+                arguments.iter().for_each(|argument| argument.visit(f)),
+        }
+    }
+
+    /// The dereferenced object and the member name, for both `target.name` and
+    /// `target['name']`.
+    pub fn member(&self) -> Option<(&Expression, &str)> {
+        match self {
+            Expression::Property { target, name } => Some((target, name)),
+            Expression::Index { target, index } => match index.as_ref() {
+                Expression::Literal(Literal::String(name)) => Some((target, name)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The job outputs referenced as `needs.<job>.outputs.<name>`, as `(job, name)` pairs.
+    pub fn needs_outputs(&self) -> Vec<(String, String)> {
+        let mut ret = Vec::new();
+        self.visit(&mut |expression| {
+            let reference = expression.member().and_then(|(outputs, output)| {
+                let (job, outputs_name) = outputs.member()?;
+                let (needs, job) = job.member()?;
+                let is_needs = matches!(needs, Expression::Context(Context::Needs));
+                (is_needs && outputs_name == "outputs").then(|| (job.into(), output.into()))
+            });
+            ret.extend(reference);
+        });
+        ret
+    }
+
+    /// Binding strength of the expression's top-level construct. Higher binds tighter.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Binary { operator, .. } => operator.precedence(),
+            Expression::Not(_) => 5,
+            _ => 6,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut Formatter<'_>, minimum_precedence: u8) -> std::fmt::Result {
+        if self.precedence() < minimum_precedence {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Literal(literal) => write!(f, "{literal}"),
+            Expression::Context(context) => write!(f, "{context}"),
+            Expression::Property { target, name } => {
+                target.fmt_operand(f, 6)?;
+                write!(f, ".{name}")
+            }
+            Expression::Index { target, index } => {
+                target.fmt_operand(f, 6)?;
+                write!(f, "[{index}]")
+            }
+            Expression::Not(operand) => {
+                write!(f, "!")?;
+                operand.fmt_operand(f, 5)
+            }
+            Expression::Binary { lhs, operator, rhs } => {
+                // Operators are left-associative, so the right operand of the same precedence
+                // needs to be parenthesized.
+                let precedence = operator.precedence();
+                lhs.fmt_operand(f, precedence)?;
+                write!(f, " {operator} ")?;
+                rhs.fmt_operand(f, precedence + 1)
+            }
+            Expression::Call { function, arguments } => {
+                write!(f, "{function}({})", arguments.iter().join(", "))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Expression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Expression::parse(s)
+    }
+}
+
+impl std::ops::Not for Expression {
+    type Output = Expression;
+
+    fn not(self) -> Self::Output {
+        Expression::Not(Box::new(self))
+    }
+}
+
+impl From<Context> for Expression {
+    fn from(context: Context) -> Self {
+        Expression::Context(context)
+    }
+}
+
+impl From<Literal> for Expression {
+    fn from(literal: Literal) -> Self {
+        Expression::Literal(literal)
+    }
+}
+
+impl From<bool> for Expression {
+    fn from(value: bool) -> Self {
+        Literal::Bool(value).into()
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(value: f64) -> Self {
+        Literal::Number(value).into()
+    }
+}
+
+/// Renders the expression wrapped in `${{ }}`, so it can be placed in any string-valued field.
+impl From<Expression> for String {
+    fn from(expression: Expression) -> Self {
+        expression.wrapped()
+    }
+}
+
+impl From<&Expression> for String {
+    fn from(expression: &Expression) -> Self {
+        expression.wrapped()
+    }
+}
+
+impl Serialize for Expression {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where S: serde::Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Expression {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where D: serde::Deserializer<'de> {
+        let text = String::deserialize(deserializer)?;
+        Expression::parse(&text).map_err(D::Error::custom)
+    }
+}
+
+
+// ====================
+// === Constructors ===
+// ====================
+
+pub fn github() -> Expression {
+    Context::Github.into()
+}
+
+pub fn runner() -> Expression {
+    Context::Runner.into()
+}
+
+pub fn env(name: impl Into<String>) -> Expression {
+    Expression::from(Context::Env).property(name)
+}
+
+pub fn secret(name: impl Into<String>) -> Expression {
+    Expression::from(Context::Secrets).property(name)
+}
+
+pub fn matrix(name: impl Into<String>) -> Expression {
+    Expression::from(Context::Matrix).property(name)
+}
+
+pub fn input(name: impl Into<String>) -> Expression {
+    Expression::from(Context::Inputs).property(name)
+}
+
+/// Output of a job that the current job depends on, e.g. `needs.build.outputs.version`.
+pub fn needs_output(job_id: impl Into<String>, output: impl Into<String>) -> Expression {
+    Expression::from(Context::Needs).property(job_id).property("outputs").property(output)
+}
+
+/// Output of a job of the current workflow, e.g. `jobs.build.outputs.version`.
+///
+/// Available only when defining the outputs of a reusable workflow.
+pub fn job_output(job_id: impl Into<String>, output: impl Into<String>) -> Expression {
+    Expression::from(Context::Jobs).property(job_id).property("outputs").property(output)
+}
+
+/// Output of a previous step in the current job, e.g. `steps.prepare.outputs.version`.
+pub fn step_output(step_id: impl Into<String>, output: impl Into<String>) -> Expression {
+    Expression::from(Context::Steps).property(step_id).property("outputs").property(output)
+}
+
+pub fn contains(haystack: impl Into<Expression>, needle: impl Into<Expression>) -> Expression {
+    Expression::Call {
+        function:  Function::Contains,
+        arguments: vec![haystack.into(), needle.into()],
+    }
+}
+
+pub fn starts_with(text: impl Into<Expression>, prefix: impl Into<Expression>) -> Expression {
+    Expression::Call {
+        function:  Function::StartsWith,
+        arguments: vec![text.into(), prefix.into()],
+    }
+}
+
+pub fn ends_with(text: impl Into<Expression>, suffix: impl Into<Expression>) -> Expression {
+    Expression::Call { function: Function::EndsWith, arguments: vec![text.into(), suffix.into()] }
+}
+
+pub fn format(
+    format_string: impl Into<String>,
+    arguments: impl IntoIterator<Item: Into<Expression>>,
+) -> Expression {
+    let format_string = Expression::literal(format_string);
+    let arguments = once(format_string).chain(arguments.into_iter().map(into)).collect();
+    Expression::Call { function: Function::Format, arguments }
+}
+
+pub fn to_json(value: impl Into<Expression>) -> Expression {
+    Expression::Call { function: Function::ToJson, arguments: vec![value.into()] }
+}
+
+pub fn from_json(value: impl Into<Expression>) -> Expression {
+    Expression::Call { function: Function::FromJson, arguments: vec![value.into()] }
+}
+
+pub fn always() -> Expression {
+    Expression::Call { function: Function::Always, arguments: vec![] }
+}
+
+pub fn success() -> Expression {
+    Expression::Call { function: Function::Success, arguments: vec![] }
+}
+
+pub fn failure() -> Expression {
+    Expression::Call { function: Function::Failure, arguments: vec![] }
+}
+
+pub fn cancelled() -> Expression {
+    Expression::Call { function: Function::Cancelled, arguments: vec![] }
+}
+
+
+// ==============
+// === Parser ===
+// ==============
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Identifier(String),
+    Number(f64),
+    String(String),
+    Operator(Operator),
+    Bang,
+    Dot,
+    Comma,
+    OpenParen,
+    CloseParen,
+    OpenBracket,
+    CloseBracket,
+    Star,
+}
+
+/// Consume the next character if it is the expected one.
+fn consume_char(chars: &mut Peekable<Chars>, expected: char) -> bool {
+    chars.next_if_eq(&expected).is_some()
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut ret = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '.' => Token::Dot,
+            ',' => Token::Comma,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '[' => Token::OpenBracket,
+            ']' => Token::CloseBracket,
+            '*' => Token::Star,
+            '!' if consume_char(&mut chars, '=') => Token::Operator(Operator::NotEqual),
+            '!' => Token::Bang,
+            '=' if consume_char(&mut chars, '=') => Token::Operator(Operator::Equal),
+            '<' if consume_char(&mut chars, '=') => Token::Operator(Operator::LessOrEqual),
+            '<' => Token::Operator(Operator::Less),
+            '>' if consume_char(&mut chars, '=') => Token::Operator(Operator::GreaterOrEqual),
+            '>' => Token::Operator(Operator::Greater),
+            '&' if consume_char(&mut chars, '&') => Token::Operator(Operator::And),
+            '|' if consume_char(&mut chars, '|') => Token::Operator(Operator::Or),
+            '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\'') if consume_char(&mut chars, '\'') => value.push('\''),
+                        Some('\'') => break,
+                        Some(c) => value.push(c),
+                        None => bail!("Unterminated string literal: '{value}"),
+                    }
+                }
+                Token::String(value)
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut text = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '.') {
+                    text.push(c);
+                }
+                let value = if let Some(hex) = text.strip_prefix("0x") {
+                    i64::from_str_radix(hex, 16).map(|value| value as f64).anyhow_err()
+                } else {
+                    text.parse::<f64>().anyhow_err()
+                };
+                Token::Number(value.with_context(|| format!("Invalid number literal: {text}"))?)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = String::from(c);
+                while let Some(c) =
+                    chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+                {
+                    name.push(c);
+                }
+                Token::Identifier(name)
+            }
+            c => bail!("Unexpected character: `{c}`"),
+        };
+        ret.push(token);
+    }
+    Ok(ret)
+}
+
+/// Recursive descent parser over the tokenized expression.
+struct Parser {
+    tokens:   Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Result<Self> {
+        Ok(Self { tokens: tokenize(text)?, position: 0 })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let ret = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        ret
+    }
+
+    fn consume_if(&mut self, expected: &Token) -> bool {
+        let matches = self.peek() == Some(expected);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, expected: Token) -> Result {
+        match self.advance() {
+            Some(token) if token == expected => Ok(()),
+            other => bail!("Expected {expected:?}, found {other:?}."),
+        }
+    }
+
+    fn parse_all(mut self) -> Result<Expression> {
+        let ret = self.parse_binary(1)?;
+        if let Some(token) = self.peek() {
+            bail!("Unexpected trailing token: {token:?}.");
+        }
+        Ok(ret)
+    }
+
+    /// Parse a chain of binary operators that bind at least as tight as `minimum_precedence`.
+    fn parse_binary(&mut self, minimum_precedence: u8) -> Result<Expression> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(Token::Operator(operator)) = self.peek().cloned() {
+            let precedence = operator.precedence();
+            if precedence < minimum_precedence {
+                break;
+            }
+            self.position += 1;
+            let rhs = self.parse_binary(precedence + 1)?;
+            lhs = lhs.binary(operator, rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression> {
+        if self.consume_if(&Token::Bang) {
+            Ok(!self.parse_unary()?)
+        } else {
+            self.parse_postfix()
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expression> {
+        let mut ret = self.parse_primary()?;
+        loop {
+            if self.consume_if(&Token::Dot) {
+                ret = match self.advance() {
+                    Some(Token::Identifier(name)) => ret.property(name),
+                    Some(Token::Star) => ret.property("*"),
+                    other => bail!("Expected property name after `.`, found {other:?}."),
+                };
+            } else if self.consume_if(&Token::OpenBracket) {
+                let index = if self.consume_if(&Token::Star) {
+                    Expression::literal("*")
+                } else {
+                    self.parse_binary(1)?
+                };
+                self.expect(Token::CloseBracket)?;
+                ret = ret.index_by(index);
+            } else {
+                break Ok(ret);
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression> {
+        match self.advance() {
+            Some(Token::OpenParen) => {
+                let ret = self.parse_binary(1)?;
+                self.expect(Token::CloseParen)?;
+                Ok(ret)
+            }
+            Some(Token::Number(value)) => Ok(value.into()),
+            Some(Token::String(value)) => Ok(Expression::literal(value)),
+            Some(Token::Identifier(name)) => match name.to_lowercase().as_str() {
+                "true" => Ok(true.into()),
+                "false" => Ok(false.into()),
+                "null" => Ok(Literal::Null.into()),
+                _ if self.consume_if(&Token::OpenParen) => {
+                    let function = Function::from_str(&name)
+                        .map_err(|_| anyhow!("Unknown function: `{name}`."))?;
+                    let mut arguments = Vec::new();
+                    if !self.consume_if(&Token::CloseParen) {
+                        loop {
+                            arguments.push(self.parse_binary(1)?);
+                            if self.consume_if(&Token::CloseParen) {
+                                break;
+                            }
+                            self.expect(Token::Comma)?;
+                        }
+                    }
+                    Expression::call(function, arguments)
+                }
+                _ => Context::from_str(&name)
+                    .map(Expression::Context)
+                    .map_err(|_| anyhow!("Unknown context: `{name}`.")),
+            },
+            other => bail!("Unexpected token: {other:?}."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(text: &str) -> Result {
+        let expression = Expression::parse(text)?;
+        assert_eq!(expression.to_string(), text);
+        assert_eq!(Expression::parse(&expression.wrapped())?, expression);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_and_render() -> Result {
+        round_trip("github.event.pull_request.user.login == 'dependabot'")?;
+        round_trip("startsWith(runner.name, 'GitHub Actions') || !contains(matrix.os, 'mac')")?;
+        round_trip("success() || failure()")?;
+        round_trip("(github.ref == 'refs/heads/main' || always()) && needs.build.outputs.ok")?;
+        round_trip("github['event'].commits[0].message != null")?;
+        round_trip("format('{0}-{1}', runner.os, 'It''s') == toJSON(fromJSON('[1, 2]'))")?;
+        Ok(())
+    }
+
+    #[test]
+    fn builders_match_parsed() -> Result {
+        let built = needs_output("prepare", "ENSO_VERSION").wrapped();
+        assert_eq!(built, "${{ needs.prepare.outputs.ENSO_VERSION }}");
+        let built = runner().property("os").equal_to(Expression::literal("Windows")).or(!success());
+        assert_eq!(Expression::parse("runner.os == 'Windows' || !success()")?, built);
+        Ok(())
+    }
+
+    #[test]
+    fn field_values() {
+        assert_eq!(Expression::literal("us-west-1").to_field_value(), "us-west-1");
+        assert_eq!(Expression::from(true).to_field_value(), "true");
+        assert_eq!(Expression::literal("${{ oops }}").to_field_value(), "${{ '${{ oops }}' }}");
+        assert_eq!(secret("GITHUB_TOKEN").to_field_value(), "${{ secrets.GITHUB_TOKEN }}");
+    }
+
+    #[test]
+    fn needs_output_references() -> Result {
+        let expression = Expression::parse(
+            "needs.build.outputs.id != needs['build-docs'].outputs['id-long'] \
+             && needs.build.result == 'success' && steps.build.outputs.id",
+        )?;
+        let expected = [("build", "id"), ("build-docs", "id-long")];
+        let expected = expected.map(|(job, output)| (job.to_string(), output.to_string()));
+        assert_eq!(expression.needs_outputs(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn typos_are_rejected() {
+        assert!(Expression::parse("githb.event").is_err());
+        assert!(Expression::parse("startWith(github.ref, 'a')").is_err());
+        assert!(Expression::parse("contains(github.ref)").is_err());
+        assert!(Expression::parse("github.ref == ").is_err());
+        assert!(Expression::parse("'unterminated").is_err());
+    }
+}
@@ -0,0 +1,362 @@
+//! Evaluation of the expressions outside of GitHub, e.g. when running a workflow locally.
+//!
+//! The values are represented as JSON, just like the contexts that GitHub provides. The semantics
+//! (loose equality, case-insensitive string comparison, truthiness) follow the GitHub
+//! documentation. `hashFiles` is not supported.
+
+use crate::prelude::*;
+
+use crate::actions::workflow::definition::expression::Context;
+use crate::actions::workflow::definition::expression::Expression;
+use crate::actions::workflow::definition::expression::Function;
+use crate::actions::workflow::definition::expression::Literal;
+use crate::actions::workflow::definition::expression::Operator;
+
+use serde_json::Value;
+use std::cmp::Ordering;
+
+
+
+/// Data available to the evaluated expressions.
+#[derive(Clone, Debug, Default)]
+pub struct EvaluationContext {
+    /// Values of the contexts. Contexts not present here evaluate to `null`.
+    pub contexts: HashMap<Context, Value>,
+    /// Whether any of the previous steps (or the needed jobs) have failed.
+    ///
+    /// This is what the `success()` and `failure()` status functions check.
+    pub failed:   bool,
+}
+
+impl EvaluationContext {
+    pub fn set(&mut self, context: Context, value: impl Into<Value>) {
+        self.contexts.insert(context, value.into());
+    }
+
+    /// Get a mutable reference to the context, initializing it with an empty object if needed.
+    pub fn object_mut(&mut self, context: Context) -> &mut serde_json::Map<String, Value> {
+        let value = self.contexts.entry(context).or_insert_with(|| Value::Object(default()));
+        if !value.is_object() {
+            *value = Value::Object(default());
+        }
+        // Cannot fail, we just made sure it is an object.
+        value.as_object_mut().unwrap()
+    }
+}
+
+impl Expression {
+    /// Evaluate the expression within the given context.
+    pub fn evaluate(&self, context: &EvaluationContext) -> Result<Value> {
+        Ok(match self {
+            Expression::Literal(literal) => match literal {
+                Literal::Null => Value::Null,
+                Literal::Bool(value) => Value::Bool(*value),
+                Literal::Number(value) => number(*value),
+                Literal::String(value) => Value::String(value.clone()),
+            },
+            Expression::Context(name) => context.contexts.get(name).cloned().unwrap_or_default(),
+            Expression::Property { target, name } => {
+                let target = target.evaluate(context)?;
+                property(&target, name)
+            }
+            Expression::Index { target, index } => {
+                let target = target.evaluate(context)?;
+                match index.evaluate(context)? {
+                    Value::Number(index) => index
+                        .as_f64()
+                        .and_then(|index| target.as_array()?.get(index as usize).cloned())
+                        .unwrap_or_default(),
+                    index => property(&target, &to_string(&index)),
+                }
+            }
+            Expression::Not(operand) => Value::Bool(!is_truthy(&operand.evaluate(context)?)),
+            Expression::Binary { lhs, operator, rhs } => {
+                let lhs = lhs.evaluate(context)?;
+                // Logical operators short-circuit and yield one of the operands, not a boolean.
+                match operator {
+                    Operator::And if !is_truthy(&lhs) => return Ok(lhs),
+                    Operator::Or if is_truthy(&lhs) => return Ok(lhs),
+                    Operator::And | Operator::Or => return rhs.evaluate(context),
+                    _ => {}
+                }
+                let rhs = rhs.evaluate(context)?;
+                let ordering = compare(&lhs, &rhs);
+                Value::Bool(match operator {
+                    Operator::Equal => ordering == Some(Ordering::Equal),
+                    Operator::NotEqual => ordering != Some(Ordering::Equal),
+                    Operator::Less => ordering == Some(Ordering::Less),
+                    Operator::LessOrEqual =>
+                        matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    Operator::Greater => ordering == Some(Ordering::Greater),
+                    Operator::GreaterOrEqual =>
+                        matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                    Operator::And | Operator::Or => unreachable!("Handled above."),
+                })
+            }
+            Expression::Call { function, arguments } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(context))
+                    .collect::<Result<Vec<_>>>()?;
+                call(*function, &arguments, context)?
+            }
+        })
+    }
+
+    /// Evaluate the expression as a condition, like in the `if` keys.
+    pub fn evaluate_condition(&self, context: &EvaluationContext) -> Result<bool> {
+        Ok(is_truthy(&self.evaluate(context)?))
+    }
+
+    /// Check if the expression calls any of the status functions, like `always()`.
+    ///
+    /// If the `if` condition does not, GitHub implicitly combines it with `success()`.
+    pub fn uses_status_function(&self) -> bool {
+        match self {
+            Expression::Literal(_) | Expression::Context(_) => false,
+            Expression::Property { target, .. } => target.uses_status_function(),
+            Expression::Index { target, index } =>
+                target.uses_status_function() || index.uses_status_function(),
+            Expression::Not(operand) => operand.uses_status_function(),
+            Expression::Binary { lhs, rhs, .. } =>
+                lhs.uses_status_function() || rhs.uses_status_function(),
+            Expression::Call { function, arguments } =>
+                matches!(
+                    function,
+                    Function::Success | Function::Always | Function::Cancelled | Function::Failure
+                ) || arguments.iter().any(Expression::uses_status_function),
+        }
+    }
+}
+
+/// Replace all the `${{ }}` blocks in the text with the values of the expressions.
+pub fn substitute(text: &str, context: &EvaluationContext) -> Result<String> {
+    let mut ret = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${{") {
+        ret.push_str(&rest[..start]);
+        let body = &rest[start + 3..];
+        let end = find_block_end(body)
+            .with_context(|| format!("Unterminated expression in text: {text}"))?;
+        let expression = Expression::parse(&body[..end])?;
+        ret.push_str(&to_string(&expression.evaluate(context)?));
+        rest = &body[end + 2..];
+    }
+    ret.push_str(rest);
+    Ok(ret)
+}
+
+/// Parse all the `${{ }}` blocks in the text.
+pub fn embedded(text: &str) -> Result<Vec<Expression>> {
+    let mut ret = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${{") {
+        let body = &rest[start + 3..];
+        let end = find_block_end(body)
+            .with_context(|| format!("Unterminated expression in text: {text}"))?;
+        ret.push(Expression::parse(&body[..end])?);
+        rest = &body[end + 2..];
+    }
+    Ok(ret)
+}
+
+/// Find the closing `}}` of the expression block, skipping the string literals.
+fn find_block_end(body: &str) -> Option<usize> {
+    let mut in_string = false;
+    let mut chars = body.char_indices().peekable();
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            // The escaped quote (`''`) is just two consecutive toggles.
+            '\'' => in_string = !in_string,
+            '}' if !in_string && chars.peek().map(|(_, ch)| *ch) == Some('}') =>
+                return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Convert the value into a string, as done when it is substituted into a text.
+pub fn to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Number(number) => match number.as_f64() {
+            Some(value) if value.fract() == 0.0 && value.abs() < 1e15 =>
+                format!("{}", value as i64),
+            _ => number.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64().map_or(false, |n| n != 0.0 && !n.is_nan()),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+fn number(value: f64) -> Value {
+    serde_json::Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+fn property(target: &Value, name: &str) -> Value {
+    match target {
+        // Property names are case-insensitive.
+        Value::Object(map) => map
+            .get(name)
+            .or_else(|| map.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, v)| v))
+            .cloned()
+            .unwrap_or_default(),
+        _ => Value::Null,
+    }
+}
+
+/// Convert the value to a number, as done for the comparisons of values of different types.
+fn to_number(value: &Value) -> f64 {
+    match value {
+        Value::Null => 0.0,
+        Value::Bool(value) => f64::from(u8::from(*value)),
+        Value::Number(number) => number.as_f64().unwrap_or(f64::NAN),
+        Value::String(text) if text.trim().is_empty() => 0.0,
+        Value::String(text) => text.trim().parse().unwrap_or(f64::NAN),
+        Value::Array(_) | Value::Object(_) => f64::NAN,
+    }
+}
+
+fn compare(lhs: &Value, rhs: &Value) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (Value::String(lhs), Value::String(rhs)) =>
+            Some(lhs.to_lowercase().cmp(&rhs.to_lowercase())),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        // Arrays and objects are only equal to themselves.
+        (Value::Array(_) | Value::Object(_), _) | (_, Value::Array(_) | Value::Object(_)) =>
+            (lhs == rhs).then(|| Ordering::Equal),
+        _ => to_number(lhs).partial_cmp(&to_number(rhs)),
+    }
+}
+
+fn call(function: Function, arguments: &[Value], context: &EvaluationContext) -> Result<Value> {
+    function.check_arity(arguments.len())?;
+    let text = |index: usize| to_string(&arguments[index]).to_lowercase();
+    Ok(match function {
+        Function::Contains => match &arguments[0] {
+            Value::Array(items) => Value::Bool(
+                items.iter().any(|item| compare(item, &arguments[1]) == Some(Ordering::Equal)),
+            ),
+            _ => Value::Bool(text(0).contains(&text(1))),
+        },
+        Function::StartsWith => Value::Bool(text(0).starts_with(&text(1))),
+        Function::EndsWith => Value::Bool(text(0).ends_with(&text(1))),
+        Function::Format => Value::String(format(&to_string(&arguments[0]), &arguments[1..])?),
+        Function::Join => {
+            let separator = arguments.get(1).map_or(",".into(), to_string);
+            Value::String(match &arguments[0] {
+                Value::Array(items) => items.iter().map(to_string).join(&separator),
+                other => to_string(other),
+            })
+        }
+        Function::ToJson => Value::String(serde_json::to_string_pretty(&arguments[0])?),
+        Function::FromJson => serde_json::from_str(&to_string(&arguments[0]))?,
+        Function::HashFiles => bail!("Function `{function}` is not supported outside of GitHub."),
+        Function::Success => Value::Bool(!context.failed),
+        Function::Always => Value::Bool(true),
+        Function::Cancelled => Value::Bool(false),
+        Function::Failure => Value::Bool(context.failed),
+    })
+}
+
+/// Implementation of the `format` function: `{N}` is replaced with N-th argument, while `{{` and
+/// `}}` are the escaped braces.
+fn format(format_string: &str, arguments: &[Value]) -> Result<String> {
+    let mut ret = String::new();
+    let mut chars = format_string.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.next_if_eq(&'{').is_some() => ret.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => ret.push('}'),
+            '{' => {
+                let index: String = chars.by_ref().take_while(|ch| *ch != '}').collect();
+                let index: usize = index
+                    .parse()
+                    .with_context(|| format!("Invalid placeholder in: {format_string}"))?;
+                let argument = arguments
+                    .get(index)
+                    .with_context(|| format!("Missing argument {index} for: {format_string}"))?;
+                ret.push_str(&to_string(argument));
+            }
+            _ => ret.push(ch),
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> EvaluationContext {
+        let mut context = EvaluationContext::default();
+        context.set(Context::Github, json!({"ref": "refs/heads/develop", "event_name": "push"}));
+        context.set(Context::Env, json!({"COUNT": "3", "EMPTY": ""}));
+        context.set(Context::Steps, json!({"prepare": {"outputs": {"version": "1.2.3"}}}));
+        context.set(Context::Matrix, json!({"os": ["Linux", "Windows"]}));
+        context
+    }
+
+    fn evaluate(text: &str) -> Value {
+        Expression::parse(text).unwrap().evaluate(&context()).unwrap()
+    }
+
+    #[test]
+    fn evaluating() {
+        assert_eq!(evaluate("github.ref == 'REFS/HEADS/DEVELOP'"), json!(true));
+        assert_eq!(evaluate("github['event_name']"), json!("push"));
+        assert_eq!(evaluate("steps.prepare.outputs.version"), json!("1.2.3"));
+        assert_eq!(evaluate("steps.missing.outputs.version"), Value::Null);
+        assert_eq!(evaluate("env.COUNT == 3"), json!(true));
+        assert_eq!(evaluate("env.COUNT > 10"), json!(false));
+        assert_eq!(evaluate("env.EMPTY || 'default'"), json!("default"));
+        assert_eq!(evaluate("env.COUNT && 'set'"), json!("set"));
+        assert_eq!(evaluate("!env.EMPTY"), json!(true));
+        assert_eq!(evaluate("matrix.os[1]"), json!("Windows"));
+        assert_eq!(evaluate("contains(matrix.os, 'linux')"), json!(true));
+        assert_eq!(evaluate("startsWith(github.ref, 'refs/heads/')"), json!(true));
+        assert_eq!(evaluate("format('{0}-{{{1}}}', 'a', 2)"), json!("a-{2}"));
+        assert_eq!(evaluate("join(matrix.os, ', ')"), json!("Linux, Windows"));
+        assert_eq!(evaluate("fromJSON('[1, 2]')[1]"), json!(2));
+        assert_eq!(evaluate("success() && !failure()"), json!(true));
+    }
+
+    #[test]
+    fn substituting() -> Result {
+        let text = "v${{ steps.prepare.outputs.version }} on ${{ format('{0}}}', github.ref) }}";
+        assert_eq!(substitute(text, &context())?, "v1.2.3 on refs/heads/develop}");
+        assert_eq!(substitute("${{ 1 }} ${{ null }}|", &context())?, "1 |");
+        assert!(substitute("${{ github.ref", &context()).is_err());
+
+        let expressions = embedded(text)?;
+        assert_eq!(expressions, [
+            Expression::parse("steps.prepare.outputs.version")?,
+            Expression::parse("format('{0}}}', github.ref)")?,
+        ]);
+        assert!(embedded("needs.build.outputs.id")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn status_functions() -> Result {
+        assert!(!Expression::parse("github.ref == 'main'")?.uses_status_function());
+        assert!(Expression::parse("always() && github.ref == 'main'")?.uses_status_function());
+        let mut context = context();
+        context.failed = true;
+        assert!(Expression::parse("failure()")?.evaluate_condition(&context)?);
+        assert!(!Expression::parse("success()")?.evaluate_condition(&context)?);
+        Ok(())
+    }
+}
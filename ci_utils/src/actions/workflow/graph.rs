@@ -0,0 +1,293 @@
+//! Rendering the job dependency graph of a workflow, as Graphviz DOT or Mermaid flowchart.
+
+use crate::prelude::*;
+
+use crate::actions::workflow::definition::expression::evaluation::embedded;
+use crate::actions::workflow::definition::Expression;
+use crate::actions::workflow::definition::Job;
+use crate::actions::workflow::definition::Workflow;
+
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+
+
+/// A job in the graph, with the properties that are worth showing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Node {
+    pub id:        String,
+    pub name:      String,
+    pub runs_on:   Vec<String>,
+    /// The `if` condition of the job.
+    pub condition: Option<String>,
+    /// Matrix dimensions, each described as `name: values`.
+    pub matrix:    Vec<String>,
+    /// Reusable workflow called by the job.
+    pub calls:     Option<String>,
+}
+
+impl Node {
+    pub fn new(id: impl Into<String>, job: &Job) -> Self {
+        let runs_on = job
+            .runs_on
+            .iter()
+            .map(|label| match serde_json::to_value(label) {
+                Ok(serde_json::Value::String(label)) => label,
+                _ => format!("{label:?}"),
+            })
+            .collect();
+        let matrix = job.strategy.iter().flat_map(|strategy| {
+            strategy.matrix.dimensions.iter().map(|(name, values)| format!("{name}: {values}"))
+        });
+        Self {
+            id: id.into(),
+            name: job.name.clone(),
+            runs_on,
+            condition: job.r#if.as_ref().map(ToString::to_string),
+            matrix: matrix.collect(),
+            calls: job.uses.clone(),
+        }
+    }
+
+    /// Lines of the node's label.
+    pub fn label_lines(&self) -> Vec<String> {
+        let mut lines = vec![self.name.clone()];
+        if !self.runs_on.is_empty() {
+            lines.push(format!("runs-on: {}", self.runs_on.join(", ")));
+        }
+        lines.extend(self.matrix.iter().map(|dimension| format!("matrix {dimension}")));
+        if let Some(calls) = &self.calls {
+            lines.push(format!("calls: {calls}"));
+        }
+        if let Some(condition) = &self.condition {
+            lines.push(format!("if: {condition}"));
+        }
+        lines
+    }
+}
+
+/// The `needs` relation. Points from the needed job to the job that needs it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge {
+    pub from:    String,
+    pub to:      String,
+    /// Outputs of the needed job that are used by the dependent one.
+    pub outputs: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobGraph {
+    pub title: String,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl JobGraph {
+    pub fn new(workflow: &Workflow) -> Result<Self> {
+        let nodes = workflow.jobs.iter().map(|(id, job)| Node::new(id, job)).collect();
+        let mut edges = Vec::new();
+        for (id, job) in &workflow.jobs {
+            let used = used_needs_outputs(job)
+                .with_context(|| format!("Failed to find the outputs used by the job {id}."))?;
+            for needed in &job.needs {
+                let outputs = workflow
+                    .jobs
+                    .get(needed)
+                    .map(|needed_job| {
+                        needed_job
+                            .outputs
+                            .keys()
+                            .filter(|output| used.contains(&(needed.clone(), (*output).clone())))
+                            .cloned()
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                edges.push(Edge { from: needed.clone(), to: id.clone(), outputs });
+            }
+        }
+        Ok(Self { title: workflow.name.clone(), nodes, edges })
+    }
+
+    /// Render the graph in the Graphviz DOT language.
+    pub fn to_dot(&self) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut ret = format!("digraph {} {{\n", quote(&self.title));
+        ret += "    rankdir=LR;\n    node [shape=box];\n";
+        for node in &self.nodes {
+            let label = quote(&node.label_lines().join("\n")).replace('\n', "\\n");
+            ret += &format!("    {} [label={}];\n", quote(&node.id), label);
+        }
+        for edge in &self.edges {
+            let attributes = if edge.outputs.is_empty() {
+                String::new()
+            } else {
+                format!(" [label={}]", quote(&edge.outputs.join("\n")).replace('\n', "\\n"))
+            };
+            ret += &format!("    {} -> {}{attributes};\n", quote(&edge.from), quote(&edge.to));
+        }
+        ret += "}\n";
+        ret
+    }
+
+    /// Render the graph as a Mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        let mut ret = format!("---\ntitle: {}\n---\nflowchart LR\n", self.title);
+        for node in &self.nodes {
+            let label = node.label_lines().iter().map(|line| mermaid_escape(line)).join("<br/>");
+            ret += &format!("    {}[\"{label}\"]\n", mermaid_id(&node.id));
+        }
+        for edge in &self.edges {
+            let arrow = if edge.outputs.is_empty() {
+                "-->".to_string()
+            } else {
+                let outputs =
+                    edge.outputs.iter().map(|output| mermaid_escape(output)).join("<br/>");
+                format!("-->|\"{outputs}\"|")
+            };
+            ret += &format!("    {} {arrow} {}\n", mermaid_id(&edge.from), mermaid_id(&edge.to));
+        }
+        ret
+    }
+}
+
+/// The `needs.<job>.outputs.<name>` references anywhere in the job definition, as `(job, name)`.
+fn used_needs_outputs(job: &Job) -> Result<BTreeSet<(String, String)>> {
+    let mut expressions = Vec::new();
+    collect_expressions(&serde_json::to_value(job)?, None, &mut expressions)?;
+    Ok(expressions.iter().flat_map(Expression::needs_outputs).collect())
+}
+
+/// Gather the expressions from the `${{ }}` blocks in the strings of the definition.
+fn collect_expressions(value: &Value, key: Option<&str>, out: &mut Vec<Expression>) -> Result {
+    match value {
+        // The conditions are expressions even without the `${{ }}` wrapper.
+        Value::String(text) if key == Some("if") => out.push(Expression::parse(text)?),
+        Value::String(text) => out.extend(embedded(text)?),
+        Value::Array(items) =>
+            items.iter().try_for_each(|item| collect_expressions(item, None, out))?,
+        Value::Object(fields) => fields
+            .iter()
+            .try_for_each(|(name, field)| collect_expressions(field, Some(name.as_str()), out))?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+/// Mermaid node identifiers cannot contain arbitrary characters, unlike the job identifiers.
+fn mermaid_id(id: &str) -> String {
+    id.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect()
+}
+
+/// Escape the text to be put in the quoted Mermaid label.
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKFLOW: &str = r#"
+name: Release
+on:
+  workflow_dispatch: {}
+jobs:
+  draft:
+    name: Draft release
+    runs-on:
+      - self-hosted
+      - Linux
+    outputs:
+      id: ${{ steps.draft.outputs.id }}
+      version: ${{ steps.draft.outputs.version }}
+    steps:
+      - id: draft
+        run: ./run release create-draft
+  package:
+    name: Package
+    needs:
+      - draft
+    if: github.ref == 'refs/heads/main'
+    runs-on:
+      - ${{ matrix.os }}
+    strategy:
+      matrix:
+        os:
+          - windows-latest
+          - macos-latest
+    steps:
+      - run: ./run ide upload --release-id ${{ needs.draft.outputs.id }}
+"#;
+
+    #[test]
+    fn rendering() -> Result {
+        let workflow = serde_yaml::from_str::<Workflow>(WORKFLOW)?;
+        let graph = JobGraph::new(&workflow)?;
+        assert_eq!(graph.edges, [Edge {
+            from:    "draft".into(),
+            to:      "package".into(),
+            outputs: vec!["id".into()],
+        }]);
+        let package = &graph.nodes[1];
+        assert_eq!(package.runs_on, ["${{ matrix.os }}"]);
+        assert_eq!(package.matrix, [r#"os: ["windows-latest","macos-latest"]"#]);
+        assert_eq!(package.condition.as_deref(), Some("github.ref == 'refs/heads/main'"));
+
+        let dot = graph.to_dot();
+        assert!(dot.contains(r#""draft" -> "package" [label="id"];"#));
+        assert!(dot.contains(r#"label="Draft release\nruns-on: self-hosted, Linux""#));
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.contains(r#"draft -->|"id"| package"#));
+        assert!(mermaid.contains("Package<br/>runs-on: ${{ matrix.os }}<br/>matrix os:"));
+        Ok(())
+    }
+
+    #[test]
+    fn output_references() -> Result {
+        let workflow = serde_yaml::from_str::<Workflow>(
+            r#"
+name: Outputs
+on:
+  workflow_dispatch: {}
+jobs:
+  build:
+    name: Build
+    runs-on:
+      - ubuntu-latest
+    outputs:
+      id: ${{ steps.build.outputs.id }}
+      id-long: ${{ steps.build.outputs.id-long }}
+    steps: []
+  build-docs:
+    name: Build docs
+    runs-on:
+      - ubuntu-latest
+    outputs:
+      id: ${{ steps.build.outputs.id }}
+    steps: []
+  publish:
+    name: Publish
+    needs:
+      - build
+      - build-docs
+    if: needs['build'].outputs['id'] != ''
+    runs-on:
+      - ubuntu-latest
+    steps:
+      - run: echo ${{ needs.build.outputs.id-long }}
+      - run: echo "Unlike needs.build-docs.outputs.id, this is not an expression."
+"#,
+        )?;
+        let graph = JobGraph::new(&workflow)?;
+        assert_eq!(graph.edges, [
+            Edge {
+                from:    "build".into(),
+                to:      "publish".into(),
+                outputs: vec!["id".into(), "id-long".into()],
+            },
+            Edge { from: "build-docs".into(), to: "publish".into(), outputs: vec![] },
+        ]);
+        Ok(())
+    }
+}
@@ -0,0 +1,822 @@
+//! Running workflow jobs locally, without pushing the workflow to GitHub.
+//!
+//! Only the `run` steps are executed. Steps using actions have no local equivalent and are
+//! skipped, unless they use one of the known composite actions, whose steps are run instead. In a
+//! similar manner, jobs calling reusable workflows are run only if the workflow is known. Of the
+//! matrix jobs, only the first combination is run. Everything that was skipped is listed in the
+//! [`Report`].
+
+use crate::prelude::*;
+
+use crate::actions::workflow::definition::action::Runs;
+use crate::actions::workflow::definition::expression::evaluation::substitute;
+use crate::actions::workflow::definition::expression::Context;
+use crate::actions::workflow::definition::expression::EvaluationContext;
+use crate::actions::workflow::definition::Action;
+use crate::actions::workflow::definition::Expression;
+use crate::actions::workflow::definition::Job;
+use crate::actions::workflow::definition::RunDefaults;
+use crate::actions::workflow::definition::Shell;
+use crate::actions::workflow::definition::Step;
+use crate::actions::workflow::definition::Workflow;
+use crate::program::command::spawn_log_processor;
+
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::BufReader;
+
+
+
+/// Get the job together with all the jobs it transitively needs, in an order they can be run.
+pub fn execution_order(workflow: &Workflow, job_id: &str) -> Result<Vec<String>> {
+    let mut order = Vec::new();
+    visit(workflow, job_id, &mut Vec::new(), &mut order)?;
+    Ok(order)
+}
+
+/// Get all the workflow's jobs, in an order they can be run.
+pub fn full_execution_order(workflow: &Workflow) -> Result<Vec<String>> {
+    let mut order = Vec::new();
+    for job_id in workflow.jobs.keys() {
+        visit(workflow, job_id, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Depth-first traversal of the `needs` graph, appending jobs to `done` in the post-order.
+fn visit(
+    workflow: &Workflow,
+    job_id: &str,
+    visiting: &mut Vec<String>,
+    done: &mut Vec<String>,
+) -> Result {
+    if done.iter().any(|done| done == job_id) {
+        return Ok(());
+    }
+    ensure!(
+        !visiting.iter().any(|visiting| visiting == job_id),
+        "Cycle in the job dependencies: {} -> {job_id}",
+        visiting.join(" -> ")
+    );
+    let job = workflow.jobs.get(job_id).with_context(|| {
+        format!(
+            "There is no job `{job_id}` in the workflow `{}`. Available jobs: {}",
+            workflow.name,
+            workflow.jobs.keys().join(", ")
+        )
+    })?;
+    visiting.push(job_id.into());
+    for needed in &job.needs {
+        visit(workflow, needed, visiting, done)?;
+    }
+    visiting.pop();
+    done.push(job_id.into());
+    Ok(())
+}
+
+/// Result of a job or a step, as exposed through `needs.<job>.result` or `steps.<id>.outcome`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+    Skipped,
+}
+
+impl Default for Outcome {
+    fn default() -> Self {
+        Outcome::Skipped
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StepReport {
+    pub job:     String,
+    pub step:    String,
+    pub outcome: Outcome,
+    /// Why the step was skipped, or other remarks on how it was run.
+    pub note:    Option<String>,
+}
+
+/// Summary of the local run.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub jobs:  Vec<(String, Outcome)>,
+    pub steps: Vec<StepReport>,
+}
+
+impl Report {
+    pub fn succeeded(&self) -> bool {
+        self.jobs.iter().all(|(_, outcome)| *outcome != Outcome::Failure)
+    }
+
+    fn step(&mut self, job: &str, step: &str, outcome: Outcome, note: Option<String>) {
+        if let Some(note) = &note {
+            info!("{job}: step `{step}` {outcome}: {note}");
+        }
+        self.steps.push(StepReport { job: job.into(), step: step.into(), outcome, note });
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Jobs:")?;
+        for (job, outcome) in &self.jobs {
+            writeln!(f, "  {outcome:<8} {job}")?;
+        }
+        let noted = self.steps.iter().filter(|step| step.note.is_some()).collect_vec();
+        if !noted.is_empty() {
+            writeln!(f, "Steps that were skipped or not run as on GitHub:")?;
+            for step in noted {
+                let note = step.note.as_deref().unwrap_or_default();
+                writeln!(f, "  {} / {}: {note}", step.job, step.step)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct JobResult {
+    outcome: Outcome,
+    outputs: Map<String, Value>,
+}
+
+impl JobResult {
+    fn to_json(&self) -> Value {
+        json!({ "result": self.outcome.to_string(), "outputs": self.outputs })
+    }
+}
+
+/// State of the job being run.
+#[derive(Clone, Debug)]
+struct JobState {
+    /// Name used to identify the job in the logs and the report.
+    label:    String,
+    context:  EvaluationContext,
+    /// Job's environment, including the variables set by the previous steps through `GITHUB_ENV`.
+    env:      BTreeMap<String, String>,
+    defaults: RunDefaults,
+}
+
+/// Runs the workflow jobs on the local machine.
+#[derive(Clone, Debug)]
+pub struct LocalRunner {
+    /// Repository root, used as the workspace.
+    pub repo_root: PathBuf,
+    /// Known reusable workflows, keyed by the `uses` value of the jobs calling them.
+    pub workflows: BTreeMap<String, Workflow>,
+    /// Known composite actions, keyed by the `uses` value of the steps using them.
+    pub actions:   BTreeMap<String, Action>,
+    /// Inputs of the run workflow, as if it was manually dispatched.
+    pub inputs:    BTreeMap<String, String>,
+    /// Values of the `secrets` context.
+    pub secrets:   BTreeMap<String, String>,
+    /// Do not run anything, just report what would be run.
+    pub dry_run:   bool,
+}
+
+impl LocalRunner {
+    pub fn new(repo_root: impl Into<PathBuf>) -> Self {
+        Self {
+            repo_root: repo_root.into(),
+            workflows: default(),
+            actions:   default(),
+            inputs:    default(),
+            secrets:   default(),
+            dry_run:   false,
+        }
+    }
+
+    /// Run the given job, after all the jobs it needs.
+    pub async fn run(&self, workflow: &Workflow, job_id: &str) -> Result<Report> {
+        let order = execution_order(workflow, job_id)?;
+        info!("Will run jobs: {}", order.join(", "));
+        let mut inputs = Map::new();
+        if let Some(dispatch) = &workflow.on.workflow_dispatch {
+            for (name, input) in &dispatch.inputs {
+                inputs.insert(name.clone(), input.default.clone().unwrap_or_default());
+            }
+        }
+        for (name, value) in &self.inputs {
+            inputs.insert(name.clone(), Value::String(value.clone()));
+        }
+        let mut report = Report::default();
+        self.run_jobs(workflow, order, inputs, "", &mut report).await?;
+        Ok(report)
+    }
+
+    fn base_context(&self, workflow: &Workflow, inputs: Map<String, Value>) -> EvaluationContext {
+        let runner_os = match TARGET_OS {
+            OS::Windows => "Windows",
+            OS::MacOS => "macOS",
+            _ => "Linux",
+        };
+        let mut context = EvaluationContext::default();
+        context.set(
+            Context::Github,
+            json!({
+                "event_name": "workflow_dispatch",
+                "workflow": workflow.name,
+                "workspace": self.repo_root,
+                "token": self.secrets.get("GITHUB_TOKEN"),
+                "ref": "",
+                "base_ref": "",
+                "head_ref": "",
+            }),
+        );
+        context.set(
+            Context::Runner,
+            json!({ "os": runner_os, "name": "Local", "temp": std::env::temp_dir() }),
+        );
+        context.set(Context::Secrets, json!(self.secrets));
+        context.set(Context::Inputs, inputs);
+        context
+    }
+
+    fn run_jobs<'a>(
+        &'a self,
+        workflow: &'a Workflow,
+        order: Vec<String>,
+        inputs: Map<String, Value>,
+        prefix: &'a str,
+        report: &'a mut Report,
+    ) -> BoxFuture<'a, Result<BTreeMap<String, JobResult>>> {
+        async move {
+            let mut results = BTreeMap::<String, JobResult>::new();
+            for job_id in order {
+                let job = &workflow.jobs[&job_id];
+                let mut context = self.base_context(workflow, inputs.clone());
+                let mut needs = Map::new();
+                for needed in &job.needs {
+                    let result = results.get(needed).cloned().unwrap_or_default();
+                    context.failed |= result.outcome != Outcome::Success;
+                    needs.insert(needed.clone(), result.to_json());
+                }
+                context.set(Context::Needs, needs);
+                let label = format!("{prefix}{job_id}");
+                let result = if should_run(job.r#if.as_ref(), &context)? {
+                    self.run_job(workflow, job, label.clone(), context, report).await?
+                } else {
+                    info!("Skipping job {label}, as its condition is not met.");
+                    JobResult::default()
+                };
+                report.jobs.push((label, result.outcome));
+                results.insert(job_id, result);
+            }
+            Ok(results)
+        }
+        .boxed()
+    }
+
+    async fn run_job(
+        &self,
+        workflow: &Workflow,
+        job: &Job,
+        label: String,
+        mut context: EvaluationContext,
+        report: &mut Report,
+    ) -> Result<JobResult> {
+        if let Some(uses) = &job.uses {
+            return self.call_workflow(uses, job, &label, &context, report).await;
+        }
+
+        info!("Running job {label}.");
+        if let Some(strategy) = &job.strategy {
+            // Only the first combination is run, as the matrix is typically used for the systems.
+            let combinations = strategy
+                .matrix
+                .combinations()
+                .with_context(|| format!("Failed to expand the matrix of job {label}."))?;
+            let matrix = combinations
+                .first()
+                .with_context(|| format!("The matrix of job {label} has no combinations."))?;
+            let note = format!(
+                "Only the first of {} matrix combinations is run: {}.",
+                combinations.len(),
+                json!(matrix)
+            );
+            report.step(&label, "strategy", Outcome::Success, Some(note));
+            context.set(Context::Matrix, json!(matrix));
+        }
+
+        let mut env = BTreeMap::new();
+        for (name, value) in workflow.env.iter().chain(&job.env) {
+            context.set(Context::Env, json!(env));
+            env.insert(name.clone(), substitute(value, &context)?);
+        }
+        context.set(Context::Env, json!(env));
+        let defaults = job
+            .defaults
+            .as_ref()
+            .or(workflow.defaults.as_ref())
+            .and_then(|defaults| defaults.run.clone())
+            .unwrap_or_default();
+        let mut state = JobState { label, context, env, defaults };
+
+        let steps = self.run_steps(&mut state, &job.steps, report);
+        let outcome = match job.timeout_minutes {
+            Some(minutes) => {
+                let result = tokio::time::timeout(minutes_duration(minutes), steps).await;
+                match result {
+                    Ok(outcome) => outcome?,
+                    Err(_) => {
+                        // Like on GitHub, the timed out job fails and its dependents are skipped.
+                        error!("Job {} timed out after {minutes} minutes.", state.label);
+                        let note = format!("Timed out after {minutes} minutes.");
+                        report.step(&state.label, "timeout", Outcome::Failure, Some(note));
+                        return Ok(JobResult { outcome: Outcome::Failure, outputs: default() });
+                    }
+                }
+            }
+            None => steps.await?,
+        };
+        let mut outputs = Map::new();
+        for (name, value) in &job.outputs {
+            outputs.insert(name.clone(), Value::String(substitute(value, &state.context)?));
+        }
+        Ok(JobResult { outcome, outputs })
+    }
+
+    /// Run the job calling a reusable workflow.
+    async fn call_workflow(
+        &self,
+        uses: &str,
+        job: &Job,
+        label: &str,
+        context: &EvaluationContext,
+        report: &mut Report,
+    ) -> Result<JobResult> {
+        let called = if let Some(called) = self.workflows.get(uses) {
+            called
+        } else {
+            let note = format!("Calls the reusable workflow `{uses}`, which is not known locally.");
+            report.step(label, uses, Outcome::Skipped, Some(note));
+            return Ok(JobResult::default());
+        };
+        let mut inputs = Map::new();
+        if let Some(call) = &called.on.workflow_call {
+            for (name, input) in &call.inputs {
+                inputs.insert(name.clone(), input.default.clone().unwrap_or_default());
+            }
+        }
+        for (name, value) in &job.with {
+            inputs.insert(name.clone(), substitute_value(value, context)?);
+        }
+        info!("Job {label} calls the workflow {uses}.");
+        let prefix = format!("{label}/");
+        let order = full_execution_order(called)?;
+        let results = self.run_jobs(called, order, inputs, &prefix, report).await?;
+
+        let failed = results.values().any(|result| result.outcome == Outcome::Failure);
+        let mut context = self.base_context(called, default());
+        let jobs: Map<_, _> =
+            results.iter().map(|(id, result)| (id.clone(), result.to_json())).collect();
+        context.set(Context::Jobs, jobs);
+        let mut outputs = Map::new();
+        if let Some(call) = &called.on.workflow_call {
+            for (name, output) in &call.outputs {
+                outputs.insert(name.clone(), Value::String(substitute(&output.value, &context)?));
+            }
+        }
+        let outcome = if failed { Outcome::Failure } else { Outcome::Success };
+        Ok(JobResult { outcome, outputs })
+    }
+
+    /// Run the steps and return the overall outcome.
+    fn run_steps<'a>(
+        &'a self,
+        state: &'a mut JobState,
+        steps: &'a [Step],
+        report: &'a mut Report,
+    ) -> BoxFuture<'a, Result<Outcome>> {
+        async move {
+            for (index, step) in steps.iter().enumerate() {
+                let name = step_name(step, index);
+                let (outcome, outputs) = if should_run(step.r#if.as_ref(), &state.context)? {
+                    self.run_step(state, step, &name, report).await?
+                } else {
+                    // Without an explicit condition, the step is run only if nothing failed.
+                    let condition = match &step.r#if {
+                        Some(condition) => condition.to_string(),
+                        None => "success()".into(),
+                    };
+                    let note = format!("Its condition `{condition}` is not met.");
+                    report.step(&state.label, &name, Outcome::Skipped, Some(note));
+                    (Outcome::Skipped, default())
+                };
+                let conclusion =
+                    if step.continue_on_error == Some(true) { Outcome::Success } else { outcome };
+                state.context.failed |= conclusion == Outcome::Failure;
+                if let Some(id) = &step.id {
+                    state.context.object_mut(Context::Steps).insert(
+                        id.clone(),
+                        json!({
+                            "outputs": outputs,
+                            "outcome": outcome.to_string(),
+                            "conclusion": conclusion.to_string(),
+                        }),
+                    );
+                }
+            }
+            Ok(if state.context.failed { Outcome::Failure } else { Outcome::Success })
+        }
+        .boxed()
+    }
+
+    async fn run_step(
+        &self,
+        state: &mut JobState,
+        step: &Step,
+        name: &str,
+        report: &mut Report,
+    ) -> Result<(Outcome, BTreeMap<String, String>)> {
+        if let Some(uses) = &step.uses {
+            return if let Some(action) = self.actions.get(uses) {
+                self.run_composite(state, step, action, report).await
+            } else {
+                let note = format!("Uses `{uses}`, which has no local equivalent.");
+                report.step(&state.label, name, Outcome::Skipped, Some(note));
+                Ok((Outcome::Skipped, default()))
+            };
+        }
+        let run = if let Some(run) = &step.run {
+            run
+        } else {
+            report.step(&state.label, name, Outcome::Skipped, Some("Nothing to run.".into()));
+            return Ok((Outcome::Skipped, default()));
+        };
+
+        let mut env = state.env.clone();
+        for (variable, value) in &step.env {
+            state.context.set(Context::Env, json!(env));
+            env.insert(variable.clone(), substitute(value, &state.context)?);
+        }
+        state.context.set(Context::Env, json!(env));
+        let script = substitute(run, &state.context)?;
+        state.context.set(Context::Env, json!(state.env));
+        if self.dry_run {
+            let note = format!("Dry run, would execute:\n{script}");
+            report.step(&state.label, name, Outcome::Success, Some(note));
+            return Ok((Outcome::Success, default()));
+        }
+
+        info!("{}: running step `{name}`.", state.label);
+        let execution = self.execute(state, step, &script, env);
+        let result = match step.timeout_minutes {
+            Some(minutes) => tokio::time::timeout(minutes_duration(minutes), execution)
+                .await
+                .unwrap_or_else(|_| Err(anyhow!("Timed out after {minutes} minutes."))),
+            None => execution.await,
+        };
+        match result {
+            Ok((env_updates, outputs)) => {
+                state.env.extend(env_updates);
+                state.context.set(Context::Env, json!(state.env));
+                report.step(&state.label, name, Outcome::Success, None);
+                Ok((Outcome::Success, outputs))
+            }
+            Err(error) => {
+                error!("{}: step `{name}` failed: {error:?}", state.label);
+                report.step(&state.label, name, Outcome::Failure, None);
+                Ok((Outcome::Failure, default()))
+            }
+        }
+    }
+
+    /// Run the steps of the composite action, in place of the step using it.
+    async fn run_composite(
+        &self,
+        state: &mut JobState,
+        step: &Step,
+        action: &Action,
+        report: &mut Report,
+    ) -> Result<(Outcome, BTreeMap<String, String>)> {
+        let mut inputs = Map::new();
+        for (name, input) in &action.inputs {
+            inputs.insert(name.clone(), json!(input.default));
+        }
+        if let Some(with) = &step.with {
+            if let Value::Object(with) = serde_json::to_value(with)? {
+                for (name, value) in with {
+                    inputs.insert(name, substitute_value(&value, &state.context)?);
+                }
+            }
+        }
+
+        // The action's steps have their own `inputs` and `steps` contexts.
+        let outer_inputs = state.context.contexts.insert(Context::Inputs, Value::Object(inputs));
+        let outer_steps = state.context.contexts.remove(&Context::Steps);
+        let failed_before = state.context.failed;
+        let Runs::Composite { steps } = &action.runs;
+        let outcome = self.run_steps(state, steps, report).await?;
+        let mut outputs = BTreeMap::new();
+        for (name, output) in &action.outputs {
+            outputs.insert(name.clone(), substitute(&output.value, &state.context)?);
+        }
+
+        for (context, outer) in [(Context::Inputs, outer_inputs), (Context::Steps, outer_steps)] {
+            match outer {
+                Some(value) => state.context.contexts.insert(context, value),
+                None => state.context.contexts.remove(&context),
+            };
+        }
+        // The failure is propagated by the caller, based on the returned outcome.
+        state.context.failed = failed_before;
+        Ok((outcome, outputs))
+    }
+
+    /// Execute the step's script. Returns the environment and outputs that it set.
+    async fn execute(
+        &self,
+        state: &JobState,
+        step: &Step,
+        script: &str,
+        env: BTreeMap<String, String>,
+    ) -> Result<(Vec<(String, String)>, BTreeMap<String, String>)> {
+        let temp = tempfile::tempdir()?;
+        let env_file = temp.path().join("env");
+        let output_file = temp.path().join("output");
+        crate::fs::write(&env_file, "")?;
+        crate::fs::write(&output_file, "")?;
+
+        let shell = step
+            .shell
+            .as_ref()
+            .or(state.defaults.shell.as_ref())
+            .unwrap_or(if TARGET_OS == OS::Windows { &Shell::Pwsh } else { &Shell::Bash });
+        let script_path = temp.path().join("script").with_extension(script_extension(shell));
+        crate::fs::write(&script_path, script)?;
+        let working_directory =
+            step.working_directory.as_ref().or(state.defaults.working_directory.as_ref());
+
+        let mut command = shell_command(shell, &script_path);
+        command
+            .current_dir(self.repo_root.join(working_directory.map_or(Path::new(""), |p| p)))
+            .envs(env)
+            .env("CI", "true")
+            .env("GITHUB_ACTIONS", "true")
+            .env("GITHUB_WORKSPACE", &self.repo_root)
+            .env("GITHUB_ENV", &env_file)
+            .env("GITHUB_OUTPUT", &output_file)
+            .env("RUNNER_TEMP", temp.path())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let mut child = command.spawn()?;
+        let stderr = child.stderr.take().context("Failed to capture standard error.")?;
+        spawn_log_processor(format!("{}⚠️", state.label), stderr);
+
+        // Standard output is processed here, as it may contain the legacy `set-output` commands.
+        let mut outputs = BTreeMap::new();
+        let stdout = child.stdout.take().context("Failed to capture standard output.")?;
+        let mut lines = BufReader::new(stdout).lines();
+        while let Some(line) = lines.next_line().await? {
+            info!("{}ℹ️ {line}", state.label);
+            if let Some((name, value)) = parse_set_output(&line) {
+                outputs.insert(name.to_string(), value.to_string());
+            }
+        }
+        let status = child.wait().await?;
+        ensure!(status.success(), "Script failed with {status}.");
+
+        outputs.extend(parse_file_commands(&crate::fs::read_to_string(&output_file)?)?);
+        let env_updates = parse_file_commands(&crate::fs::read_to_string(&env_file)?)?;
+        Ok((env_updates, outputs))
+    }
+}
+
+/// Check the `if` condition, remembering that without a status function it implies `success()`.
+fn should_run(condition: Option<&Expression>, context: &EvaluationContext) -> Result<bool> {
+    match condition {
+        Some(condition) if condition.uses_status_function() =>
+            condition.evaluate_condition(context),
+        Some(condition) => Ok(!context.failed && condition.evaluate_condition(context)?),
+        None => Ok(!context.failed),
+    }
+}
+
+/// Substitute the expressions in a value, if it is a string.
+fn substitute_value(value: &Value, context: &EvaluationContext) -> Result<Value> {
+    Ok(match value {
+        Value::String(text) => Value::String(substitute(text, context)?),
+        other => other.clone(),
+    })
+}
+
+fn step_name(step: &Step, index: usize) -> String {
+    step.name
+        .clone()
+        .or_else(|| step.id.clone())
+        .or_else(|| step.uses.clone())
+        .or_else(|| step.run.as_ref().and_then(|run| run.lines().next()).map(String::from))
+        .unwrap_or_else(|| format!("#{index}"))
+}
+
+fn minutes_duration(minutes: u32) -> Duration {
+    Duration::from_secs(u64::from(minutes) * 60)
+}
+
+fn script_extension(shell: &Shell) -> &'static str {
+    match shell {
+        Shell::Bash | Shell::Sh => "sh",
+        Shell::Pwsh | Shell::Powershell => "ps1",
+        Shell::Cmd => "cmd",
+        Shell::Python => "py",
+    }
+}
+
+/// Command running the script file, the same way as GitHub does for the given shell.
+fn shell_command(shell: &Shell, script: &Path) -> Command {
+    let script = script.display().to_string();
+    let (program, args) = match shell {
+        Shell::Bash => ("bash", vec![
+            "--noprofile".into(),
+            "--norc".into(),
+            "-eo".into(),
+            "pipefail".into(),
+            script,
+        ]),
+        Shell::Sh => ("sh", vec!["-e".into(), script]),
+        Shell::Pwsh => ("pwsh", vec!["-command".into(), format!(". '{script}'")]),
+        Shell::Powershell => ("powershell", vec!["-command".into(), format!(". '{script}'")]),
+        Shell::Cmd => ("cmd", vec![
+            "/D".into(),
+            "/E:ON".into(),
+            "/V:OFF".into(),
+            "/S".into(),
+            "/C".into(),
+            format!("CALL \"{script}\""),
+        ]),
+        Shell::Python => ("python", vec![script]),
+    };
+    let mut command = Command::new(program);
+    command.args(args);
+    command
+}
+
+/// Parse the legacy `::set-output name=<name>::<value>` workflow command.
+fn parse_set_output(line: &str) -> Option<(&str, &str)> {
+    line.trim().strip_prefix("::set-output name=")?.split_once("::")
+}
+
+/// Parse the contents of the files like `GITHUB_ENV` or `GITHUB_OUTPUT`.
+///
+/// Each entry is either a `NAME=value` line or a multiline `NAME<<DELIMITER` block, ended by the
+/// line with the delimiter.
+fn parse_file_commands(text: &str) -> Result<Vec<(String, String)>> {
+    let mut ret = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let equals = line.find('=');
+        let heredoc = line.find("<<");
+        match (equals, heredoc) {
+            (Some(equals), heredoc) if heredoc.map_or(true, |heredoc| equals < heredoc) =>
+                ret.push((line[..equals].into(), line[equals + 1..].into())),
+            (_, Some(heredoc)) => {
+                let delimiter = &line[heredoc + 2..];
+                let mut value = Vec::new();
+                loop {
+                    let line = lines.next().with_context(|| {
+                        format!("Missing delimiter `{delimiter}` for `{}`.", &line[..heredoc])
+                    })?;
+                    if line == delimiter {
+                        break;
+                    }
+                    value.push(line);
+                }
+                ret.push((line[..heredoc].into(), value.join("\n")));
+            }
+            _ => bail!("Invalid line in the file command: {line}"),
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKFLOW: &str = r#"
+name: Test
+on:
+  workflow_dispatch:
+    inputs:
+      greeting:
+        default: Hello
+env:
+  TARGET: World
+jobs:
+  produce:
+    name: Produce
+    runs-on:
+      - ubuntu-latest
+    outputs:
+      message: ${{ steps.produce.outputs.message }}
+    strategy:
+      matrix:
+        os:
+          - windows
+          - linux
+        exclude:
+          - os: windows
+        include:
+          - os: linux
+            extra: set by step
+    steps:
+      - uses: actions/checkout@v3
+      - id: produce
+        run: |
+          echo "message=${{ inputs.greeting }}, $TARGET" >> "$GITHUB_OUTPUT"
+          echo "EXTRA=set by step" >> "$GITHUB_ENV"
+        shell: bash
+      - run: test "$EXTRA" = "${{ matrix.extra }}"
+        shell: bash
+  consume:
+    name: Consume
+    needs:
+      - produce
+    runs-on:
+      - ubuntu-latest
+    steps:
+      - run: test "${{ needs.produce.outputs.message }}" = "Hello, World"
+        shell: bash
+      - run: exit 1
+        shell: bash
+      - name: Not run after failure
+        run: exit 1
+        shell: bash
+      - name: Run after failure
+        if: failure()
+        run: echo "Cleaning up."
+        shell: bash
+      - name: Run on success
+        if: success() && inputs.greeting
+        run: exit 1
+        shell: bash
+  unrelated:
+    name: Unrelated
+    runs-on:
+      - ubuntu-latest
+    steps:
+      - run: exit 1
+"#;
+
+    #[test]
+    fn ordering() -> Result {
+        let workflow = serde_yaml::from_str::<Workflow>(WORKFLOW)?;
+        assert_eq!(execution_order(&workflow, "consume")?, ["produce", "consume"]);
+        assert!(execution_order(&workflow, "missing").is_err());
+        let mut cyclic = workflow.clone();
+        cyclic.jobs.get_mut("produce").unwrap().needs("consume");
+        assert!(execution_order(&cyclic, "consume").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn file_commands() -> Result {
+        let text = "A=1\nB<<EOF\nfirst\nsecond\nEOF\nC=x<<y\n";
+        let expected = [("A", "1"), ("B", "first\nsecond"), ("C", "x<<y")];
+        let expected = expected.map(|(name, value)| (name.to_string(), value.to_string()));
+        assert_eq!(parse_file_commands(text)?, expected);
+        assert!(parse_file_commands("A<<EOF\nno end").is_err());
+        assert_eq!(parse_set_output("::set-output name=a::b::c"), Some(("a", "b::c")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn running() -> Result {
+        let workflow = serde_yaml::from_str::<Workflow>(WORKFLOW)?;
+        let temp = tempfile::tempdir()?;
+        let report = LocalRunner::new(temp.path()).run(&workflow, "consume").await?;
+        assert_eq!(report.jobs, [
+            ("produce".to_string(), Outcome::Success),
+            ("consume".to_string(), Outcome::Failure)
+        ]);
+        let outcomes = report.steps.iter().map(|step| step.outcome).collect_vec();
+        assert_eq!(outcomes, [
+            Outcome::Success,
+            Outcome::Skipped,
+            Outcome::Success,
+            Outcome::Success,
+            Outcome::Success,
+            Outcome::Failure,
+            Outcome::Skipped,
+            Outcome::Success,
+            Outcome::Skipped,
+        ]);
+        let report = report.to_string();
+        assert!(report.contains("actions/checkout@v3"));
+        assert!(report.contains(r#"Only the first of 1 matrix combinations is run: {"extra":"#));
+        assert!(report.contains("Not run after failure: Its condition `success()` is not met."));
+        assert!(report.contains("Its condition `success() && inputs.greeting` is not met."));
+        Ok(())
+    }
+}
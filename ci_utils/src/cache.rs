@@ -85,8 +85,8 @@ impl Cache {
     pub fn get<S>(&self, storable: S) -> BoxFuture<'static, Result<S::Output>>
     where S: Storable {
         let this = self.clone();
+        let span = info_span!("Looking up the cache.", category = "cache", key = ?storable.key());
         async move {
-            // FIXME trace
             let code = digest(&storable)?;
             let entry_dir = this.root.join(&code);
             let entry_meta = entry_dir.with_appended_extension("json");
@@ -118,6 +118,7 @@ impl Cache {
                 }
             }
         }
+        .instrument(span)
         .boxed()
     }
 }
@@ -3,9 +3,15 @@ use std::collections::BTreeSet;
 
 use crate::models::config::Runner;
 use crate::models::config::RunnerLocation;
+use crate::programs::docker::BuildOptions;
+use crate::programs::docker::ImageId;
+use crate::programs::docker::RestartPolicy;
+use crate::programs::docker::RunOptions;
 
 use platforms::target::OS;
 
+pub mod reconcile;
+
 /// Name of the directory with a runner that is placed in runner's container image build context.
 ///
 /// Must be in sync with relevant entries in `Dockerfile`s (the `ADD` commands).
@@ -13,6 +19,27 @@ pub const DIRECTORY_WITH_RUNNER_PACKAGE: &str = "runner";
 
 pub const DIRECTORY_WITH_CI_CRATE: &str = "ci";
 
+/// Environment variable passing the registration token to the runner's container.
+///
+/// This keeps the token out of the container's command line.
+pub const REGISTRATION_TOKEN_VARIABLE: &str = "RUNNER_REGISTRATION_TOKEN";
+
+/// Label of the runner containers, with the runner's qualified name as the value.
+pub const CONTAINER_LABEL: &str = "org.enso.runner";
+
+/// Prefix of the names of the runners' containers and units at the given location.
+pub fn location_prefix(location: &RunnerLocation) -> String {
+    match location {
+        RunnerLocation::Organization(org) => iformat!("{org.name}"),
+        RunnerLocation::Repository(repo) => iformat!("{repo.owner}-{repo.name}"),
+    }
+}
+
+/// Name of the systemd unit running the runner with the given qualified name.
+pub fn unit_name(qualified_name: &str) -> String {
+    format!("{qualified_name}.service")
+}
+
 /// Full runner configuration.
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -30,11 +57,7 @@ pub struct Config {
 impl Config {
     /// Pretty printed triple with repository owner, repository name and runner name.
     pub fn qualified_name(&self) -> String {
-        let location_prefix = match &self.location {
-            RunnerLocation::Organization(org) => iformat!("{org.name}"),
-            RunnerLocation::Repository(repo) => iformat!("{repo.owner}-{repo.name}"),
-        };
-        iformat!("{location_prefix}-{self.runner.name}-{self.server_name}-{self.index}")
+        format!("{}-{}", location_prefix(&self.location), self.registered_name())
     }
 
     /// The custom labels that the runner will be registered with.
@@ -106,6 +129,96 @@ impl Config {
         ret.set_extension(script_extension(self.os));
         ret
     }
+
+    pub fn run_script_filename(&self) -> PathBuf {
+        let mut ret = PathBuf::from("run");
+        ret.set_extension(script_extension(self.os));
+        ret
+    }
+
+    /// Tag of the runner's image. Docker requires the tags to be lowercase.
+    pub fn image_tag(&self) -> String {
+        self.qualified_name().to_lowercase()
+    }
+
+    /// Build of the runner's image. The context must contain the runner package in
+    /// [`DIRECTORY_WITH_RUNNER_PACKAGE`].
+    pub fn build_options(&self, context: impl Into<PathBuf>) -> BuildOptions {
+        let mut options = BuildOptions::new(context);
+        options.file = Some(options.context.join(&self.runner.dockerfile));
+        options.target = Some(self.runner.target.clone().into());
+        options.tags = vec![self.image_tag()];
+        options.build_args = self
+            .runner
+            .args
+            .iter()
+            .map(|arg| match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            })
+            .collect();
+        options
+    }
+
+    /// The command of the runner's container: it registers the runner and then runs it.
+    pub fn container_command(&self) -> Result<Vec<String>> {
+        let token = match self.os {
+            OS::Windows => format!("%{REGISTRATION_TOKEN_VARIABLE}%"),
+            _ => format!("${REGISTRATION_TOKEN_VARIABLE}"),
+        };
+        let configure = once(self.guest_config_script_path().display().to_string())
+            .chain(self.register_script_call_args(token)?)
+            .join(" ");
+        let script = format!("{configure} && {}", self.guest_run_script_path().display());
+        let shell: [&str; 2] = match self.os {
+            OS::Windows => ["cmd", "/c"],
+            _ => ["sh", "-c"],
+        };
+        Ok(shell.into_iter().map(into).chain(once(script)).collect())
+    }
+
+    /// The runner's container, with the registration token to use.
+    pub fn run_options(&self, image: ImageId, token: impl AsRef<str>) -> Result<RunOptions> {
+        let mut options = RunOptions::new(image);
+        options.name = Some(self.qualified_name());
+        options.restart = Some(RestartPolicy::UnlessStopped);
+        options.command = self.container_command()?.into_iter().map(into).collect();
+        options.env_raw(REGISTRATION_TOKEN_VARIABLE, token.as_ref());
+        options.label(CONTAINER_LABEL, self.qualified_name());
+        for (host_port, container_port) in &self.runner.ports {
+            options.publish_port(*host_port, *container_port);
+        }
+        if self.runner.docker_access {
+            options.bind_docker_daemon();
+        }
+        Ok(options)
+    }
+
+    /// The user-level systemd unit running the runner installed in the given directory.
+    ///
+    /// The runner must be already registered, as the registration token is short-lived.
+    pub fn systemd_unit(&self, runner_dir: &Path) -> String {
+        let run_script = runner_dir.join(self.run_script_filename());
+        format!(
+            r#"[Unit]
+Description=GitHub Actions runner {name} for the {location}
+
+[Service]
+WorkingDirectory={runner_dir}
+ExecStart={run_script}
+Restart=always
+KillSignal=SIGTERM
+TimeoutStopSec=5min
+
+[Install]
+WantedBy=default.target
+"#,
+            name = self.registered_name(),
+            location = self.location,
+            runner_dir = runner_dir.display(),
+            run_script = run_script.display(),
+        )
+    }
 }
 
 /// The extension used by the scripts that are part of GitHub Actions Runner distribution.
@@ -0,0 +1,463 @@
+//! Bringing the self-hosted runners registered in GitHub in line with the configuration.
+//!
+//! Each machine reconciles only its own runners, i.e. the ones configured for its server name:
+//! the missing ones are registered and the offline ones are registered anew. Registered runners
+//! that are not configured for any server are removed, if they were registered by this machine,
+//! i.e. carry its server name as a label. Other runners, like the ones registered by other tooling
+//! or matching the `external` patterns, are never touched.
+
+use crate::prelude::*;
+
+use crate::deploy::runner;
+use crate::deploy::runner::location_prefix;
+use crate::deploy::runner::unit_name;
+use crate::deploy::runner::DIRECTORY_WITH_RUNNER_PACKAGE;
+use crate::github::model;
+use crate::models::config::Config;
+use crate::models::config::RunnerLocation;
+use crate::programs::docker::ContainerId;
+use crate::programs::systemctl::Manager;
+use crate::programs::Docker;
+use crate::programs::Systemctl;
+use std::collections::BTreeSet;
+
+
+
+/// How the runners are run on this machine.
+#[derive(Clone, Debug)]
+pub enum Deployment {
+    /// Each runner runs in its own container. Its image is built from the runner's Dockerfile in
+    /// the given context directory.
+    Container { context: PathBuf },
+    /// Each runner is installed into its own subdirectory of `runners_dir` and run by a systemd
+    /// unit of the current user, written to `unit_dir`.
+    ///
+    /// Note that the user's units are stopped when the user logs out, unless lingering is enabled
+    /// with `loginctl enable-linger`.
+    Systemd { runners_dir: PathBuf, unit_dir: PathBuf },
+}
+
+/// Default directory of the runners deployed as systemd units.
+pub fn default_runners_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join("actions-runners"))
+}
+
+/// Directory of the current user's systemd units.
+pub fn default_unit_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|config| config.join_iter(["systemd", "user"]))
+}
+
+/// Why a registered runner is removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// The runner is not configured for any server.
+    Stale,
+    /// The runner is configured for this server, but it is offline. It is registered anew.
+    Offline,
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reason::Stale => write!(f, "not configured"),
+            Reason::Offline => write!(f, "offline"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Action {
+    /// Remove the runner's registration, along with its container or unit on this machine.
+    Remove { location: RunnerLocation, runner: model::Runner, reason: Reason },
+    /// Register the runner and start it on this machine.
+    Register(runner::Config),
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Remove { location, runner, reason } =>
+                write!(f, "Remove {} ({reason}) from the {location}.", runner.name),
+            Action::Register(config) => write!(
+                f,
+                "Register {} at the {} with labels: {}.",
+                config.registered_name(),
+                config.location,
+                config.custom_labels().iter().join(", ")
+            ),
+        }
+    }
+}
+
+/// The actions that reconcile the runners. The removals come first.
+#[derive(Clone, Debug, Default)]
+pub struct Plan {
+    pub actions: Vec<Action>,
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.actions.is_empty() {
+            return writeln!(f, "The runners are up to date.");
+        }
+        for action in &self.actions {
+            writeln!(f, "{action}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Expand the configuration of the given server into the runners it should run.
+pub fn configured_runners(config: &Config, server_name: &str, os: OS) -> Vec<runner::Config> {
+    let machine = config.get(server_name).into_iter().flatten();
+    machine
+        .flat_map(move |repo| {
+            repo.runners.iter().flat_map(move |runner| {
+                let runner = runner.resolve();
+                (0..runner.count).map(move |index| runner::Config {
+                    location: repo.location.clone(),
+                    runner: runner.clone(),
+                    os,
+                    server_name: server_name.into(),
+                    index,
+                })
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct Reconciler {
+    /// Configuration of all the servers.
+    pub config:      Config,
+    /// The server this machine is, i.e. the key of its runners in the configuration.
+    pub server_name: String,
+    /// Operating system of the runners run by this machine.
+    pub os:          OS,
+    pub deployment:  Deployment,
+}
+
+impl Reconciler {
+    /// The runners this machine should run.
+    pub fn desired(&self) -> Vec<runner::Config> {
+        configured_runners(&self.config, &self.server_name, self.os)
+    }
+
+    /// The locations where this machine's runners are registered.
+    pub fn locations(&self) -> Vec<RunnerLocation> {
+        let mut locations = Vec::<RunnerLocation>::new();
+        for config in self.desired() {
+            if !locations.contains(&config.location) {
+                locations.push(config.location);
+            }
+        }
+        locations
+    }
+
+    /// Registered names of the runners configured at the location, for all servers.
+    pub fn configured_names(&self, location: &RunnerLocation) -> BTreeSet<String> {
+        self.config
+            .keys()
+            .flat_map(|server_name| configured_runners(&self.config, server_name, self.os))
+            .filter(|config| &config.location == location)
+            .map(|config| config.registered_name())
+            .collect()
+    }
+
+    /// Whether the runner at the location is managed by something else than us.
+    pub fn is_external(&self, location: &RunnerLocation, name: &str) -> bool {
+        self.config
+            .values()
+            .flatten()
+            .any(|repo| &repo.location == location && repo.is_external(name))
+    }
+
+    /// Plan the reconciliation of the location, given the runners registered there.
+    pub fn plan_location(&self, location: &RunnerLocation, registered: &[model::Runner]) -> Plan {
+        let mut removals = Vec::new();
+        let mut registrations = Vec::new();
+        let desired = self.desired().into_iter().filter(|config| &config.location == location);
+        for config in desired {
+            let name = config.registered_name();
+            match registered.iter().find(|runner| runner.name == name) {
+                Some(runner) if runner.is_online() => {}
+                Some(runner) => {
+                    let reason = Reason::Offline;
+                    removals.push(Action::Remove {
+                        location: location.clone(),
+                        runner: runner.clone(),
+                        reason,
+                    });
+                    registrations.push(Action::Register(config));
+                }
+                None => registrations.push(Action::Register(config)),
+            }
+        }
+
+        let configured = self.configured_names(location);
+        for runner in registered {
+            let is_stale = !configured.contains(&runner.name)
+                && !self.is_external(location, &runner.name)
+                && runner.has_label(&self.server_name);
+            if is_stale {
+                if runner.busy {
+                    warn!("Not removing the runner {}, as it is running a job.", runner.name);
+                } else {
+                    let reason = Reason::Stale;
+                    removals.push(Action::Remove {
+                        location: location.clone(),
+                        runner: runner.clone(),
+                        reason,
+                    });
+                }
+            }
+        }
+        Plan { actions: removals.into_iter().chain(registrations).collect() }
+    }
+
+    /// Compare the configuration with the runners registered in GitHub.
+    pub async fn plan(&self, octocrab: &Octocrab) -> Result<Plan> {
+        let mut plan = Plan::default();
+        for location in self.locations() {
+            let registered = location.list_runners(octocrab).await?;
+            plan.actions.extend(self.plan_location(&location, &registered).actions);
+        }
+        Ok(plan)
+    }
+
+    /// Perform the planned actions.
+    pub async fn apply(&self, octocrab: &Octocrab, plan: &Plan) -> Result {
+        for action in &plan.actions {
+            info!("{action}");
+            match action {
+                Action::Remove { location, runner, .. } => {
+                    self.teardown(location, &runner.name).await;
+                    location.remove_runner(octocrab, runner.id).await?;
+                }
+                Action::Register(config) => {
+                    let token =
+                        config.location.generate_runner_registration_token(octocrab).await?;
+                    self.deploy(octocrab, config, token.as_ref()).await.with_context(|| {
+                        format!("Failed to deploy {}.", config.qualified_name())
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Register the runner and start it.
+    pub async fn deploy(
+        &self,
+        octocrab: &Octocrab,
+        config: &runner::Config,
+        token: &str,
+    ) -> Result {
+        let qualified_name = config.qualified_name();
+        match &self.deployment {
+            Deployment::Container { context } => {
+                let package = context.join(DIRECTORY_WITH_RUNNER_PACKAGE);
+                if !package.exists() {
+                    crate::github::fetch_runner(octocrab, config.os, &package).await?;
+                }
+                let image = Docker.build(config.build_options(context)).await?;
+                // The previous container of an offline runner.
+                let _ = Docker.remove_container(&ContainerId(qualified_name), true).await;
+                Docker.run_detached(&config.run_options(image, token)?).await?;
+            }
+            Deployment::Systemd { runners_dir, unit_dir } => {
+                let runner_dir = runners_dir.join(&qualified_name);
+                let config_script = runner_dir.join(config.config_script_filename());
+                if !config_script.exists() {
+                    crate::github::fetch_runner(octocrab, config.os, &runner_dir).await?;
+                }
+                Command::new(&config_script)
+                    .args(config.register_script_call_args(token)?)
+                    .current_dir(&runner_dir)
+                    .run_ok()
+                    .await?;
+                let unit = unit_name(&qualified_name);
+                crate::fs::create_dir_if_missing(unit_dir)?;
+                crate::fs::write(unit_dir.join(&unit), config.systemd_unit(&runner_dir))?;
+                Systemctl.daemon_reload(Manager::User).await?;
+                Systemctl.enable_now(Manager::User, &unit).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the runner's container or unit from this machine, if there is one.
+    ///
+    /// Failures are only logged, as the runner may as well have been run by another machine.
+    pub async fn teardown(&self, location: &RunnerLocation, registered_name: &str) {
+        let qualified_name = format!("{}-{registered_name}", location_prefix(location));
+        let result = match &self.deployment {
+            Deployment::Container { .. } =>
+                Docker.remove_container(&ContainerId(qualified_name.clone()), true).await,
+            Deployment::Systemd { runners_dir, unit_dir } => {
+                let unit = unit_name(&qualified_name);
+                let unit_path = unit_dir.join(&unit);
+                if unit_path.exists() {
+                    async {
+                        Systemctl.disable_now(Manager::User, &unit).await?;
+                        crate::fs::remove_file_if_exists(&unit_path)?;
+                        crate::fs::remove_dir_if_exists(runners_dir.join(&qualified_name))?;
+                        Systemctl.daemon_reload(Manager::User).await
+                    }
+                    .await
+                } else {
+                    Ok(())
+                }
+            }
+        };
+        if let Err(e) = result {
+            debug!("Did not remove the local runner {qualified_name}: {e:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::matchers::path;
+    use wiremock::Mock;
+    use wiremock::MockServer;
+    use wiremock::ResponseTemplate;
+
+    const CONFIG: &str = r"
+builder:
+- repository:
+    owner: enso-org
+    name: enso
+  runners:
+  - name: engine
+    count: 2
+  external: [^github-hosted-]
+other:
+- repository:
+    owner: enso-org
+    name: enso
+  runners: engine
+";
+
+    fn runner(id: i32, name: &str, status: &str, labels: &[&str]) -> serde_json::Value {
+        let labels = labels
+            .iter()
+            .enumerate()
+            .map(|(id, name)| serde_json::json!({"id": id, "name": name, "type": "custom"}))
+            .collect_vec();
+        serde_json::json!({
+            "id": id,
+            "name": name,
+            "os": "Linux",
+            "status": status,
+            "busy": false,
+            "labels": labels,
+        })
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn reconcile_against_github() -> Result {
+        use std::os::unix::fs::PermissionsExt;
+
+        let server = MockServer::start().await;
+        let runners_path = "/repos/enso-org/enso/actions/runners";
+        let registered = [
+            // Up to date.
+            runner(1, "engine-builder-0", "online", &["engine", "builder"]),
+            // Configured for this machine, but offline.
+            runner(2, "engine-builder-1", "offline", &["engine", "builder"]),
+            // No longer configured.
+            runner(3, "old-builder-0", "online", &["old", "builder"]),
+            // Run by another machine.
+            runner(4, "engine-other-0", "offline", &["engine", "other"]),
+            runner(5, "unknown", "online", &["unknown"]),
+            runner(6, "github-hosted-1", "offline", &[]),
+            // Registered by other tooling.
+            runner(7, "registered-elsewhere", "offline", &["self-hosted"]),
+        ];
+        let body = serde_json::json!({"total_count": registered.len(), "runners": registered});
+        Mock::given(method("GET"))
+            .and(path(runners_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // The runner package is already there, with a configuration script that records its
+        // arguments and fails. This way the registration is checked without starting the unit.
+        let temp = tempfile::tempdir()?;
+        let runners_dir = temp.path().join("runners");
+        let runner_dir = runners_dir.join("enso-org-enso-engine-builder-1");
+        let config_script = runner_dir.join("config.sh");
+        let args_file = temp.path().join("args");
+        crate::fs::write(
+            &config_script,
+            format!("#!/bin/sh\necho \"$@\" > {}\nexit 1\n", args_file.display()),
+        )?;
+        std::fs::set_permissions(&config_script, std::fs::Permissions::from_mode(0o755))?;
+        let reconciler = Reconciler {
+            config:      serde_yaml::from_str(CONFIG)?,
+            server_name: "builder".into(),
+            os:          OS::Linux,
+            deployment:  Deployment::Systemd { runners_dir, unit_dir: temp.path().join("units") },
+        };
+        let octocrab = Octocrab::builder().base_url(server.uri())?.build()?;
+        let plan = reconciler.plan(&octocrab).await?;
+        let summary = plan
+            .actions
+            .iter()
+            .map(|action| match action {
+                Action::Remove { runner, reason, .. } => format!("remove {} {reason}", runner.name),
+                Action::Register(config) => format!("register {}", config.registered_name()),
+            })
+            .collect_vec();
+        assert_eq!(summary, [
+            "remove engine-builder-1 offline",
+            "remove old-builder-0 not configured",
+            "register engine-builder-1",
+        ]);
+
+        for id in [2, 3] {
+            Mock::given(method("DELETE"))
+                .and(path(format!("{runners_path}/{id}")))
+                .respond_with(ResponseTemplate::new(204))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        let token = serde_json::json!({"token": "ABCDEF", "expires_at": "2022-06-01T12:00:00Z"});
+        Mock::given(method("POST"))
+            .and(path(format!("{runners_path}/registration-token")))
+            .respond_with(ResponseTemplate::new(201).set_body_json(token))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let error = reconciler.apply(&octocrab, &plan).await.unwrap_err();
+        assert!(error.to_string().contains("Failed to deploy enso-org-enso-engine-builder-1."));
+        let args = crate::fs::read_to_string(&args_file)?;
+        assert!(args.contains("--name engine-builder-1 "));
+        assert!(args.contains("--token ABCDEF "));
+        Ok(())
+    }
+
+    #[test]
+    fn runner_deployment() -> Result {
+        let config = serde_yaml::from_str::<Config>(CONFIG)?;
+        let runners = configured_runners(&config, "builder", OS::Linux);
+        let runner = &runners[1];
+        assert_eq!(runner.qualified_name(), "enso-org-enso-engine-builder-1");
+
+        let command = runner.container_command()?;
+        assert_eq!(command[..2], ["sh", "-c"]);
+        assert!(command[2].starts_with("/runner/config.sh --unattended --replace"));
+        assert!(command[2].contains("--token $RUNNER_REGISTRATION_TOKEN"));
+        assert!(command[2].ends_with("--labels builder,engine && /runner/run.sh"));
+
+        let unit = runner.systemd_unit(Path::new("/srv/runners/enso-org-enso-engine-builder-1"));
+        assert!(unit.contains("ExecStart=/srv/runners/enso-org-enso-engine-builder-1/run.sh\n"));
+        Ok(())
+    }
+}
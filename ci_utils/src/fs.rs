@@ -231,6 +231,23 @@ pub fn allow_owner_execute(path: impl AsRef<Path>) -> Result {
     Ok(())
 }
 
+/// Whether the file's owner is allowed to execute it.
+#[cfg(not(target_os = "windows"))]
+#[context("Failed to read permissions of `{}`", path.as_ref().display())]
+pub fn is_owner_executable(path: impl AsRef<Path>) -> Result<bool> {
+    use std::os::unix::prelude::*;
+    let owner_can_execute = 0o0100;
+    Ok(path.as_ref().metadata()?.permissions().mode() & owner_can_execute != 0)
+}
+
+/// Whether the file's owner is allowed to execute it. Always true on Windows.
+#[cfg(target_os = "windows")]
+#[context("Failed to read permissions of `{}`", path.as_ref().display())]
+pub fn is_owner_executable(path: impl AsRef<Path>) -> Result<bool> {
+    path.as_ref().metadata()?;
+    Ok(true)
+}
+
 /// Get the size of a file after gzip compression.
 pub async fn compressed_size(path: impl AsRef<Path>) -> Result<byte_unit::Byte> {
     let file = ::tokio::io::BufReader::new(crate::fs::tokio::open(&path).await?);
@@ -286,12 +303,64 @@ pub async fn copy_if_different(source: impl AsRef<Path>, target: impl AsRef<Path
     Ok(())
 }
 
+/// Digest of the given files and directories: the relative paths and contents of all the files in
+/// them. Directories with names listed in `skipped_dirs` are not descended into.
+///
+/// Missing paths are allowed, they only contribute their path to the digest.
+pub fn digest_contents(
+    paths: impl IntoIterator<Item: AsRef<Path>>,
+    skipped_dirs: &[&str],
+) -> Result<String> {
+    use sha2::Digest;
+    let mut digest = sha2::Sha224::default();
+    for path in paths {
+        let path = path.as_ref();
+        digest.update(path.as_str());
+        if !path.exists() {
+            digest.update([0]);
+            continue;
+        }
+        let is_skipped = |entry: &walkdir::DirEntry| {
+            entry.depth() > 0
+                && entry.file_type().is_dir()
+                && skipped_dirs.iter().any(|skipped| entry.file_name() == OsStr::new(skipped))
+        };
+        let walker = walkdir::WalkDir::new(path).sort_by_file_name().into_iter();
+        for entry in walker.filter_entry(|entry| !is_skipped(entry)) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                let relative_path = entry.path().strip_prefix(path)?;
+                digest.update(relative_path.as_str());
+                std::io::copy(&mut open(entry.path())?, &mut digest)?;
+            }
+        }
+    }
+    Ok(data_encoding::BASE64URL_NOPAD.encode(&digest.finalize()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::log::setup_logging;
     use ::tokio;
 
+    #[test]
+    fn digesting_contents() -> Result {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        write(root.join_iter(["src", "main.rs"]), "fn main() {}")?;
+        write(root.join_iter(["src", "target", "output"]), "1")?;
+        let digest = || digest_contents([root.join("src"), root.join("missing")], &["target"]);
+
+        let initial = digest()?;
+        assert_eq!(digest()?, initial);
+        write(root.join_iter(["src", "target", "output"]), "2")?;
+        assert_eq!(digest()?, initial, "Skipped directories should not matter.");
+        write(root.join_iter(["src", "main.rs"]), "fn main() { }")?;
+        assert_ne!(digest()?, initial);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn copy_if_different_test() -> Result {
         setup_logging()?;
@@ -1,6 +1,10 @@
 use crate::prelude::*;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum, strum::Display, Serialize, Deserialize,
+)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
 pub enum AsyncPolicy {
     Sequential,
     FutureParallelism,
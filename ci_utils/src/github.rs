@@ -33,12 +33,59 @@ pub async fn get_all<T: DeserializeOwned>(
     client.all_pages(first_page).await
 }
 
+/// List all self-hosted runners, given the API path of either a repository's or an organization's
+/// runners.
+///
+/// The response is not an [`octocrab::Page`], so the pages are walked manually.
+pub async fn list_runners_at(octocrab: &Octocrab, path: &str) -> Result<Vec<model::Runner>> {
+    let mut runners = Vec::new();
+    for page in 1.. {
+        let url = octocrab.absolute_url(path)?;
+        let query = [("per_page", MAX_PER_PAGE.to_string()), ("page", page.to_string())];
+        let response: model::Runners = octocrab.get(url, Some(&query)).await?;
+        let is_last = response.runners.is_empty()
+            || runners.len() + response.runners.len() >= response.total_count as usize;
+        runners.extend(response.runners);
+        if is_last {
+            break;
+        }
+    }
+    Ok(runners)
+}
+
+/// Remove the registration of a self-hosted runner, given the API path of its owner's runners.
+pub async fn remove_runner_at(octocrab: &Octocrab, path: &str, runner_id: i32) -> Result {
+    let url = octocrab.absolute_url(format!("{path}/{runner_id}"))?;
+    let response = octocrab._delete(url, Option::<&()>::None).await?;
+    crate::io::web::handle_error_response(response).await?;
+    Ok(())
+}
+
 /// Entity that uniquely identifies a GitHub-hosted repository.
 #[async_trait]
 pub trait RepoPointer: Display {
     fn owner(&self) -> &str;
     fn name(&self) -> &str;
 
+    /// API path of the repository's self-hosted runners.
+    fn runners_path(&self) -> String {
+        iformat!("/repos/{self.owner()}/{self.name()}/actions/runners")
+    }
+
+    /// List the self-hosted runners registered for this repository.
+    async fn list_runners(&self, octocrab: &Octocrab) -> Result<Vec<model::Runner>> {
+        list_runners_at(octocrab, &self.runners_path())
+            .await
+            .context(format!("Failed to list the self-hosted runners of the {self} repository."))
+    }
+
+    /// Remove the self-hosted runner from this repository.
+    async fn remove_runner(&self, octocrab: &Octocrab, runner_id: i32) -> Result {
+        remove_runner_at(octocrab, &self.runners_path(), runner_id).await.context(format!(
+            "Failed to remove the self-hosted runner {runner_id} from the {self} repository."
+        ))
+    }
+
     /// Generate a token that can be used to register a new runner for this repository.
     async fn generate_runner_registration_token(
         &self,
@@ -191,6 +238,27 @@ pub trait OrganizationPointer {
     /// Organization name.
     fn name(&self) -> &str;
 
+    /// API path of the organization's self-hosted runners.
+    fn runners_path(&self) -> String {
+        iformat!("/orgs/{self.name()}/actions/runners")
+    }
+
+    /// List the self-hosted runners registered for this organization.
+    async fn list_runners(&self, octocrab: &Octocrab) -> Result<Vec<model::Runner>> {
+        list_runners_at(octocrab, &self.runners_path()).await.context(format!(
+            "Failed to list the self-hosted runners of the {} organization.",
+            self.name()
+        ))
+    }
+
+    /// Remove the self-hosted runner from this organization.
+    async fn remove_runner(&self, octocrab: &Octocrab, runner_id: i32) -> Result {
+        remove_runner_at(octocrab, &self.runners_path(), runner_id).await.context(format!(
+            "Failed to remove the self-hosted runner {runner_id} from the {} organization.",
+            self.name()
+        ))
+    }
+
     /// Generate a token that can be used to register a new runner for this repository.
     async fn generate_runner_registration_token(
         &self,
@@ -16,6 +16,16 @@ pub struct Runner {
     pub labels: Vec<Label>,
 }
 
+impl Runner {
+    pub fn is_online(&self) -> bool {
+        self.status == "online"
+    }
+
+    pub fn has_label(&self, name: &str) -> bool {
+        self.labels.iter().any(|label| label.name == name)
+    }
+}
+
 /// A label assigned to the self-hosted runner.
 ///
 /// See:
@@ -47,7 +47,13 @@ impl<'a> GraalVM<'a> {
         crate::program::version::find_in_text(line)
     }
 
-    async fn url(&self) -> anyhow::Result<Url> {
+    /// Name of the directory that the package is extracted to.
+    pub fn directory_name(&self) -> String {
+        format!("{}-{}-{}", PACKAGE_PREFIX, self.java_version, self.graal_version)
+    }
+
+    /// URL of the package, found in the GraalVM releases.
+    pub async fn url(&self) -> anyhow::Result<Url> {
         let Self { graal_version, java_version, client, arch, os } = &self;
 
         let os_name = match *os {
@@ -85,10 +91,7 @@ impl<'a> Goodie for GraalVM<'a> {
     }
 
     async fn lookup(&self, database: &GoodieDatabase) -> Result<Self::Instance> {
-        let expected_dir_name = PathBuf::from(format!(
-            "{}-{}-{}",
-            PACKAGE_PREFIX, self.java_version, self.graal_version
-        ));
+        let expected_dir_name = PathBuf::from(self.directory_name());
         for entry in crate::fs::read_dir(&database.root_directory)? {
             let entry = entry?;
             if entry.file_type()?.is_dir() && entry.path().file_name().contains(&expected_dir_name)
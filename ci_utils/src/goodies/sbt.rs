@@ -2,6 +2,12 @@ use crate::goodie::GoodieDatabase;
 use crate::prelude::*;
 use crate::programs;
 
+/// Version of the SBT package at [`URL`].
+pub const VERSION: Version = Version::new(1, 5, 5);
+
+/// The SBT package. It is extracted to the `sbt` directory.
+pub const URL: &str = "https://github.com/sbt/sbt/releases/download/v1.5.5/sbt-1.5.5.tgz";
+
 pub struct Sbt;
 
 pub struct Instance {
@@ -28,8 +34,7 @@ impl Goodie for Sbt {
     }
 
     async fn install(&self, database: &GoodieDatabase) -> Result<Self::Instance> {
-        let url = "https://github.com/sbt/sbt/releases/download/v1.5.5/sbt-1.5.5.tgz";
-        crate::io::download_and_extract(url.clone(), &database.root_directory).await?;
+        crate::io::download_and_extract(URL, &database.root_directory).await?;
         self.lookup(database).await
     }
 }
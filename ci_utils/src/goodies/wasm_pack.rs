@@ -1,2 +1,85 @@
-// use crate::goodie::GoodieDatabase;
-// use crate::prelude::*;
+use crate::prelude::*;
+
+use crate::goodie::GoodieDatabase;
+use crate::programs;
+
+/// The wasm-pack release binary of the given version.
+#[derive(Clone, Debug)]
+pub struct WasmPack {
+    pub version: Version,
+    pub os:      OS,
+    pub arch:    Arch,
+}
+
+pub struct Instance {
+    directory: PathBuf,
+}
+
+impl crate::goodie::Instance for Instance {
+    fn add_to_environment(&self) -> anyhow::Result<()> {
+        crate::env::prepend_to_path(&self.directory)
+    }
+}
+
+impl WasmPack {
+    /// The target triple naming the release package.
+    pub fn target(&self) -> Result<&'static str> {
+        Ok(match (self.os, self.arch) {
+            (OS::Linux, Arch::X86_64) => "x86_64-unknown-linux-musl",
+            (OS::Linux, Arch::AArch64) => "aarch64-unknown-linux-musl",
+            (OS::MacOS, _) => "x86_64-apple-darwin",
+            (OS::Windows, Arch::X86_64) => "x86_64-pc-windows-msvc",
+            (os, arch) => bail!("No wasm-pack package for {os} on {arch}."),
+        })
+    }
+
+    /// Name of the directory that the package is extracted to.
+    pub fn directory_name(&self) -> Result<String> {
+        Ok(format!("wasm-pack-v{}-{}", self.version, self.target()?))
+    }
+
+    pub fn url(&self) -> Result<Url> {
+        let url = format!(
+            "https://github.com/rustwasm/wasm-pack/releases/download/v{}/{}.tar.gz",
+            self.version,
+            self.directory_name()?
+        );
+        Ok(url.parse()?)
+    }
+}
+
+#[async_trait]
+impl Goodie for WasmPack {
+    const NAME: &'static str = "wasm-pack";
+    type Instance = Instance;
+
+    async fn is_already_available(&self) -> Result<bool> {
+        Ok(programs::WasmPack.version().await.contains(&self.version))
+    }
+
+    async fn lookup(&self, database: &GoodieDatabase) -> Result<Self::Instance> {
+        database.find_dir(self.directory_name()?).map(|directory| Instance { directory })
+    }
+
+    async fn install(&self, database: &GoodieDatabase) -> Result<Self::Instance> {
+        crate::io::download_and_extract(self.url()?, &database.root_directory).await?;
+        self.lookup(database).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_url() -> Result {
+        let goodie =
+            WasmPack { version: Version::new(0, 10, 2), os: OS::Linux, arch: Arch::X86_64 };
+        assert_eq!(
+            goodie.url()?.as_str(),
+            "https://github.com/rustwasm/wasm-pack/releases/download/v0.10.2/\
+             wasm-pack-v0.10.2-x86_64-unknown-linux-musl.tar.gz"
+        );
+        Ok(())
+    }
+}
@@ -26,16 +26,21 @@ pub async fn download(url: impl IntoUrl) -> Result<impl Stream<Item = reqwest::R
 /// Get the full response body from URL as bytes.
 pub async fn download_all(url: impl IntoUrl) -> anyhow::Result<Bytes> {
     let url = url.into_url()?;
-    let bar = progress_bar(indicatif::ProgressBar::new_spinner);
-    bar.enable_steady_tick(Duration::from_millis(100));
-    bar.set_message(format!("Downloading {}", url));
-    let response = reqwest::get(url).await?;
-    if let Some(e) = response.error_for_status_ref().err() {
-        let body = response.text().await?;
-        Err(e).context(body)
-    } else {
-        response.bytes().await.map_err(Into::into)
+    let span = info_span!("Downloading.", category = "download", %url);
+    async move {
+        let bar = progress_bar(indicatif::ProgressBar::new_spinner);
+        bar.enable_steady_tick(Duration::from_millis(100));
+        bar.set_message(format!("Downloading {}", url));
+        let response = reqwest::get(url).await?;
+        if let Some(e) = response.error_for_status_ref().err() {
+            let body = response.text().await?;
+            Err(e).context(body)
+        } else {
+            response.bytes().await.map_err(Into::into)
+        }
     }
+    .instrument(span)
+    .await
 }
 
 /// Take the trailing filename from URL path.
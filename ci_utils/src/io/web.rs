@@ -49,6 +49,7 @@ pub async fn download_file(url: impl IntoUrl, output: impl AsRef<Path>) -> Resul
 
 
 #[tracing::instrument(name="Streaming http response to a file.", skip(output), fields(
+    category = "download",
     dest = %output.as_ref().display(),
     url  = %response.url()
 ), err)]
@@ -168,6 +168,7 @@ use prelude::*;
 use std::net::Ipv4Addr;
 use std::net::SocketAddrV4;
 use std::net::TcpListener;
+use std::sync::Mutex;
 
 use ::anyhow::Context;
 
@@ -179,11 +180,21 @@ pub const USER_AGENT: &str = "enso-build";
 
 pub const UNREGISTERED_PORTS: Range<u16> = 49152..65535;
 
+lazy_static! {
+    /// Ports that have been already returned by [`get_free_port`].
+    static ref HANDED_OUT_PORTS: Mutex<HashSet<u16>> = default();
+}
+
 /// Looks up a free port in the IANA private or dynamic port range.
+///
+/// The same port is never returned twice, so services started concurrently won't collide, even
+/// though none of them has bound its port yet.
 pub fn get_free_port() -> Result<u16> {
     let port_range = UNREGISTERED_PORTS;
-    port_range
+    let mut handed_out = HANDED_OUT_PORTS.lock().map_err(|e| anyhow!("{e}"))?;
+    let port = port_range
         .into_iter()
+        .filter(|port| !handed_out.contains(port))
         .find(|port| {
             // Note that we must use Ipv4Addr::UNSPECIFIED. Ipv4Addr::LOCALHOST would not be enough,
             // as it misses e.g. services spawned by docker subnetworks.
@@ -192,7 +203,17 @@ pub fn get_free_port() -> Result<u16> {
             // FIXME investigate? this can show firewall dialog on windows
             TcpListener::bind(ipv4).is_ok()
         })
-        .context("Failed to find a free local port.")
+        .context("Failed to find a free local port.")?;
+    handed_out.insert(port);
+    Ok(port)
+}
+
+/// Let [`get_free_port`] return the port again, once the service using it has stopped.
+pub fn release_port(port: u16) {
+    match HANDED_OUT_PORTS.lock() {
+        Ok(mut handed_out) => drop(handed_out.remove(&port)),
+        Err(e) => warn!("Failed to release the port {port}: {e}"),
+    }
 }
 
 pub fn ok_ready_boxed<'a, T: 'a + Send>(t: T) -> BoxFuture<'a, Result<T>> {
@@ -212,4 +233,17 @@ mod tests {
     pub fn get_free_port_test() {
         debug!("{:?}", get_free_port());
     }
+
+    #[test]
+    fn handed_out_ports() -> Result {
+        let first = get_free_port()?;
+        let second = get_free_port()?;
+        assert_ne!(first, second);
+        assert!(UNREGISTERED_PORTS.contains(&first));
+        assert!(HANDED_OUT_PORTS.lock().unwrap().contains(&first));
+        release_port(first);
+        assert!(!HANDED_OUT_PORTS.lock().unwrap().contains(&first));
+        release_port(second);
+        Ok(())
+    }
 }
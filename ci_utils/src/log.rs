@@ -12,6 +12,8 @@ use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::Registry;
 
+pub mod profiling;
+
 pub fn is_our_module_path(path: impl AsRef<str>) -> bool {
     ["ide_ci::", "enso_build", "enso_build2"]
         .into_iter()
@@ -64,7 +66,7 @@ pub fn setup_logging() -> Result {
         .from_env_lossy();
 
     tracing::subscriber::set_global_default(
-        Registry::default().with(MyLayer).with(
+        Registry::default().with(MyLayer).with(profiling::ProfilingLayer).with(
             tracing_subscriber::fmt::layer()
                 .without_time()
                 .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
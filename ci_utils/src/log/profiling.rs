@@ -0,0 +1,338 @@
+//! Profiling of the build, based on the tracing spans.
+//!
+//! Once enabled, the start and end time of every span is recorded. When finished, the recording is
+//! written as a Chrome trace-event JSON file (to be opened in `chrome://tracing` or Perfetto) and
+//! the slowest spans are printed.
+//!
+//! Spans can declare what they measure with a `category` field, like `process`, `download` or
+//! `cache`. Spans without it are considered build steps.
+
+use crate::prelude::*;
+
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::span::Attributes;
+use tracing::span::Record;
+use tracing::Id;
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+
+
+/// Category of the spans that do not declare any.
+pub const DEFAULT_CATEGORY: &str = "step";
+
+/// Name of the span field that holds its category.
+pub const CATEGORY_FIELD: &str = "category";
+
+/// Length limit of the span descriptions in the slowest spans table.
+pub const DESCRIPTION_MAX_LENGTH: usize = 120;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Where the Chrome trace file is written.
+    pub output:        PathBuf,
+    /// How many of the slowest spans are printed.
+    pub slowest_count: usize,
+}
+
+/// A span that has been closed while profiling.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpanRecord {
+    pub name:     String,
+    pub category: String,
+    pub fields:   BTreeMap<String, String>,
+    /// Since the profiling was enabled.
+    pub start:    Duration,
+    pub duration: Duration,
+}
+
+impl SpanRecord {
+    pub fn end(&self) -> Duration {
+        self.start + self.duration
+    }
+
+    /// The span name followed by its fields, shortened if needed.
+    pub fn description(&self) -> String {
+        let fields = self.fields.iter().map(|(name, value)| format!("{name}={value}"));
+        let ret = once(self.name.clone()).chain(fields).join(" ");
+        if ret.chars().count() > DESCRIPTION_MAX_LENGTH {
+            let shortened: String = ret.chars().take(DESCRIPTION_MAX_LENGTH - 1).collect();
+            shortened + "…"
+        } else {
+            ret
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Recording {
+    settings: Settings,
+    start:    Instant,
+    spans:    Vec<SpanRecord>,
+}
+
+lazy_static! {
+    static ref RECORDING: Mutex<Option<Recording>> = default();
+}
+
+fn recording() -> std::sync::MutexGuard<'static, Option<Recording>> {
+    // Poisoning is not a concern, as the recording is only ever appended to.
+    RECORDING.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Start recording the spans. Requires [`ProfilingLayer`] to be registered, see
+/// [`crate::log::setup_logging`].
+pub fn enable(settings: Settings) {
+    *recording() = Some(Recording { settings, start: Instant::now(), spans: default() });
+}
+
+pub fn is_enabled() -> bool {
+    recording().is_some()
+}
+
+/// Stop recording, write the trace file and print the slowest spans.
+///
+/// Does nothing if profiling was not enabled. Spans that are still open are not included.
+pub fn finish() -> Result {
+    let recording = recording().take();
+    if let Some(Recording { settings, spans, .. }) = recording {
+        crate::fs::create_parent_dir_if_missing(&settings.output)?;
+        crate::fs::write_json(&settings.output, &ChromeTrace::new(&spans))?;
+        println!("{}", slowest_table(&spans, settings.slowest_count));
+        info!("Profiling trace written to {}.", settings.output.display());
+    }
+    Ok(())
+}
+
+/// Table of the slowest spans, the slowest first.
+pub fn slowest_table(spans: &[SpanRecord], count: usize) -> String {
+    let mut slowest = spans.iter().collect_vec();
+    slowest.sort_by(|a, b| b.duration.cmp(&a.duration));
+    let mut ret =
+        format!("Slowest {} of {} profiled spans:\n", count.min(spans.len()), spans.len());
+    for span in slowest.into_iter().take(count) {
+        let seconds = span.duration.as_secs_f64();
+        ret += &format!("  {seconds:>9.2}s  {:<8}  {}\n", span.category, span.description());
+    }
+    ret
+}
+
+/// Event of the "complete" (`X`) kind in the Chrome trace-event format.
+#[derive(Clone, Debug, Serialize)]
+pub struct TraceEvent<'a> {
+    pub name: &'a str,
+    pub cat:  &'a str,
+    pub ph:   &'static str,
+    /// Start time in microseconds.
+    pub ts:   u64,
+    /// Duration in microseconds.
+    pub dur:  u64,
+    pub pid:  u32,
+    pub tid:  u64,
+    pub args: &'a BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChromeTrace<'a> {
+    pub trace_events:      Vec<TraceEvent<'a>>,
+    pub display_time_unit: &'static str,
+}
+
+impl<'a> ChromeTrace<'a> {
+    pub fn new(spans: &'a [SpanRecord]) -> Self {
+        let micros = |duration: Duration| duration.as_micros().try_into().unwrap_or(u64::MAX);
+        let trace_events = spans
+            .iter()
+            .zip(assign_tracks(spans))
+            .map(|(span, track)| TraceEvent {
+                name: &span.name,
+                cat:  &span.category,
+                ph:   "X",
+                ts:   micros(span.start),
+                dur:  micros(span.duration),
+                pid:  std::process::id(),
+                tid:  track,
+                args: &span.fields,
+            })
+            .collect();
+        Self { trace_events, display_time_unit: "ms" }
+    }
+}
+
+/// Assign the spans to tracks (trace's threads), so the spans on each track are properly nested, as
+/// the trace viewers require. Spans of concurrent tasks end up on separate tracks.
+pub fn assign_tracks(spans: &[SpanRecord]) -> Vec<u64> {
+    let mut order = (0..spans.len()).collect_vec();
+    // Outer spans go first, so the inner ones can be nested in them.
+    order.sort_by(|&a, &b| {
+        spans[a].start.cmp(&spans[b].start).then(spans[b].duration.cmp(&spans[a].duration))
+    });
+    let mut ret = vec![0; spans.len()];
+    // For each track, the ends of the spans that are open at the current point.
+    let mut tracks: Vec<Vec<Duration>> = default();
+    for index in order {
+        let span = &spans[index];
+        let fits = |open: &mut Vec<Duration>| {
+            while open.last().map_or(false, |&end| end <= span.start) {
+                open.pop();
+            }
+            open.last().map_or(true, |&end| span.end() <= end)
+        };
+        let track = match tracks.iter_mut().position(fits) {
+            Some(track) => track,
+            None => {
+                tracks.push(default());
+                tracks.len() - 1
+            }
+        };
+        tracks[track].push(span.end());
+        ret[index] = track as u64;
+    }
+    ret
+}
+
+/// Collects the span fields as strings.
+#[derive(Clone, Debug, Default)]
+struct FieldVisitor(BTreeMap<String, String>);
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().into(), format!("{value:?}"));
+    }
+}
+
+/// Data attached to the spans while profiling.
+#[derive(Clone, Debug)]
+struct Timing {
+    start:  Instant,
+    fields: BTreeMap<String, String>,
+}
+
+/// Layer recording the span timings, when profiling is enabled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProfilingLayer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for ProfilingLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !is_enabled() {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            let mut fields = FieldVisitor::default();
+            attrs.record(&mut fields);
+            let timing = Timing { start: Instant::now(), fields: fields.0 };
+            span.extensions_mut().insert(timing);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<Timing>() {
+                let mut fields = FieldVisitor::default();
+                values.record(&mut fields);
+                timing.fields.extend(fields.0);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(&id) {
+            Some(span) => span,
+            None => return,
+        };
+        let timing = match span.extensions_mut().remove::<Timing>() {
+            Some(timing) => timing,
+            None => return,
+        };
+        let duration = timing.start.elapsed();
+        if let Some(recording) = recording().as_mut() {
+            let mut fields = timing.fields;
+            let category = fields.remove(CATEGORY_FIELD).unwrap_or_else(|| DEFAULT_CATEGORY.into());
+            recording.spans.push(SpanRecord {
+                name: span.name().into(),
+                category,
+                fields,
+                start: timing.start.saturating_duration_since(recording.start),
+                duration,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn profiling() -> Result {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("trace.json");
+        let subscriber = tracing_subscriber::Registry::default().with(ProfilingLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            drop(info_span!("Before profiling."));
+            enable(Settings { output: output.clone(), slowest_count: 1 });
+            let outer = info_span!("Building.").entered();
+            let process = info_span!("Running process.", category = "process", status = 0);
+            process.in_scope(|| std::thread::sleep(Duration::from_millis(20)));
+            drop(process);
+            let spans = recording().as_ref().map(|recording| recording.spans.clone());
+            let spans = spans.unwrap_or_default();
+            assert_eq!(spans.len(), 1);
+            assert_eq!(spans[0].category, "process");
+            assert_eq!(spans[0].description(), "Running process. status=0");
+            assert!(spans[0].duration >= Duration::from_millis(20));
+            drop(outer);
+            finish()
+        })?;
+        assert!(!is_enabled());
+
+        let trace: serde_json::Value = serde_json::from_str(&crate::fs::read_to_string(&output)?)?;
+        let events = trace["traceEvents"].as_array().context("No trace events.")?;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1]["name"], "Building.");
+        assert_eq!(events[1]["cat"], DEFAULT_CATEGORY);
+        assert_eq!(events[0]["ph"], "X");
+        assert_eq!(events[0]["args"]["status"], "0");
+        Ok(())
+    }
+
+    #[test]
+    fn table() {
+        let span = |name: &str, start, secs| SpanRecord {
+            name:     name.into(),
+            category: DEFAULT_CATEGORY.into(),
+            fields:   default(),
+            start:    Duration::from_secs(start),
+            duration: Duration::from_secs(secs),
+        };
+        let table = slowest_table(&[span("fast", 0, 1), span("slow", 0, 3)], 1);
+        assert_eq!(table, "Slowest 1 of 2 profiled spans:\n       3.00s  step      slow\n");
+        let long = span(&"x".repeat(200), 0, 0).description();
+        assert_eq!(long.chars().count(), DESCRIPTION_MAX_LENGTH);
+    }
+
+    #[test]
+    fn tracks() {
+        let span = |start, secs| SpanRecord {
+            name:     default(),
+            category: default(),
+            fields:   default(),
+            start:    Duration::from_secs(start),
+            duration: Duration::from_secs(secs),
+        };
+        // The build, with two overlapping tasks and a step nested in the first one.
+        let spans = [span(1, 2), span(0, 10), span(2, 4), span(1, 1), span(5, 5)];
+        assert_eq!(assign_tracks(&spans), [0, 0, 1, 0, 0]);
+    }
+}
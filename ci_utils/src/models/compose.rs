@@ -1,34 +1,354 @@
-//! Simple and incomplete model for docker-compose.yml file contents.
+//! Model for the docker-compose.yml file contents.
 //!
-//! See: <https://docs.docker.com/compose/compose-file/compose-file-v3/>
+//! Only the commonly used subset of the specification is covered. The shorthand forms (e.g. the
+//! list of `depends_on` services) are accepted, but the files are always written in the full form.
+//!
+//! See: <https://docs.docker.com/compose/compose-file/>
 
 use crate::prelude::*;
 
+use crate::serde::single_or_sequence;
+use crate::serde::Either;
+use serde::Deserializer;
+
+
+
 /// A build section of the service.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 pub struct Build {
-    pub context: PathBuf,
-    pub target:  String,
-    pub args:    Vec<String>,
+    pub context:    PathBuf,
+    /// Path to the Dockerfile, relative to the context.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dockerfile: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target:     Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "key_value_pairs",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub args:       BTreeMap<String, String>,
+}
+
+impl From<PathBuf> for Build {
+    fn from(context: PathBuf) -> Self {
+        Self { context, ..default() }
+    }
+}
+
+/// A command, like the service's command or the healthcheck's test.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum Command {
+    /// Command line run by the container's shell.
+    Shell(String),
+    /// Command given as separate arguments. For the healthchecks, it must start with either
+    /// `CMD`, `CMD-SHELL` or `NONE`.
+    Exec(Vec<String>),
+}
+
+impl Command {
+    pub fn exec<S: Into<String>>(args: impl IntoIterator<Item = S>) -> Self {
+        Self::Exec(args.into_iter().map(Into::into).collect())
+    }
+}
+
+/// How the service's health is checked. The durations use the Compose format, like `1m30s`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Healthcheck {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test:         Option<Command>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval:     Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout:      Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries:      Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_period: Option<String>,
+    /// Disable the healthcheck defined by the image.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable:      Option<bool>,
+}
+
+/// What state of the dependency the service waits for before being started.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    ServiceStarted,
+    ServiceHealthy,
+    ServiceCompletedSuccessfully,
+}
+
+impl Default for Condition {
+    fn default() -> Self {
+        Self::ServiceStarted
+    }
+}
+
+/// An entry of the service's `depends_on`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Dependency {
+    #[serde(default)]
+    pub condition: Condition,
+}
+
+impl From<Condition> for Dependency {
+    fn from(condition: Condition) -> Self {
+        Self { condition }
+    }
 }
 
 /// A service entry.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 pub struct Service {
-    pub build:   Build,
-    pub command: String,
-    pub volumes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image:          Option<String>,
+    #[serde(default, deserialize_with = "build", skip_serializing_if = "Option::is_none")]
+    pub build:          Option<Build>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entrypoint:     Option<Command>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command:        Option<Command>,
+    #[serde(
+        default,
+        deserialize_with = "key_value_pairs",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub environment:    BTreeMap<String, String>,
+    /// Files with the additional environment, relative to the compose file.
+    #[serde(
+        default,
+        deserialize_with = "single_or_sequence",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub env_file:       Vec<PathBuf>,
+    /// Published ports in the short syntax, like `8080:80`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports:          Vec<String>,
+    /// Mounted volumes in the short syntax, like `data:/var/lib/data`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumes:        Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub networks:       Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck:    Option<Healthcheck>,
+    #[serde(
+        default,
+        deserialize_with = "dependencies",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub depends_on:     BTreeMap<String, Dependency>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart:        Option<String>,
+}
+
+impl Service {
+    /// Service run from the given image.
+    pub fn image(image: impl Into<String>) -> Self {
+        Self { image: Some(image.into()), ..default() }
+    }
 }
 
 /// A top-level volume entry.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
 pub struct Volume {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver:   Option<String>,
+    /// The volume is not managed by Compose and must exist already.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub external: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name:     Option<String>,
+}
+
+/// A top-level network entry.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Network {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver:   Option<String>,
+    /// The network is not managed by Compose and must exist already.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub external: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name:     Option<String>,
 }
 
 /// File root.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 pub struct Compose {
+    /// Obsolete, accepted for the older files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version:  Option<String>,
     pub services: BTreeMap<String, Service>,
+    #[serde(
+        default,
+        deserialize_with = "nullable_values",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
     pub volumes:  BTreeMap<String, Volume>,
+    #[serde(
+        default,
+        deserialize_with = "nullable_values",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub networks: BTreeMap<String, Network>,
+}
+
+/// The build section can be just the context path.
+fn build<'de, D>(de: D) -> std::result::Result<Option<Build>, D::Error>
+where D: Deserializer<'de> {
+    let build = Either::<PathBuf, Build>::deserialize(de)?;
+    Ok(Some(build.into_right()))
+}
+
+/// Maps like the environment can be also given as a list of `KEY=VALUE` entries.
+fn key_value_pairs<'de, D>(de: D) -> std::result::Result<BTreeMap<String, String>, D::Error>
+where D: Deserializer<'de> {
+    use serde::de::Error;
+    match Either::<Vec<String>, BTreeMap<String, String>>::deserialize(de)? {
+        Either::Left(pairs) => pairs
+            .into_iter()
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => Ok((key.to_string(), value.to_string())),
+                None => Err(D::Error::custom(format!("Expected `KEY=VALUE`, got `{pair}`."))),
+            })
+            .collect(),
+        Either::Right(map) => Ok(map),
+    }
+}
+
+/// The dependencies can be also given as a list of services, which need to be just started.
+fn dependencies<'de, D>(de: D) -> std::result::Result<BTreeMap<String, Dependency>, D::Error>
+where D: Deserializer<'de> {
+    Ok(match Either::<Vec<String>, BTreeMap<String, Dependency>>::deserialize(de)? {
+        Either::Left(services) =>
+            services.into_iter().map(|service| (service, default())).collect(),
+        Either::Right(map) => map,
+    })
+}
+
+/// Entries with the default configuration are usually left empty, like `data:`.
+fn nullable_values<'de, D, T>(de: D) -> std::result::Result<BTreeMap<String, T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default, {
+    let map = BTreeMap::<String, Option<T>>::deserialize(de)?;
+    Ok(map.into_iter().map(|(name, value)| (name, value.unwrap_or_default())).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE: &str = r#"
+services:
+  db:
+    image: postgres:14
+    environment:
+      POSTGRES_PASSWORD: secret
+    env_file:
+      - db.env
+    ports:
+      - "5432:5432"
+    volumes:
+      - data:/var/lib/postgresql/data
+    networks:
+      - backend
+    healthcheck:
+      test: ["CMD", "pg_isready"]
+      interval: 5s
+      timeout: 3s
+      retries: 10
+      start_period: 1m30s
+    restart: unless-stopped
+  app:
+    build:
+      context: app
+      dockerfile: Dockerfile.dev
+      target: dev
+      args:
+        VERSION: "1.0"
+    container_name: app
+    entrypoint: ["/bin/app"]
+    command: --verbose
+    depends_on:
+      db:
+        condition: service_healthy
+      migrations:
+        condition: service_completed_successfully
+  migrations:
+    image: migrations
+    healthcheck:
+      disable: true
+volumes:
+  data:
+    driver: local
+  shared:
+    external: true
+    name: shared-data
+networks:
+  backend:
+    driver: bridge
+"#;
+
+    #[test]
+    fn round_trip() -> Result {
+        let compose = serde_yaml::from_str::<Compose>(COMPOSE)?;
+        let serialized = serde_yaml::to_string(&compose)?;
+        assert_eq!(serde_yaml::from_str::<Compose>(&serialized)?, compose);
+
+        let expected = serde_yaml::from_str::<serde_json::Value>(COMPOSE)?;
+        let actual = serde_yaml::from_str::<serde_json::Value>(&serialized)?;
+        assert_eq!(expected, actual, "Round trip changed the file:\n{}", serialized);
+
+        let db = &compose.services["db"];
+        let healthcheck = db.healthcheck.as_ref().unwrap();
+        assert_eq!(healthcheck.test, Some(Command::exec(["CMD", "pg_isready"])));
+        assert_eq!(healthcheck.retries, Some(10));
+        let app = &compose.services["app"];
+        assert_eq!(app.command, Some(Command::Shell("--verbose".into())));
+        assert_eq!(app.depends_on["db"].condition, Condition::ServiceHealthy);
+        assert!(compose.volumes["shared"].external);
+        Ok(())
+    }
+
+    #[test]
+    fn shorthands() -> Result {
+        let compose = serde_yaml::from_str::<Compose>(
+            r#"
+version: "3.8"
+services:
+  app:
+    build: app
+    environment:
+      - MODE=dev
+      - EMPTY=
+    env_file: app.env
+    depends_on:
+      - db
+volumes:
+  data:
+"#,
+        )?;
+        let app = &compose.services["app"];
+        assert_eq!(app.build, Some(Build::from(PathBuf::from("app"))));
+        assert_eq!(app.environment["MODE"], "dev");
+        assert_eq!(app.environment["EMPTY"], "");
+        assert_eq!(app.env_file, [PathBuf::from("app.env")]);
+        assert_eq!(app.depends_on["db"].condition, Condition::ServiceStarted);
+        assert_eq!(compose.volumes["data"], Volume::default());
+
+        let serialized = serde_yaml::to_string(&compose)?;
+        assert!(serialized.contains("condition: service_started"));
+        assert_eq!(serde_yaml::from_str::<Compose>(&serialized)?, compose);
+
+        let invalid = "services:\n  app:\n    environment: [MODE]\n";
+        assert!(serde_yaml::from_str::<Compose>(invalid).is_err());
+        Ok(())
+    }
 }
@@ -36,7 +36,7 @@ impl RepoConfig {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum RunnerLocation {
     Organization(OrganizationContext),
@@ -64,10 +64,38 @@ impl RunnerLocation {
             RunnerLocation::Repository(repo) => repo.url(),
         }
     }
+
+    /// The runners registered at this location.
+    pub async fn list_runners(
+        &self,
+        octocrab: &Octocrab,
+    ) -> anyhow::Result<Vec<crate::github::model::Runner>> {
+        match self {
+            RunnerLocation::Organization(org) => org.list_runners(octocrab).await,
+            RunnerLocation::Repository(repo) => repo.list_runners(octocrab).await,
+        }
+    }
+
+    /// Remove the registration of a runner from this location.
+    pub async fn remove_runner(&self, octocrab: &Octocrab, runner_id: i32) -> anyhow::Result<()> {
+        match self {
+            RunnerLocation::Organization(org) => org.remove_runner(octocrab, runner_id).await,
+            RunnerLocation::Repository(repo) => repo.remove_runner(octocrab, runner_id).await,
+        }
+    }
+}
+
+impl Display for RunnerLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunnerLocation::Organization(org) => write!(f, "{} organization", org.name),
+            RunnerLocation::Repository(repo) => write!(f, "{repo} repository"),
+        }
+    }
 }
 
 /// Data denoting a specific GitHub organization.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct OrganizationContext {
     pub name: String,
 }
@@ -0,0 +1,306 @@
+//! Model of the test run results, gathered from the test runners' reports.
+
+use crate::prelude::*;
+
+use crate::serde::duration_secs;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+pub mod history;
+pub mod junit;
+
+
+
+/// How many lines of the failure details are shown in the summary.
+pub const FAILURE_DETAILS_LINES: usize = 10;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Failure {
+    pub message: Option<String>,
+    /// Kind of the failure, typically the exception class name.
+    pub kind:    Option<String>,
+    /// Further details, like the stack trace.
+    pub details: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    Passed,
+    /// Test assertion has not been met.
+    Failed(Failure),
+    /// Test could not be run to completion because of an unexpected error.
+    Errored(Failure),
+    Skipped {
+        message: Option<String>,
+    },
+}
+
+impl Outcome {
+    pub fn failure(&self) -> Option<&Failure> {
+        match self {
+            Outcome::Failed(failure) | Outcome::Errored(failure) => Some(failure),
+            _ => None,
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        self.failure().is_some()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TestCase {
+    pub name:       String,
+    pub class_name: Option<String>,
+    #[serde(with = "duration_secs")]
+    pub duration:   Duration,
+    #[serde(flatten)]
+    pub outcome:    Outcome,
+    /// Whether the test has failed at first but passed when rerun.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub flaky:      bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestSuite {
+    pub name:     String,
+    #[serde(with = "duration_secs")]
+    pub duration: Duration,
+    pub cases:    Vec<TestCase>,
+}
+
+/// Numbers of tests by their outcome.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Totals {
+    pub passed:  usize,
+    pub failed:  usize,
+    pub skipped: usize,
+}
+
+impl Totals {
+    pub fn total(&self) -> usize {
+        self.passed + self.failed + self.skipped
+    }
+}
+
+/// Results of all the test suites run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestReport {
+    pub suites: Vec<TestSuite>,
+}
+
+impl TestReport {
+    /// Load all the JUnit XML reports matching the given glob pattern.
+    pub fn from_junit_glob(pattern: &str) -> Result<Self> {
+        let mut ret = Self::default();
+        for path in glob::glob(pattern)? {
+            ret.suites.extend(junit::parse_file(path?)?);
+        }
+        Ok(ret)
+    }
+
+    pub fn merge(&mut self, other: TestReport) {
+        self.suites.extend(other.suites);
+    }
+
+    pub fn cases(&self) -> impl Iterator<Item = (&TestSuite, &TestCase)> {
+        self.suites.iter().flat_map(|suite| suite.cases.iter().map(move |case| (suite, case)))
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (&TestSuite, &TestCase)> {
+        self.cases().filter(|(_, case)| case.outcome.is_failure())
+    }
+
+    /// Tests that have passed only after being rerun.
+    pub fn flaky(&self) -> impl Iterator<Item = (&TestSuite, &TestCase)> {
+        self.cases().filter(|(_, case)| case.flaky)
+    }
+
+    /// Incorporate the results of rerunning some of the suites.
+    ///
+    /// The rerun results replace the previous ones. Tests that have failed before and passed now
+    /// are marked as flaky.
+    pub fn apply_rerun(&mut self, rerun: TestReport) {
+        for suite in rerun.suites {
+            let old = match self.suites.iter_mut().find(|old| old.name == suite.name) {
+                Some(old) => old,
+                None => {
+                    self.suites.push(suite);
+                    continue;
+                }
+            };
+            old.duration += suite.duration;
+            for mut case in suite.cases {
+                match old.cases.iter_mut().find(|old_case| old_case.name == case.name) {
+                    Some(old_case) => {
+                        let failed_before = old_case.flaky || old_case.outcome.is_failure();
+                        case.flaky = failed_before && case.outcome == Outcome::Passed;
+                        *old_case = case;
+                    }
+                    None => old.cases.push(case),
+                }
+            }
+        }
+    }
+
+    /// Names of the suites that have any failed tests.
+    pub fn failed_suites(&self) -> BTreeSet<&str> {
+        self.failures().map(|(suite, _)| suite.name.as_str()).collect()
+    }
+
+    pub fn slowest(&self, count: usize) -> Vec<(&TestSuite, &TestCase)> {
+        let mut cases = self.cases().collect_vec();
+        cases.sort_by(|(_, a), (_, b)| b.duration.cmp(&a.duration));
+        cases.truncate(count);
+        cases
+    }
+
+    pub fn totals(&self) -> Totals {
+        let mut ret = Totals::default();
+        for (_, case) in self.cases() {
+            match case.outcome {
+                Outcome::Passed => ret.passed += 1,
+                Outcome::Failed(_) | Outcome::Errored(_) => ret.failed += 1,
+                Outcome::Skipped { .. } => ret.skipped += 1,
+            }
+        }
+        ret
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> Result {
+        crate::fs::create_parent_dir_if_missing(&path)?;
+        crate::fs::write_json(path, self)
+    }
+
+    /// Human-readable summary, listing the slowest tests and all the failures.
+    pub fn summary(&self, slowest_count: usize) -> Summary<'_> {
+        Summary { report: self, slowest_count }
+    }
+}
+
+/// See [`TestReport::summary`].
+#[derive(Clone, Copy, Debug)]
+pub struct Summary<'a> {
+    report:        &'a TestReport,
+    slowest_count: usize,
+}
+
+impl<'a> Display for Summary<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let totals = self.report.totals();
+        let duration: Duration = self.report.suites.iter().map(|suite| suite.duration).sum();
+        writeln!(
+            f,
+            "Test results: {} tests in {} suites, {} passed, {} failed, {} skipped ({:.1}s).",
+            totals.total(),
+            self.report.suites.len(),
+            totals.passed,
+            totals.failed,
+            totals.skipped,
+            duration.as_secs_f64()
+        )?;
+        let slowest = self.report.slowest(self.slowest_count);
+        if !slowest.is_empty() {
+            writeln!(f, "Slowest tests:")?;
+            for (suite, case) in slowest {
+                let seconds = case.duration.as_secs_f64();
+                writeln!(f, "  {seconds:>8.2}s  {} / {}", suite.name, case.name)?;
+            }
+        }
+        let flaky = self.report.flaky().collect_vec();
+        if !flaky.is_empty() {
+            writeln!(f, "Flaky tests (passed only when rerun):")?;
+            for (suite, case) in flaky {
+                writeln!(f, "  {} / {}", suite.name, case.name)?;
+            }
+        }
+        if totals.failed > 0 {
+            writeln!(f, "Failures:")?;
+            for (suite, case) in self.report.failures() {
+                writeln!(f, "  {} / {}", suite.name, case.name)?;
+                if let Some(failure) = case.outcome.failure() {
+                    let header = failure.kind.iter().chain(&failure.message).join(": ");
+                    if !header.is_empty() {
+                        writeln!(f, "    {header}")?;
+                    }
+                    let details = failure.details.as_deref().unwrap_or_default();
+                    for line in details.lines().take(FAILURE_DETAILS_LINES) {
+                        writeln!(f, "      {line}")?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(name: &str, secs: u64, outcome: Outcome) -> TestCase {
+        TestCase {
+            name: name.into(),
+            class_name: None,
+            duration: Duration::from_secs(secs),
+            outcome,
+            flaky: false,
+        }
+    }
+
+    #[test]
+    fn summarizing() -> Result {
+        let failure = Failure { message: Some("1 != 2".into()), ..default() };
+        let report = TestReport {
+            suites: vec![TestSuite {
+                name:     "Table_Tests".into(),
+                duration: Duration::from_secs(6),
+                cases:    vec![
+                    case("fast", 1, Outcome::Passed),
+                    case("slow", 3, Outcome::Failed(failure)),
+                    case("pending", 0, Outcome::Skipped { message: None }),
+                ],
+            }],
+        };
+        assert_eq!(report.totals(), Totals { passed: 1, failed: 1, skipped: 1 });
+        assert_eq!(report.failed_suites(), BTreeSet::from(["Table_Tests"]));
+        let summary = report.summary(1).to_string();
+        assert!(summary.contains("3 tests in 1 suites, 1 passed, 1 failed, 1 skipped"));
+        assert!(summary.contains("3.00s  Table_Tests / slow"));
+        assert!(summary.contains("    1 != 2"));
+
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(json["suites"][0]["cases"][1]["status"], "failed");
+        assert_eq!(json["suites"][0]["cases"][1]["duration"], 3.0);
+        assert_eq!(serde_json::from_value::<TestReport>(json)?, report);
+        Ok(())
+    }
+
+    #[test]
+    fn rerunning() {
+        let suite = |cases| TestSuite { name: "Table_Tests".into(), duration: default(), cases };
+        let failed = || Outcome::Failed(default());
+        let mut report = TestReport {
+            suites: vec![suite(vec![
+                case("stable", 1, Outcome::Passed),
+                case("flaky", 1, failed()),
+                case("broken", 1, failed()),
+            ])],
+        };
+        report.apply_rerun(TestReport {
+            suites: vec![suite(vec![
+                case("flaky", 1, Outcome::Passed),
+                case("broken", 1, failed()),
+            ])],
+        });
+        assert_eq!(report.suites.len(), 1);
+        let flaky = report.flaky().map(|(_, case)| case.name.as_str()).collect_vec();
+        assert_eq!(flaky, ["flaky"]);
+        assert_eq!(report.totals(), Totals { passed: 2, failed: 1, skipped: 0 });
+        assert!(report
+            .summary(0)
+            .to_string()
+            .contains("passed only when rerun):\n  Table_Tests / flaky"));
+    }
+}
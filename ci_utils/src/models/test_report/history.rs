@@ -0,0 +1,127 @@
+//! Local record of the tests that turned out to be flaky, so the repeated offenders stand out.
+
+use crate::prelude::*;
+
+use crate::models::test_report::TestReport;
+use chrono::DateTime;
+use chrono::Utc;
+
+
+
+/// How many times a test must have been flaky to be considered a repeated offender.
+pub const REPEATED_OFFENDER_THRESHOLD: usize = 2;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// In how many test runs the test was flaky.
+    pub occurrences: usize,
+    pub last_seen:   DateTime<Utc>,
+}
+
+/// Flaky tests seen so far, keyed by `suite / test` names.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlakinessHistory {
+    pub tests: BTreeMap<String, Entry>,
+}
+
+impl FlakinessHistory {
+    /// Read the history file. If it does not exist yet, the history is empty.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            path.read_to_json()
+        } else {
+            Ok(default())
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result {
+        crate::fs::create_parent_dir_if_missing(&path)?;
+        path.write_as_json(self)
+    }
+
+    /// Note all the flaky tests from the given report.
+    pub fn record(&mut self, report: &TestReport, now: DateTime<Utc>) {
+        for (suite, case) in report.flaky() {
+            let key = format!("{} / {}", suite.name, case.name);
+            let entry = self.tests.entry(key).or_insert(Entry { occurrences: 0, last_seen: now });
+            entry.occurrences += 1;
+            entry.last_seen = now;
+        }
+    }
+
+    /// Tests that have been flaky at least [`REPEATED_OFFENDER_THRESHOLD`] times, the most
+    /// frequent first.
+    pub fn repeated_offenders(&self) -> Vec<(&str, &Entry)> {
+        let mut ret = self
+            .tests
+            .iter()
+            .filter(|(_, entry)| entry.occurrences >= REPEATED_OFFENDER_THRESHOLD)
+            .map(|(name, entry)| (name.as_str(), entry))
+            .collect_vec();
+        ret.sort_by(|(_, a), (_, b)| b.occurrences.cmp(&a.occurrences));
+        ret
+    }
+
+    /// Load the history file, record the flaky tests of the report and save it back. Returns the
+    /// updated history.
+    pub fn update_file(path: impl AsRef<Path>, report: &TestReport) -> Result<Self> {
+        let mut history = Self::load(&path)?;
+        history.record(report, Utc::now());
+        history.save(&path)?;
+        Ok(history)
+    }
+}
+
+impl Display for FlakinessHistory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let offenders = self.repeated_offenders();
+        if !offenders.is_empty() {
+            writeln!(f, "Repeatedly flaky tests:")?;
+            for (name, entry) in offenders {
+                let last_seen = entry.last_seen.format("%Y-%m-%d");
+                writeln!(f, "  {:>4}x  {name} (last seen {last_seen})", entry.occurrences)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_report::Outcome;
+    use crate::models::test_report::TestCase;
+    use crate::models::test_report::TestSuite;
+
+    #[test]
+    fn recording() -> Result {
+        let case = |name: &str, flaky| TestCase {
+            name: name.into(),
+            class_name: None,
+            duration: default(),
+            outcome: Outcome::Passed,
+            flaky,
+        };
+        let report = TestReport {
+            suites: vec![TestSuite {
+                name:     "Database".into(),
+                duration: default(),
+                cases:    vec![case("connects", true), case("queries", false)],
+            }],
+        };
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("flakiness.json");
+        let history = FlakinessHistory::update_file(&path, &report)?;
+        assert_eq!(history.tests["Database / connects"].occurrences, 1);
+        assert!(history.repeated_offenders().is_empty());
+        assert!(history.to_string().is_empty());
+
+        let history = FlakinessHistory::update_file(&path, &report)?;
+        assert_eq!(history.tests.len(), 1);
+        assert_eq!(history.repeated_offenders()[0].0, "Database / connects");
+        assert!(history.to_string().contains("2x  Database / connects"));
+        Ok(())
+    }
+}
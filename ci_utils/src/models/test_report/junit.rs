@@ -0,0 +1,153 @@
+//! Parser of the JUnit XML reports, as written by the sbt and the Enso test runner.
+//!
+//! The root element is either a single `testsuite` or `testsuites` with any number of (possibly
+//! nested) suites.
+
+use crate::prelude::*;
+
+use crate::models::test_report::Failure;
+use crate::models::test_report::Outcome;
+use crate::models::test_report::TestCase;
+use crate::models::test_report::TestSuite;
+use roxmltree::Node;
+use std::time::Duration;
+
+
+
+pub fn parse_file(path: impl AsRef<Path>) -> Result<Vec<TestSuite>> {
+    let path = path.as_ref();
+    let text = crate::fs::read_to_string(path)?;
+    parse(&text).with_context(|| format!("Failed to parse JUnit report {}.", path.display()))
+}
+
+pub fn parse(text: &str) -> Result<Vec<TestSuite>> {
+    let document = roxmltree::Document::parse(text)?;
+    let root = document.root_element();
+    let mut ret = Vec::new();
+    match root.tag_name().name() {
+        "testsuite" | "testsuites" => collect_suites(root, &mut ret)?,
+        other => bail!("Expected `testsuite` or `testsuites` root element, found `{other}`."),
+    }
+    Ok(ret)
+}
+
+fn collect_suites(node: Node, suites: &mut Vec<TestSuite>) -> Result {
+    if node.has_tag_name("testsuite") {
+        suites.push(parse_suite(node)?);
+    }
+    for child in node.children().filter(|child| child.has_tag_name("testsuite")) {
+        collect_suites(child, suites)?;
+    }
+    Ok(())
+}
+
+fn parse_suite(node: Node) -> Result<TestSuite> {
+    let cases = node
+        .children()
+        .filter(|child| child.has_tag_name("testcase"))
+        .map(parse_case)
+        .collect::<Result<Vec<_>>>()?;
+    let duration = match duration(node)? {
+        Some(duration) => duration,
+        None => cases.iter().map(|case| case.duration).sum(),
+    };
+    let name = node.attribute("name").unwrap_or_default().to_string();
+    Ok(TestSuite { name, duration, cases })
+}
+
+fn parse_case(node: Node) -> Result<TestCase> {
+    let name = node.attribute("name").context("Test case without a name.")?.to_string();
+    let failure = |node: Node| Failure {
+        message: node.attribute("message").map(ToString::to_string),
+        kind:    node.attribute("type").map(ToString::to_string),
+        details: node.text().map(str::trim).filter(|text| !text.is_empty()).map(Into::into),
+    };
+    let mut outcome = Outcome::Passed;
+    for child in node.children() {
+        match child.tag_name().name() {
+            "failure" => outcome = Outcome::Failed(failure(child)),
+            "error" => outcome = Outcome::Errored(failure(child)),
+            "skipped" => {
+                let message = child.attribute("message").map(ToString::to_string);
+                outcome = Outcome::Skipped { message };
+            }
+            _ => {}
+        }
+    }
+    Ok(TestCase {
+        name,
+        class_name: node.attribute("classname").map(ToString::to_string),
+        duration: duration(node)?.unwrap_or_default(),
+        outcome,
+        flaky: false,
+    })
+}
+
+/// Parse the `time` attribute, given in seconds.
+fn duration(node: Node) -> Result<Option<Duration>> {
+    node.attribute("time")
+        .map(|time| {
+            // Some reporters use thousands separators, e.g. `1,234.5`.
+            let secs = time.replace(',', "").parse::<f64>()?;
+            ensure!(secs.is_finite() && secs >= 0.0, "Invalid test duration: {time}.");
+            Ok(Duration::from_secs_f64(secs))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="Table.Aggregate" tests="4" failures="1" errors="1" skipped="1" time="2.5">
+    <testcase name="should sum" classname="Table.Aggregate" time="1.25"/>
+    <testcase name="should count" classname="Table.Aggregate" time="0.75">
+      <failure message="Expected 3, got 2" type="Assertion_Error">
+        at Table.Aggregate.count
+      </failure>
+    </testcase>
+    <testcase name="should group" time="0.5">
+      <error message="Panic" type="Illegal_State"/>
+    </testcase>
+    <testcase name="should pivot">
+      <skipped message="Not implemented."/>
+    </testcase>
+    <system-out><![CDATA[Some output.]]></system-out>
+  </testsuite>
+  <testsuite name="Table.Join">
+    <testcase name="should join" time="1,000.5"/>
+  </testsuite>
+</testsuites>
+"#;
+
+    #[test]
+    fn parsing() -> Result {
+        let suites = parse(REPORT)?;
+        assert_eq!(suites.len(), 2);
+        let aggregate = &suites[0];
+        assert_eq!(aggregate.duration, Duration::from_millis(2500));
+        assert_eq!(aggregate.cases.len(), 4);
+        assert_eq!(aggregate.cases[0].outcome, Outcome::Passed);
+        assert_eq!(
+            aggregate.cases[1].outcome,
+            Outcome::Failed(Failure {
+                message: Some("Expected 3, got 2".into()),
+                kind:    Some("Assertion_Error".into()),
+                details: Some("at Table.Aggregate.count".into()),
+            })
+        );
+        assert!(matches!(aggregate.cases[2].outcome, Outcome::Errored(_)));
+        assert_eq!(aggregate.cases[3].outcome, Outcome::Skipped {
+            message: Some("Not implemented.".into()),
+        });
+        // The suite duration is summed from the cases, if not given.
+        assert_eq!(suites[1].duration, Duration::from_millis(1_000_500));
+
+        let single = parse(r#"<testsuite name="Lone"><testcase name="case"/></testsuite>"#)?;
+        assert_eq!(single[0].name, "Lone");
+        assert!(parse("<html/>").is_err());
+        Ok(())
+    }
+}
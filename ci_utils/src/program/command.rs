@@ -241,6 +241,8 @@ pub trait CommandOption {
 pub struct Command {
     pub inner:          tokio::process::Command,
     pub status_checker: Arc<dyn Fn(ExitStatus) -> Result + Send + Sync>,
+    /// Prefix of the intercepted output lines. If not set, the program name is used.
+    pub log_prefix:     Option<String>,
 }
 
 impl Borrow<tokio::process::Command> for Command {
@@ -265,11 +267,17 @@ impl Command {
     pub fn new<S: AsRef<OsStr>>(program: S) -> Command {
         let inner = tokio::process::Command::new(program);
         let status_checker = Arc::new(|status: ExitStatus| status.exit_ok().anyhow_err());
-        Self { inner, status_checker }
+        Self { inner, status_checker, log_prefix: None }
     }
 
     pub fn new_over<P: Program + 'static>(inner: tokio::process::Command) -> Self {
-        Command { inner, status_checker: Arc::new(P::handle_exit_status) }
+        Command { inner, status_checker: Arc::new(P::handle_exit_status), log_prefix: None }
+    }
+
+    /// Set the prefix of the intercepted output lines, e.g. to tell apart concurrent runs.
+    pub fn log_prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+        self.log_prefix = Some(prefix.into());
+        self
     }
 
     pub fn spawn_intercepting(&mut self) -> Result<Child> {
@@ -277,9 +285,11 @@ impl Command {
         self.stderr(Stdio::piped());
 
         let pretty = self.describe();
-        let program = self.inner.as_std().get_program();
-        let program = Path::new(program).file_stem().unwrap_or_default().to_os_string();
-        let program = program.to_string_lossy();
+        let program = self.log_prefix.clone().unwrap_or_else(|| {
+            let program = self.inner.as_std().get_program();
+            let program = Path::new(program).file_stem().unwrap_or_default();
+            program.to_string_lossy().into()
+        });
         info!("Spawning child process: {}", pretty);
         let mut child = self.inner.spawn()?;
         if let Some(pid) = child.id() {
@@ -295,6 +305,7 @@ impl Command {
         let pretty = self.describe();
         let span = info_span!(
             "Running process.",
+            category = "process",
             status = tracing::field::Empty,
             pid = tracing::field::Empty,
             command = %self.describe()
@@ -19,6 +19,7 @@ pub mod rustup;
 pub mod sbt;
 pub mod seven_zip;
 pub mod sh;
+pub mod systemctl;
 pub mod tar;
 pub mod vs;
 pub mod vswhere;
@@ -39,4 +40,5 @@ pub use pwsh::PwSh;
 pub use sbt::Sbt;
 pub use seven_zip::SevenZip;
 pub use sh::Bash;
+pub use systemctl::Systemctl;
 pub use wasm_pack::WasmPack;
@@ -6,6 +6,15 @@ use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::process::Stdio;
 use std::str::FromStr;
+use tempfile::TempDir;
+use tokio::process::Child;
+
+pub mod service;
+
+crate::define_env_var! {
+    /// Enables BuildKit for the `docker build` command.
+    DOCKER_BUILDKIT, bool
+}
 
 #[derive(Clone, Debug, PartialEq, Ord, PartialOrd, Eq, Hash)]
 pub enum NetworkDriver {
@@ -66,6 +75,30 @@ pub struct NetworkInfo {
 
 
 
+/// Registry of the images that have no registry host in their reference.
+pub const DOCKER_HUB: &str = "docker.io";
+
+/// Credentials for logging into a Docker registry.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct Credentials {
+    /// Host of the registry, like `ghcr.io` or `localhost:5000`.
+    pub registry: String,
+    pub username: String,
+    #[derivative(Debug = "ignore")]
+    pub password: String,
+}
+
+/// The registry host of the image reference, like `ghcr.io` for `ghcr.io/enso-org/runtime:1.0`.
+///
+/// Returns `None` for the images from [Docker Hub](DOCKER_HUB), like `postgres:14` or
+/// `library/postgres`.
+pub fn registry_of(image: &str) -> Option<&str> {
+    let (first, _) = image.split_once('/')?;
+    let is_host = first.contains('.') || first.contains(':') || first == "localhost";
+    is_host.then(|| first)
+}
+
 pub struct Docker;
 
 impl Program for Docker {
@@ -76,21 +109,123 @@ impl Program for Docker {
 
 impl Docker {
     pub async fn build(&self, options: BuildOptions) -> Result<ImageId> {
+        // The build output differs between the classic builder and BuildKit, so the image ID is
+        // not parsed from it.
+        let id_file = tempfile::NamedTempFile::new()?;
         let mut command = self.cmd()?;
-        command.arg("build").args(options.args());
+        command.arg("build").args(options.args()).arg("--iidfile").arg(id_file.path());
+        if options.uses_buildkit() {
+            command.set_env(DOCKER_BUILDKIT, &true)?;
+        }
         debug!("{:?}", command);
-        let output = command.output_ok().await?;
-        let built_image_id = std::str::from_utf8(&output.stdout)?
-            .lines()
-            .inspect(|line| debug!("{}", line))
-            .filter(|line| !line.is_empty())
-            .last()
-            .ok_or_else(|| anyhow!("Docker provided no output"))?
-            .split(' ')
-            .last()
-            .ok_or_else(|| anyhow!("The last line has no space!"))?;
+        command.run_ok().await?;
+        let built_image_id = crate::fs::read_to_string(id_file.path())?.trim().to_string();
+        ensure!(!built_image_id.is_empty(), "Docker provided no image ID.");
         debug!("Image {} successfully built!", built_image_id);
-        Ok(ImageId(built_image_id.into()))
+        Ok(ImageId(built_image_id))
+    }
+
+    /// Create the `target` reference to the `source` image, like `ghcr.io/enso-org/runtime:1.0`.
+    pub async fn tag(&self, source: &ImageId, target: impl AsRef<str>) -> Result {
+        self.cmd()?.args(["tag", source.0.as_str(), target.as_ref()]).run_ok().await
+    }
+
+    /// Push the image to its registry, logging in first if the credentials are given.
+    pub async fn push(&self, image: impl AsRef<str>, credentials: Option<&Credentials>) -> Result {
+        let config = self.login_config(credentials).await?;
+        self.cmd_with_config(config.as_ref())?.args(["push", image.as_ref()]).run_ok().await
+    }
+
+    /// Pull the image from its registry, logging in first if the credentials are given.
+    pub async fn pull(&self, image: impl AsRef<str>, credentials: Option<&Credentials>) -> Result {
+        let config = self.login_config(credentials).await?;
+        self.cmd_with_config(config.as_ref())?.args(["pull", image.as_ref()]).run_ok().await
+    }
+
+    /// Tag the image with each of the tags in the repository and push them all.
+    ///
+    /// The repository includes the registry host, like `ghcr.io/enso-org/runtime`.
+    pub async fn tag_and_push(
+        &self,
+        image: &ImageId,
+        repository: &str,
+        tags: impl IntoIterator<Item = impl AsRef<str>>,
+        credentials: Option<&Credentials>,
+    ) -> Result<Vec<String>> {
+        if let Some(credentials) = credentials {
+            let registry = registry_of(repository).unwrap_or(DOCKER_HUB);
+            ensure!(
+                registry == credentials.registry,
+                "The credentials are for {}, while {repository} is in {registry}.",
+                credentials.registry,
+            );
+        }
+        let config = self.login_config(credentials).await?;
+        let mut ret = Vec::new();
+        for tag in tags {
+            let target = format!("{repository}:{}", tag.as_ref());
+            self.tag(image, &target).await?;
+            self.cmd_with_config(config.as_ref())?.args(["push", &target]).run_ok().await?;
+            ret.push(target);
+        }
+        Ok(ret)
+    }
+
+    /// Log into the registry using a temporary configuration directory, so the credentials are
+    /// not stored in the user's configuration. The directory must be kept for the authenticated
+    /// commands, see [`Docker::cmd_with_config`].
+    pub async fn login_config(&self, credentials: Option<&Credentials>) -> Result<Option<TempDir>> {
+        let credentials = match credentials {
+            Some(credentials) => credentials,
+            None => return Ok(None),
+        };
+        let config = tempfile::tempdir()?;
+        let mut command = self.cmd_with_config(Some(&config))?;
+        command
+            .args(["login", "--username", credentials.username.as_str(), "--password-stdin"])
+            .arg(&credentials.registry)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = command.spawn()?;
+        let mut stdin = child.stdin.take().context("Failed to get the login standard input.")?;
+        stdin.write_all(credentials.password.as_bytes()).await?;
+        drop(stdin);
+        let output = child.wait_with_output().await?;
+        ensure!(
+            output.status.success(),
+            "Failed to log into {}: {}",
+            credentials.registry,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        Ok(Some(config))
+    }
+
+    /// Command using the given configuration directory, e.g. one obtained from
+    /// [`Docker::login_config`]. If not given, the default configuration is used.
+    pub fn cmd_with_config(&self, config: Option<&TempDir>) -> Result<Command> {
+        let mut command = self.cmd()?;
+        if let Some(config) = config {
+            command.arg("--config").arg(config.path());
+        }
+        Ok(command)
+    }
+
+    pub async fn inspect_image(&self, image: impl AsRef<str>) -> Result<ImageInfo> {
+        self.inspect("image", image.as_ref()).await
+    }
+
+    pub async fn inspect_container(&self, container: &ContainerId) -> Result<ContainerInfo> {
+        self.inspect("container", container.as_str()).await
+    }
+
+    async fn inspect<T: DeserializeOwned>(&self, kind: &str, name: &str) -> Result<T> {
+        let output =
+            self.cmd()?.arg("inspect").arg(format!("--type={kind}")).arg(name).run_stdout().await?;
+        let mut infos = serde_json::from_str::<Vec<T>>(&output)
+            .with_context(|| format!("Failed to parse the description of {kind} {name}."))?;
+        ensure!(infos.len() == 1, "Expected a single {kind} named {name}, got {}.", infos.len());
+        Ok(infos.remove(0))
     }
 
     pub fn run_cmd(&self, options: &RunOptions) -> Result<Command> {
@@ -121,6 +256,69 @@ impl Docker {
         // output.status.exit_ok()?;
     }
 
+    /// Both standard output and error of the container.
+    pub async fn logs(&self, container: &ContainerId) -> Result<String> {
+        let output = self.cmd()?.args(["logs", container.as_str()]).output_ok().await?;
+        let mut ret = String::from_utf8_lossy(&output.stdout).into_owned();
+        ret.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(ret)
+    }
+
+    /// Follow the container's output, logging it until the container stops. Dropping the returned
+    /// process stops following.
+    pub fn follow_logs(&self, container: &ContainerId) -> Result<Child> {
+        self.cmd()?
+            .args(["logs", "--follow", container.as_str()])
+            .log_prefix(container.as_str())
+            .kill_on_drop(true)
+            .spawn_intercepting()
+    }
+
+    /// Wait until the container stops and return its exit code.
+    pub async fn wait(&self, container: &ContainerId) -> Result<i32> {
+        let output = self.cmd()?.args(["wait", container.as_str()]).output_ok().await?;
+        let code = output.single_line_stdout()?;
+        code.parse().with_context(|| format!("Invalid exit code of {container}: {code}"))
+    }
+
+    pub async fn is_running(&self, container: &ContainerId) -> Result<bool> {
+        let format = "--format={{.State.Running}}";
+        let output = self.cmd()?.args(["inspect", format, container.as_str()]).output_ok().await?;
+        Ok(output.single_line_stdout()? == "true")
+    }
+
+    /// All the containers, including the stopped ones, with the given label value.
+    pub async fn list_containers_labeled(
+        &self,
+        name: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<Vec<ContainerId>> {
+        let filter = format!("--filter=label={}={}", name.as_ref(), value.as_ref());
+        let output = self.cmd()?.args(["ps", "--all", "--quiet", &filter]).output_ok().await?;
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|id| ContainerId(id.into()))
+            .collect())
+    }
+
+    /// Run the command in the running container, with the additional environment variables.
+    pub fn exec_cmd<S: AsRef<OsStr>>(
+        &self,
+        container: &ContainerId,
+        env: &BTreeMap<String, String>,
+        command: impl IntoIterator<Item = S>,
+    ) -> Result<Command> {
+        let mut cmd = self.cmd()?;
+        cmd.arg("exec");
+        for (name, value) in env {
+            cmd.arg("--env").arg(format!("{name}={value}"));
+        }
+        cmd.arg(container.as_str()).args(command);
+        Ok(cmd)
+    }
+
     pub async fn kill(&self, target: impl AsRef<str>) -> Result {
         Docker.call_args(["kill", target.as_ref()]).await
     }
@@ -208,24 +406,36 @@ impl Docker {
 
 #[derive(Clone, Debug)]
 pub struct BuildOptions {
-    pub context:    PathBuf,
-    pub target:     Option<OsString>,
-    pub tags:       Vec<String>,
-    pub build_args: HashMap<String, Option<String>>,
-    pub file:       Option<PathBuf>,
+    pub context:      PathBuf,
+    pub target:       Option<OsString>,
+    pub tags:         Vec<String>,
+    pub build_args:   HashMap<String, Option<String>>,
+    pub file:         Option<PathBuf>,
+    /// Images whose BuildKit cache is imported, e.g. the previously pushed builds.
+    pub cache_from:   Vec<String>,
+    /// Export the BuildKit cache by embedding it into the built image, so the image can be used
+    /// in `cache_from` once pushed.
+    pub inline_cache: bool,
 }
 
 impl BuildOptions {
     pub fn new(context_path: impl Into<PathBuf>) -> Self {
         Self {
-            context:    context_path.into(),
-            target:     default(),
-            tags:       default(),
-            build_args: default(),
-            file:       default(),
+            context:      context_path.into(),
+            target:       default(),
+            tags:         default(),
+            build_args:   default(),
+            file:         default(),
+            cache_from:   default(),
+            inline_cache: default(),
         }
     }
 
+    /// The cache options are supported only by BuildKit.
+    pub fn uses_buildkit(&self) -> bool {
+        self.inline_cache || !self.cache_from.is_empty()
+    }
+
     pub fn add_build_arg_from_env_or<R>(
         &mut self,
         name: impl AsRef<str>,
@@ -269,10 +479,100 @@ impl BuildOptions {
             // C:\Users\mwu\AppData\Local\Temp\2\.tmpOykTop`
             ret.push(file.without_verbatim_prefix().into());
         }
+        for image in &self.cache_from {
+            ret.push("--cache-from".into());
+            ret.push(image.into());
+        }
+        if self.inline_cache {
+            ret.push("--build-arg".into());
+            ret.push("BUILDKIT_INLINE_CACHE=1".into());
+        }
         ret
     }
 }
 
+/// Description of an image, as given by `docker inspect`. Only the commonly used fields are
+/// included.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageInfo {
+    pub id:           String,
+    #[serde(default)]
+    pub repo_tags:    Vec<String>,
+    /// References by digest of the pushed image, like `ghcr.io/enso-org/runtime@sha256:…`.
+    #[serde(default)]
+    pub repo_digests: Vec<String>,
+    pub created:      String,
+    pub size:         u64,
+    pub architecture: String,
+    pub os:           String,
+    pub config:       ImageConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageConfig {
+    /// Environment variables, like `PATH=/usr/bin`.
+    #[serde(default)]
+    pub env:         Option<Vec<String>>,
+    #[serde(default)]
+    pub cmd:         Option<Vec<String>>,
+    #[serde(default)]
+    pub entrypoint:  Option<Vec<String>>,
+    #[serde(default)]
+    pub working_dir: String,
+    #[serde(default)]
+    pub labels:      Option<HashMap<String, String>>,
+}
+
+/// Description of a container, as given by `docker inspect`. Only the commonly used fields are
+/// included.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerInfo {
+    pub id:     String,
+    /// Name of the container, with a leading slash.
+    pub name:   String,
+    /// ID of the container's image.
+    pub image:  String,
+    pub state:  ContainerState,
+    pub config: ContainerConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerState {
+    /// One of `created`, `running`, `paused`, `restarting`, `removing`, `exited` or `dead`.
+    pub status:      String,
+    pub running:     bool,
+    pub exit_code:   i32,
+    pub started_at:  String,
+    pub finished_at: String,
+    /// Present only if the image or the container defines a healthcheck.
+    #[serde(default)]
+    pub health:      Option<ContainerHealth>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerHealth {
+    /// One of `starting`, `healthy` or `unhealthy`.
+    pub status:         String,
+    /// How many of the latest checks have failed in a row.
+    pub failing_streak: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerConfig {
+    /// The image name the container was created from.
+    pub image:  String,
+    #[serde(default)]
+    pub env:    Option<Vec<String>>,
+    #[serde(default)]
+    pub labels: Option<HashMap<String, String>>,
+}
+
 /// Using the --restart flag on Docker run you can specify a restart policy for how a container
 /// should or should not be restarted on exit.
 #[derive(Clone, Copy, Debug)]
@@ -346,6 +646,8 @@ pub struct RunOptions {
     pub storage_size_gb:   Option<usize>,
     /// Proxy all received signals to the process (non-TTY mode only).
     pub sig_proxy:         Option<bool>,
+    /// Metadata of the container, allowing to find it later.
+    pub labels:            HashMap<String, String>,
 }
 
 impl RunOptions {
@@ -362,6 +664,7 @@ impl RunOptions {
             network: default(),
             storage_size_gb: default(),
             sig_proxy: default(),
+            labels: default(),
         }
     }
 
@@ -392,6 +695,11 @@ impl RunOptions {
         self
     }
 
+    pub fn label(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.labels.insert(name.into(), value.into());
+        self
+    }
+
     pub fn publish_port(&mut self, host_port: u16, container_port: u16) -> &mut Self {
         self.ports.insert(host_port, container_port);
         self
@@ -448,6 +756,11 @@ impl RunOptions {
             ret.push(arg.into());
         }
 
+        for (name, value) in &self.labels {
+            ret.push("--label".into());
+            ret.push(format!("{name}={value}").into());
+        }
+
         ret.push(OsString::from(&self.image.0));
 
         ret.extend(self.command.clone());
@@ -489,4 +802,87 @@ mod tests {
         dbg!(Docker.build(opts).await?);
         Ok(())
     }
+
+    #[test]
+    fn registry_host() {
+        assert_eq!(registry_of("ghcr.io/enso-org/runtime:1.0"), Some("ghcr.io"));
+        assert_eq!(registry_of("localhost:5000/runtime"), Some("localhost:5000"));
+        assert_eq!(registry_of("localhost/runtime"), Some("localhost"));
+        assert_eq!(registry_of("enso-org/runtime"), None);
+        assert_eq!(registry_of("postgres:14"), None);
+    }
+
+    #[test]
+    fn build_cache_args() {
+        let mut opts = BuildOptions::new("context");
+        assert!(!opts.uses_buildkit());
+        opts.cache_from.push("ghcr.io/enso-org/runtime:latest".into());
+        opts.inline_cache = true;
+        assert!(opts.uses_buildkit());
+        let args = opts.args().iter().map(|arg| arg.to_string_lossy()).join(" ");
+        assert_eq!(
+            args,
+            "context --cache-from ghcr.io/enso-org/runtime:latest --build-arg \
+             BUILDKIT_INLINE_CACHE=1"
+        );
+    }
+
+    /// Push an image to a local registry requiring a login and pull it back.
+    ///
+    /// The `registry:2` image is Linux-only, so Docker must run Linux containers.
+    #[tokio::test]
+    #[cfg(target_os = "linux")]
+    async fn push_to_local_registry() -> Result {
+        use regex::Regex;
+        use service::Endpoint;
+        use service::Readiness;
+        use service::Service;
+
+        // The bcrypt hash of `test-password`, as written by `htpasswd -nbB`.
+        const HTPASSWD: &str = "user:$2b$05$UX91aW7oyO6oVDF5WLgLx.ZX.S6g8uMk9BRYqRYNUpkDoPiu8pugS";
+        let script = format!(
+            "mkdir -p /auth && echo '{HTPASSWD}' > /auth/htpasswd \
+             && registry serve /etc/docker/registry/config.yml"
+        );
+        let endpoint = Endpoint::Host { port: crate::get_free_port()? };
+        // Without credentials, the `/v2/` endpoint responds with 401, so it can't be polled.
+        let readiness = Readiness::LogLine(Regex::new("listening on")?);
+        let registry = Service::new("test-registry", "registry:2", endpoint, 5000, readiness)
+            .env("REGISTRY_AUTH", "htpasswd")
+            .env("REGISTRY_AUTH_HTPASSWD_REALM", "test")
+            .env("REGISTRY_AUTH_HTPASSWD_PATH", "/auth/htpasswd")
+            .command(["sh", "-c", script.as_str()]);
+        let registry = registry.start().await?;
+        let credentials = Credentials {
+            registry: registry.host(),
+            username: "user".into(),
+            password: "test-password".into(),
+        };
+
+        let context = tempfile::tempdir()?;
+        crate::fs::write(context.path().join("Dockerfile"), "FROM scratch\nCOPY data /\n")?;
+        crate::fs::write(context.path().join("data"), "data")?;
+        let mut opts = BuildOptions::new(context.path());
+        opts.inline_cache = true;
+        let image = Docker.build(opts).await?;
+
+        let repository = format!("{}/test/image", registry.host());
+        assert!(Docker.tag_and_push(&image, &repository, ["1.0"], None).await.is_err());
+        let wrong = Credentials { password: "wrong".into(), ..credentials.clone() };
+        assert!(Docker.tag_and_push(&image, &repository, ["1.0"], Some(&wrong)).await.is_err());
+        let other = Credentials { registry: "ghcr.io".into(), ..credentials.clone() };
+        assert!(Docker.tag_and_push(&image, &repository, ["1.0"], Some(&other)).await.is_err());
+
+        let tags = ["1.0", "latest"];
+        let pushed = Docker.tag_and_push(&image, &repository, tags, Some(&credentials)).await?;
+        assert_eq!(pushed, [format!("{repository}:1.0"), format!("{repository}:latest")]);
+        let info = Docker.inspect_image(&pushed[0]).await?;
+        assert!(info.repo_digests.iter().any(|digest| digest.starts_with(&repository)));
+
+        Docker.cmd()?.args(["image", "rm", "--force", image.0.as_str()]).run_ok().await?;
+        assert!(Docker.pull(&pushed[1], None).await.is_err());
+        Docker.pull(&pushed[1], Some(&credentials)).await?;
+        assert_eq!(Docker.inspect_image(&pushed[1]).await?.id, image.0);
+        registry.stop().await
+    }
 }
@@ -0,0 +1,402 @@
+//! Services run in Docker containers for the duration of the tests, like databases.
+//!
+//! A [`Service`] describes the container and how to tell that it is ready. Starting it yields a
+//! [`Running`] guard that removes the container once it is no longer needed.
+
+use crate::prelude::*;
+
+use crate::programs::docker::ContainerId;
+use crate::programs::docker::ImageId;
+use crate::programs::docker::Network;
+use crate::programs::docker::RunOptions;
+use crate::programs::Docker;
+
+use regex::Regex;
+use std::time::Duration;
+
+
+
+/// How often the readiness probe is repeated.
+pub const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default limit on how long the service can take to become ready.
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Label of the service containers, with the identifier of their owner as the value.
+pub const OWNER_LABEL: &str = "org.enso.build.test-service-owner";
+
+/// Remove the containers of the given owner, left behind by the runs that have crashed.
+///
+/// Must not be called while the owner's services are in use.
+pub async fn remove_orphans(owner: &str) -> Result {
+    let orphans = Docker.list_containers_labeled(OWNER_LABEL, owner).await?;
+    for container in orphans {
+        info!("Removing the orphaned test service container {container}.");
+        Docker.remove_container(&container, true).await?;
+    }
+    Ok(())
+}
+
+/// Owner identifier for the services started on this machine for the given directory.
+///
+/// It stays the same across the runs, so the containers of a crashed run are found by the next
+/// one, see [`remove_orphans`]. It is also valid in the container names.
+pub fn local_owner(directory: &Path) -> String {
+    use sha2::Digest;
+    let hostname = whoami::hostname()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect::<String>();
+    let digest = sha2::Sha224::digest(directory.as_str().as_bytes());
+    format!("{hostname}-{}", data_encoding::HEXLOWER.encode(&digest[..6]))
+}
+
+/// How to tell that the service is ready to be used.
+#[derive(Clone, Debug)]
+pub enum Readiness {
+    /// A TCP connection to the service's port can be established.
+    ///
+    /// Note that with the port published on the host, Docker accepts the connections even before
+    /// the service is listening. Prefer the other probes in such case.
+    TcpConnect,
+    /// An HTTP GET request for the given path returns a success status.
+    HttpGet { path: String },
+    /// The container has printed a line matching the pattern, to either standard output or error.
+    LogLine(Regex),
+    /// The command, run within the container with the additional environment, succeeds. Useful for
+    /// the service's own clients, which can check that the service actually accepts requests.
+    Exec { command: Vec<String>, env: BTreeMap<String, String> },
+    /// The image's healthcheck reports the container as healthy.
+    Healthy,
+}
+
+/// Where the service is reachable from.
+#[derive(Clone, Debug)]
+pub enum Endpoint {
+    /// The service's port is published on the host, so it is available on `localhost`.
+    Host { port: u16 },
+    /// The service joins the network of the owning container, i.e. the one we are running in.
+    ///
+    /// As the network may be shared with other services, the service must be configured to
+    /// listen on the given port (e.g. through its command) rather than on its default one.
+    Container { owner: ContainerId, port: u16 },
+}
+
+impl Endpoint {
+    /// Endpoint for a service run by the given container (if we run in one) or the host.
+    ///
+    /// The preferred port is used if it is free, so the service is available where it is usually
+    /// expected. Pass `None` to always use a random free port, e.g. to run multiple instances.
+    pub fn deduce(owner: Option<ContainerId>, preferred_port: Option<u16>) -> Result<Self> {
+        let port = match preferred_port {
+            Some(port) if port_check::is_local_port_free(port) => port,
+            _ => crate::get_free_port()?,
+        };
+        Ok(match owner {
+            Some(owner) => {
+                debug!("Assuming that I am in the Docker container named {owner}.");
+                Self::Container { owner, port }
+            }
+            None => Self::Host { port },
+        })
+    }
+
+    /// The port on which the service is available.
+    pub fn port(&self) -> u16 {
+        match self {
+            Self::Host { port } | Self::Container { port, .. } => *port,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Service {
+    /// Name of the container. A leftover container with the same name is removed on start.
+    pub name:            String,
+    pub image:           ImageId,
+    pub env:             BTreeMap<String, String>,
+    /// Overrides the image's default command, if not empty.
+    pub command:         Vec<String>,
+    pub endpoint:        Endpoint,
+    /// The port the service listens on within its container, published as the endpoint's port.
+    pub container_port:  u16,
+    pub readiness:       Readiness,
+    pub startup_timeout: Duration,
+    /// Identifies who started the service, see [`remove_orphans`].
+    pub owner:           Option<String>,
+}
+
+impl Service {
+    pub fn new(
+        name: impl Into<String>,
+        image: impl Into<String>,
+        endpoint: Endpoint,
+        container_port: u16,
+        readiness: Readiness,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            image: ImageId(image.into()),
+            env: default(),
+            command: default(),
+            endpoint,
+            container_port,
+            readiness,
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            owner: default(),
+        }
+    }
+
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn command<S: Into<String>>(mut self, command: impl IntoIterator<Item = S>) -> Self {
+        self.command = command.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn owned_by(mut self, owner: impl Into<String>) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    pub fn container_id(&self) -> ContainerId {
+        ContainerId(self.name.clone())
+    }
+
+    pub fn run_options(&self) -> RunOptions {
+        let mut options = RunOptions::new(self.image.clone());
+        options.name = Some(self.name.clone());
+        for (name, value) in &self.env {
+            options.env_raw(name, value);
+        }
+        options.command = self.command.iter().map(Into::into).collect();
+        if let Some(owner) = &self.owner {
+            options.label(OWNER_LABEL, owner);
+        }
+        match &self.endpoint {
+            Endpoint::Host { port } => {
+                options.publish_port(*port, self.container_port);
+            }
+            Endpoint::Container { owner, .. } => {
+                options.network = Some(Network::Container(owner.clone()));
+            }
+        }
+        options
+    }
+
+    /// Start the container and wait until the service is ready.
+    ///
+    /// If it does not become ready, the container's logs are included in the error.
+    pub async fn start(self) -> Result<Running> {
+        // A container left behind by a crashed run would make the name collide.
+        let _ = Docker.remove_container(&self.container_id(), true).await;
+        debug!("Starting the {} service from {}.", self.name, self.image);
+        let container = Docker.run_detached(&self.run_options()).await?;
+        let running = Running { service: self, container, stopped: false };
+        let timeout = running.service.startup_timeout;
+        let outcome = tokio::time::timeout(timeout, running.wait_until_ready()).await;
+        let error = match outcome {
+            Ok(Ok(())) => return Ok(running),
+            Ok(Err(e)) => e,
+            Err(_) =>
+                anyhow!("The service did not become ready within {} seconds.", timeout.as_secs()),
+        };
+        let name = running.service.name.clone();
+        let logs = match running.logs().await {
+            Ok(logs) => logs,
+            Err(e) => format!("Failed to get the container logs: {e}"),
+        };
+        if let Err(e) = running.stop().await {
+            warn!("Failed to remove the container of the {name} service: {e}");
+        }
+        Err(error.context(format!("Failed to start the {name} service. Container logs:\n{logs}")))
+    }
+}
+
+/// A started service. The container is removed when the guard is stopped or dropped.
+#[derive(Debug)]
+pub struct Running {
+    pub service:   Service,
+    pub container: ContainerId,
+    stopped:       bool,
+}
+
+impl Running {
+    /// The port on which the service is available.
+    pub fn port(&self) -> u16 {
+        self.service.endpoint.port()
+    }
+
+    /// Address of the service, like `localhost:5432`.
+    pub fn host(&self) -> String {
+        format!("localhost:{}", self.port())
+    }
+
+    /// Both standard output and error of the container.
+    pub async fn logs(&self) -> Result<String> {
+        Docker.logs(&self.container).await
+    }
+
+    pub async fn is_ready(&self) -> Result<bool> {
+        Ok(match &self.service.readiness {
+            Readiness::TcpConnect =>
+                tokio::net::TcpStream::connect(("127.0.0.1", self.port())).await.is_ok(),
+            Readiness::HttpGet { path } => {
+                let url = format!("http://{}/{}", self.host(), path.trim_start_matches('/'));
+                let response = reqwest::get(url).await;
+                response.map_or(false, |response| response.status().is_success())
+            }
+            Readiness::LogLine(pattern) =>
+                self.logs().await?.lines().any(|line| pattern.is_match(line)),
+            Readiness::Exec { command, env } =>
+                Docker.exec_cmd(&self.container, env, command)?.output_ok().await.is_ok(),
+            Readiness::Healthy => {
+                let info = Docker.inspect_container(&self.container).await?;
+                let health = info.state.health.context("The image defines no healthcheck.")?;
+                health.status == "healthy"
+            }
+        })
+    }
+
+    pub async fn wait_until_ready(&self) -> Result {
+        loop {
+            if self.is_ready().await? {
+                debug!("The {} service is ready.", self.service.name);
+                return Ok(());
+            }
+            ensure!(
+                Docker.is_running(&self.container).await?,
+                "The container has exited before the service became ready."
+            );
+            tokio::time::sleep(READINESS_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Remove the container.
+    pub async fn stop(mut self) -> Result {
+        self.remove().await
+    }
+
+    /// Remove the container, unless it has been already removed.
+    pub async fn remove(&mut self) -> Result {
+        if !self.stopped {
+            debug!("Stopping the {} service.", self.service.name);
+            self.stopped = true;
+            Docker.remove_container(&self.container, true).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Running {
+    /// Fallback for the guards that were not stopped, e.g. because of an error.
+    ///
+    /// The removal is done by a background task, as we can't block the async runtime. If the
+    /// runtime shuts down before it completes, the container is left to [`remove_orphans`].
+    fn drop(&mut self) {
+        if self.stopped {
+            return;
+        }
+        let name = self.service.name.clone();
+        let container = self.container.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                debug!("Removing the container of the {name} service in the background.");
+                runtime.spawn(async move {
+                    if let Err(e) = Docker.remove_container(&container, true).await {
+                        warn!("Failed to remove the container of the {name} service: {e}");
+                    }
+                });
+            }
+            Err(_) => {
+                // Outside of the async runtime, we can just block.
+                let result = std::process::Command::new(Docker.executable_name())
+                    .args(["rm", "-f", container.as_str()])
+                    .output();
+                if let Err(e) = result {
+                    warn!("Failed to remove the container of the {name} service: {e}");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_options() {
+        let service = Service::new(
+            "db",
+            "postgres:14",
+            Endpoint::Host { port: 6543 },
+            5432,
+            Readiness::TcpConnect,
+        )
+        .env("POSTGRES_USER", "user");
+        let args = service.run_options().args();
+        let args = args.iter().map(|arg| arg.to_string_lossy()).join(" ");
+        assert_eq!(args, "--name db --env POSTGRES_USER=user -p 6543:5432 postgres:14");
+
+        let owned = service.clone().owned_by("runner-1");
+        let args = owned.run_options().args();
+        let args = args.iter().map(|arg| arg.to_string_lossy()).join(" ");
+        assert!(args.ends_with(&format!("--label {OWNER_LABEL}=runner-1 postgres:14")));
+
+        let owner = ContainerId("runner".into());
+        let service = Service { endpoint: Endpoint::Container { owner, port: 6543 }, ..service }
+            .command(["postgres", "-p", "6543"]);
+        let args = service.run_options().args();
+        let args = args.iter().map(|arg| arg.to_string_lossy()).join(" ");
+        assert_eq!(
+            args,
+            "--name db --env POSTGRES_USER=user --network=container:runner postgres:14 postgres -p \
+             6543"
+        );
+    }
+
+    #[test]
+    fn local_owners() {
+        let owner = local_owner(Path::new("/repo"));
+        assert_eq!(owner, local_owner(Path::new("/repo")));
+        assert_ne!(owner, local_owner(Path::new("/other-repo")));
+        assert!(owner.chars().all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c)));
+    }
+
+    #[test]
+    fn endpoint_deduction() -> Result {
+        let listener = std::net::TcpListener::bind("0.0.0.0:0")?;
+        let taken = listener.local_addr()?.port();
+        let endpoint = Endpoint::deduce(None, Some(taken))?;
+        assert!(matches!(endpoint, Endpoint::Host { port } if port != taken));
+        crate::release_port(endpoint.port());
+
+        let preferred = crate::get_free_port()?;
+        crate::release_port(preferred);
+        let endpoint = Endpoint::deduce(None, Some(preferred))?;
+        assert!(matches!(endpoint, Endpoint::Host { port } if port == preferred));
+
+        let owner = ContainerId("runner".into());
+        let endpoint = Endpoint::deduce(Some(owner.clone()), None)?;
+        assert!(
+            matches!(&endpoint, Endpoint::Container { owner: found, .. } if found.0 == owner.0)
+        );
+        crate::release_port(endpoint.port());
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn start_service() -> Result {
+        let endpoint = Endpoint::deduce(None, None)?;
+        let readiness = Readiness::HttpGet { path: "/".into() };
+        let service = Service::new("test-nginx", "nginx:alpine", endpoint, 80, readiness);
+        let running = service.start().await?;
+        println!("{}", running.logs().await?);
+        running.stop().await
+    }
+}
@@ -1,6 +1,7 @@
 use crate::prelude::*;
 
 use crate::programs::Program;
+use regex::Regex;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Go;
@@ -23,4 +24,27 @@ impl Program for Go {
         cmd.arg("version");
         Ok(cmd)
     }
+
+    /// Go versions are like `go1.18` or `go1.18.3`, the patch number is omitted for the first
+    /// release of a minor version.
+    fn parse_version(&self, version_text: &str) -> Result<Version> {
+        let regex = Regex::new(r"go(\d+)\.(\d+)(?:\.(\d+))?")?;
+        let captures = regex
+            .captures(version_text)
+            .with_context(|| format!("Failed to find the Go version in: {version_text}"))?;
+        let number = |index| captures.get(index).map_or(Ok(0), |m| m.as_str().parse2::<u64>());
+        Ok(Version::new(number(1)?, number(2)?, number(3)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version() -> Result {
+        assert_eq!(Go.parse_version("go version go1.18.3 linux/amd64")?, Version::new(1, 18, 3));
+        assert_eq!(Go.parse_version("go version go1.18 windows/amd64")?, Version::new(1, 18, 0));
+        Ok(())
+    }
 }
@@ -25,6 +25,16 @@ impl Program for Java {
     fn executable_name(&self) -> &'static str {
         "java"
     }
+
+    /// The GraalVM version for GraalVM's Java, otherwise the Java version.
+    fn parse_version(&self, version_text: &str) -> Result<Version> {
+        let line = version_text
+            .lines()
+            .find(|line| line.contains("GraalVM"))
+            .or_else(|| version_text.lines().next())
+            .unwrap_or_default();
+        crate::program::version::find_in_text(line)
+    }
 }
 
 #[cfg(test)]
@@ -35,6 +45,8 @@ mod tests {
     fn parse_version() {
         let contents = "openjdk 11.0.11 2021-04-20\nOpenJDK Runtime Environment GraalVM CE 21.1.0 (build 11.0.11+8-jvmci-21.1-b05)\nOpenJDK 64-Bit Server VM GraalVM CE 21.1.0 (build 11.0.11+8-jvmci-21.1-b05, mixed mode, sharing)";
         assert_eq!(Java.parse_version(contents).unwrap(), Version::new(21, 1, 0));
+        let contents = "openjdk 11.0.15 2022-04-19\nOpenJDK Runtime Environment (build 11.0.15+10)";
+        assert_eq!(Java.parse_version(contents).unwrap(), Version::new(11, 0, 15));
     }
 }
 
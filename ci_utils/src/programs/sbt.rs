@@ -1,5 +1,9 @@
 use crate::prelude::*;
 
+use crate::program::with_cwd::WithCwd;
+
+pub mod server;
+
 macro_rules! strong_string {
     ($name:ident($inner_ty:ty)) => {
         paste::paste! {
@@ -27,6 +31,14 @@ impl Program for Sbt {
     fn executable_name(&self) -> &'static str {
         "sbt"
     }
+
+    /// `--version` would boot sbt in the current directory. The version of the launcher script
+    /// does not need that.
+    fn version_command(&self) -> Result<Command> {
+        let mut cmd = self.cmd()?;
+        cmd.arg("--script-version");
+        Ok(cmd)
+    }
 }
 
 impl Sbt {
@@ -41,6 +53,47 @@ impl Sbt {
     }
 }
 
+/// Runs the sbt commands, either through a single sbt server or by spawning sbt for each of them.
+#[derive(Debug)]
+pub enum Session {
+    Server(server::Client),
+    Process(WithCwd<Sbt>),
+}
+
+impl Session {
+    /// Start the sbt server in the given project. If that fails, sbt will be spawned for each
+    /// command.
+    pub async fn start(root: impl AsRef<Path>) -> Self {
+        let root = root.as_ref();
+        match server::Client::start(root).await {
+            Ok(client) => Self::Server(client),
+            Err(e) => {
+                warn!("Failed to start the sbt server, falling back to separate processes: {e:?}");
+                Self::process(root)
+            }
+        }
+    }
+
+    pub fn process(root: impl AsRef<Path>) -> Self {
+        Self::Process(WithCwd::new(Sbt, root.as_ref()))
+    }
+
+    pub async fn call_arg(&mut self, command_line: impl AsRef<str>) -> Result {
+        match self {
+            Session::Server(client) => client.exec(command_line).await,
+            Session::Process(sbt) => sbt.call_arg(command_line.as_ref()).await,
+        }
+    }
+
+    /// Shut down the server, if there is one.
+    pub async fn finish(self) -> Result {
+        match self {
+            Session::Server(client) => client.shutdown().await,
+            Session::Process(_) => Ok(()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
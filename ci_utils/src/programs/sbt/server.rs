@@ -0,0 +1,333 @@
+//! Client of the sbt server.
+//!
+//! A single sbt instance is started in the server mode and the commands are sent to it, saving the
+//! JVM startup and the project loading for each of them. The protocol is JSON-RPC with LSP-style
+//! framing, over a Unix domain socket (or a named pipe on Windows), see
+//! https://www.scala-sbt.org/1.x/docs/sbt-server.html
+
+use crate::prelude::*;
+
+use crate::program::with_cwd::WithCwd;
+use crate::programs::Sbt;
+
+use serde_json::Value;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncBufRead;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::io::ReadHalf;
+use tokio::io::WriteHalf;
+use tokio::process::Child;
+
+
+
+/// How long to wait for the server to start, which includes loading the project.
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How long to wait for the server to exit after asking it to shut down, before killing it.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often to check whether the server has started.
+pub const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// File written by sbt with the URI of the server socket, relative to the project root.
+pub fn active_file(root: impl AsRef<Path>) -> PathBuf {
+    root.as_ref().join_iter(["project", "target", "active.json"])
+}
+
+/// Contents of the [`active_file`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Active {
+    pub uri: String,
+}
+
+impl Active {
+    /// The socket path (or the pipe name, on Windows) from the URI, like `local:///tmp/sbt.sock`.
+    pub fn socket(&self) -> Result<&str> {
+        let path = self.uri.strip_prefix("local:");
+        let path = path.with_context(|| format!("Unsupported sbt server URI: {}.", self.uri))?;
+        Ok(path.strip_prefix("//").unwrap_or(path))
+    }
+}
+
+/// The bidirectional stream to the server.
+pub trait Connection: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Connection for T {}
+
+#[cfg(unix)]
+async fn connect(socket: &str) -> Result<Box<dyn Connection>> {
+    Ok(Box::new(tokio::net::UnixStream::connect(socket).await?))
+}
+
+#[cfg(windows)]
+async fn connect(socket: &str) -> Result<Box<dyn Connection>> {
+    let pipe = format!(r"\\.\pipe\{socket}");
+    Ok(Box::new(tokio::net::windows::named_pipe::ClientOptions::new().open(pipe)?))
+}
+
+/// Frame the message with the `Content-Length` header.
+pub fn encode(message: &impl Serialize) -> Result<Vec<u8>> {
+    let body = serde_json::to_vec(message)?;
+    let mut ret = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    ret.extend(body);
+    Ok(ret)
+}
+
+/// Read a single framed message.
+pub async fn read_message(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Value> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        ensure!(reader.read_line(&mut line).await? > 0, "The sbt server closed the connection.");
+        let line = line.trim_end();
+        if line.is_empty() {
+            if content_length.is_some() {
+                break;
+            } else {
+                // Separator left over from the previous message.
+                continue;
+            }
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    let mut body = vec![0; content_length.unwrap_or_default()];
+    reader.read_exact(&mut body).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Request<'a> {
+    pub jsonrpc: &'static str,
+    pub id:      u64,
+    pub method:  &'a str,
+    pub params:  Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ResponseError {
+    pub code:    i64,
+    pub message: String,
+}
+
+/// Any message from the server: a response (with `id`) or a notification (with `method`).
+#[derive(Clone, Debug, Deserialize)]
+pub struct Incoming {
+    pub id:     Option<Value>,
+    pub method: Option<String>,
+    #[serde(default)]
+    pub params: Value,
+    pub result: Option<Value>,
+    pub error:  Option<ResponseError>,
+}
+
+/// Parameters of the `window/logMessage` notification.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LogMessage {
+    /// 1 - error, 2 - warning, 3 - info, 4 - log.
+    #[serde(rename = "type")]
+    pub kind:    u8,
+    pub message: String,
+}
+
+impl LogMessage {
+    pub fn trace(&self) {
+        let message = &self.message;
+        match self.kind {
+            1 => error!("sbt: {message}"),
+            2 => warn!("sbt: {message}"),
+            3 => info!("sbt: {message}"),
+            _ => debug!("sbt: {message}"),
+        }
+    }
+}
+
+/// Connection to a running sbt server.
+pub struct Client {
+    reader:  BufReader<ReadHalf<Box<dyn Connection>>>,
+    writer:  WriteHalf<Box<dyn Connection>>,
+    next_id: u64,
+    /// The server process, if it was started by us.
+    process: Option<Child>,
+}
+
+impl Debug for Client {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client").field("next_id", &self.next_id).finish_non_exhaustive()
+    }
+}
+
+impl Client {
+    /// Start sbt in the server mode in the given project and connect to it.
+    pub async fn start(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref();
+        let active_file = active_file(root);
+        // A leftover file from a previous server would make us connect to a dead socket.
+        crate::fs::remove_file_if_exists(&active_file)?;
+        let mut process = WithCwd::new(Sbt, root)
+            .cmd()?
+            .args(["-Dsbt.server.forcestart=true", "-Dsbt.supershell=false"])
+            // The sbt shell quits when its input is closed, so we keep it open.
+            .stdin(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn_intercepting()?;
+
+        let started = tokio::time::Instant::now();
+        let connection = loop {
+            if let Some(status) = process.try_wait()? {
+                bail!("The sbt server has exited before accepting connections: {status}.");
+            }
+            if let Ok(active) = active_file.read_to_json::<Active>() {
+                if let Ok(connection) = connect(active.socket()?).await {
+                    break connection;
+                }
+            }
+            ensure!(
+                started.elapsed() < STARTUP_TIMEOUT,
+                "The sbt server did not start within {} seconds.",
+                STARTUP_TIMEOUT.as_secs()
+            );
+            tokio::time::sleep(STARTUP_POLL_INTERVAL).await;
+        };
+        let mut client = Self::new(connection);
+        client.process = Some(process);
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// Client over an established connection.
+    pub fn new(connection: Box<dyn Connection>) -> Self {
+        let (reader, writer) = tokio::io::split(connection);
+        Self { reader: BufReader::new(reader), writer, next_id: 1, process: None }
+    }
+
+    pub async fn initialize(&mut self) -> Result {
+        let params = serde_json::json!({ "initializationOptions": {} });
+        self.request("initialize", params).await.map(drop)
+    }
+
+    /// Execute the sbt command, like `compile` or `all a b`, and wait for its completion.
+    ///
+    /// The log messages reported meanwhile are forwarded to the tracing.
+    pub async fn exec(&mut self, command_line: impl AsRef<str>) -> Result {
+        let command_line = command_line.as_ref();
+        let params = serde_json::json!({ "commandLine": command_line });
+        let result = self
+            .request("sbt/exec", params)
+            .await
+            .with_context(|| format!("sbt command `{command_line}` failed."))?;
+        let exit_code = result.get("exitCode").and_then(Value::as_i64).unwrap_or_default();
+        ensure!(exit_code == 0, "sbt command `{command_line}` failed with exit code {exit_code}.");
+        Ok(())
+    }
+
+    /// Send the request and wait for its response, handling the notifications received meanwhile.
+    pub async fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = Request { jsonrpc: "2.0", id, method, params };
+        self.writer.write_all(&encode(&request)?).await?;
+        self.writer.flush().await?;
+        loop {
+            let message = read_message(&mut self.reader).await?;
+            let message: Incoming = serde_json::from_value(message)?;
+            if message.id.as_ref().and_then(Value::as_u64) == Some(id) {
+                if let Some(ResponseError { code, message }) = message.error {
+                    bail!("{message} (error code {code})");
+                }
+                return Ok(message.result.unwrap_or_default());
+            }
+            match message.method.as_deref() {
+                Some("window/logMessage") =>
+                    serde_json::from_value::<LogMessage>(message.params)?.trace(),
+                Some(method) => trace!("Ignoring sbt server notification {method}."),
+                None => trace!("Ignoring response to another request: {:?}", message.id),
+            }
+        }
+    }
+
+    /// Ask the server to shut down and wait for it to exit. If it does not exit within the
+    /// [`SHUTDOWN_TIMEOUT`], it is killed.
+    pub async fn shutdown(mut self) -> Result {
+        let params = serde_json::json!({ "commandLine": "shutdown" });
+        let request = Request { jsonrpc: "2.0", id: self.next_id, method: "sbt/exec", params };
+        self.writer.write_all(&encode(&request)?).await?;
+        self.writer.flush().await?;
+        if let Some(mut process) = self.process.take() {
+            match tokio::time::timeout(SHUTDOWN_TIMEOUT, process.wait()).await {
+                Ok(status) => drop(status?),
+                Err(_) => {
+                    warn!(
+                        "The sbt server did not exit within {} seconds, killing it.",
+                        SHUTDOWN_TIMEOUT.as_secs()
+                    );
+                    process.kill().await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn framing() -> Result {
+        let message = serde_json::json!({ "jsonrpc": "2.0", "method": "initialize" });
+        let encoded = encode(&message)?;
+        assert!(encoded.starts_with(b"Content-Length: 39\r\n\r\n"));
+        let mut input = encoded.clone();
+        input.extend(encoded);
+        let mut reader = BufReader::new(input.as_slice());
+        assert_eq!(read_message(&mut reader).await?, message);
+        assert_eq!(read_message(&mut reader).await?, message);
+        assert!(read_message(&mut reader).await.is_err());
+
+        let active = Active { uri: "local:///tmp/sbt/server.sock".into() };
+        assert_eq!(active.socket()?, "/tmp/sbt/server.sock");
+        let active = Active { uri: "local:sbt-server-1234".into() };
+        assert_eq!(active.socket()?, "sbt-server-1234");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn executing() -> Result {
+        let (client_end, server_end) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let (reader, mut writer) = tokio::io::split(server_end);
+            let mut reader = BufReader::new(reader);
+            let responses = [
+                serde_json::json!({ "id": 1, "result": { "exitCode": 0 } }),
+                serde_json::json!({ "id": 2, "error": { "code": -33000, "message": "Failed." } }),
+            ];
+            for response in responses {
+                let request = read_message(&mut reader).await?;
+                assert_eq!(request["params"]["commandLine"], "compile");
+                let log = serde_json::json!({
+                    "method": "window/logMessage",
+                    "params": { "type": 3, "message": "Compiling." }
+                });
+                writer.write_all(&encode(&log)?).await?;
+                writer.write_all(&encode(&response)?).await?;
+            }
+            Result::Ok(())
+        });
+
+        let mut client = Client::new(Box::new(client_end));
+        client.exec("compile").await?;
+        let error = client.exec("compile").await.unwrap_err();
+        assert!(format!("{error:#}").contains("Failed. (error code -33000)"));
+        server.await??;
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use crate::prelude::*;
+
+pub struct Systemctl;
+
+impl Program for Systemctl {
+    fn executable_name(&self) -> &'static str {
+        "systemctl"
+    }
+}
+
+/// Which service manager the command talks to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Manager {
+    /// The system's service manager. Managing it usually requires root privileges.
+    System,
+    /// The service manager of the current user. Its units are stored in the user's configuration
+    /// directory, typically `~/.config/systemd/user`.
+    User,
+}
+
+impl Systemctl {
+    pub fn manager_cmd(&self, manager: Manager) -> Result<Command> {
+        let mut cmd = self.cmd()?;
+        if manager == Manager::User {
+            cmd.arg("--user");
+        }
+        Ok(cmd)
+    }
+
+    /// Reload the unit files, so the newly written ones are known.
+    pub async fn daemon_reload(&self, manager: Manager) -> Result {
+        self.manager_cmd(manager)?.arg("daemon-reload").run_ok().await
+    }
+
+    /// Enable the unit, so it is started on boot, and start it right away.
+    pub async fn enable_now(&self, manager: Manager, unit: &str) -> Result {
+        self.manager_cmd(manager)?.args(["enable", "--now", unit]).run_ok().await
+    }
+
+    /// Stop the unit and disable its start on boot.
+    pub async fn disable_now(&self, manager: Manager, unit: &str) -> Result {
+        self.manager_cmd(manager)?.args(["disable", "--now", unit]).run_ok().await
+    }
+}
@@ -44,4 +44,29 @@ impl Program for WasmOpt {
     fn executable_name(&self) -> &str {
         "wasm-opt"
     }
+
+    /// Binaryen versions are single numbers, like `wasm-opt version 108 (version_108)`. They are
+    /// treated as the major versions.
+    fn parse_version(&self, version_text: &str) -> Result<Version> {
+        let number = version_text
+            .split_whitespace()
+            .skip_while(|word| *word != "version")
+            .nth(1)
+            .with_context(|| {
+            format!("Failed to find the wasm-opt version in: {version_text}")
+        })?;
+        Ok(Version::new(number.parse2()?, 0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version() -> Result {
+        let text = "wasm-opt version 108 (version_108)";
+        assert_eq!(WasmOpt.parse_version(text)?, Version::new(108, 0, 0));
+        Ok(())
+    }
 }
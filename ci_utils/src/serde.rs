@@ -97,3 +97,33 @@ pub mod regex_vec {
             .map_err(D::Error::custom)
     }
 }
+
+/// Module to be used as `#[serde(with="duration_secs")]`
+///
+/// It represents `Duration` as a floating point number of seconds.
+pub mod duration_secs {
+    use super::*;
+
+    /// See [`duration_secs`].
+    pub fn serialize<S>(
+        value: &std::time::Duration,
+        ser: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ser.serialize_f64(value.as_secs_f64())
+    }
+
+    /// See [`duration_secs`].
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        de: D,
+    ) -> std::result::Result<std::time::Duration, D::Error> {
+        let secs = f64::deserialize(de)?;
+        if secs.is_finite() && secs >= 0.0 {
+            Ok(std::time::Duration::from_secs_f64(secs))
+        } else {
+            Err(D::Error::custom(format!("Invalid duration: {secs} seconds.")))
+        }
+    }
+}
@@ -1,11 +1,19 @@
 use enso_build::prelude::*;
 
 pub mod backend;
+pub mod ci_graph;
+pub mod ci_run_local;
 pub mod engine;
 pub mod gui;
+pub mod httpbin;
 pub mod ide;
 pub mod project_manager;
+pub mod push_image;
 pub mod release;
+pub mod runner_image;
+pub mod runners;
+pub mod runtime;
+pub mod test_environment;
 pub mod wasm;
 
 use clap::Arg;
@@ -24,6 +32,9 @@ pub const ENVIRONMENT_VARIABLE_NAME_PREFIX: &str = "ENSO_BUILD";
 
 pub const DEFAULT_REMOTE_REPOSITORY_FALLBACK: &str = "enso-org/enso";
 
+/// How many of the slowest steps are printed by default when profiling.
+pub const DEFAULT_PROFILE_SLOWEST_COUNT: usize = 20;
+
 pub fn default_repo_path() -> Option<PathBuf> {
     enso_build::repo::deduce_repository_path()
 }
@@ -108,6 +119,8 @@ pub enum Target {
     Backend(backend::Target),
     /// Build/Run/Test IDE bundle (includes GUI and Project Manager).
     Ide(ide::Target),
+    /// Build/Get the Docker image running the Project Manager bundle. Linux only.
+    Runtime(runtime::Target),
     /// Clean the repository. Keeps the IntelliJ's .idea directory intact. WARNING: This removes
     /// files that are not under version control in the repository subtree.
     GitClean,
@@ -119,6 +132,24 @@ pub enum Target {
     Release(release::Target),
     /// Regenerate GitHub Actions workflows.
     CiGen,
+    /// Run a job of the generated workflow locally, together with all the jobs it needs.
+    CiRunLocal(ci_run_local::Target),
+    /// Render the job graph of the generated workflow, e.g. to be put into the PR description.
+    CiGraph(ci_graph::Target),
+    /// Tag a local Docker image and push it to a registry.
+    PushImage(push_image::Target),
+    /// Write a Docker Compose file with the services used by the standard library tests, along
+    /// with the environment that makes the tests use them. The httpbin stand-in is not part of
+    /// the stack, it is served by the `httpbin` command.
+    TestEnvironment(test_environment::Target),
+    /// Serve the httpbin stand-in used by the standard library HTTP tests, until interrupted.
+    Httpbin(httpbin::Target),
+    /// Reconcile the self-hosted runners of this machine with the runners configuration: register
+    /// the missing ones and remove the stale ones.
+    Runners(runners::Target),
+    /// Generate the Dockerfile of a self-hosted runner's image, with the build's toolchains
+    /// preinstalled, and build the image.
+    RunnerImage(runner_image::Target),
 }
 
 /// Build, test and package Enso Engine.
@@ -158,6 +189,16 @@ pub struct Cli {
     #[clap(long, hide = !ide_ci::actions::workflow::is_in_env(), parse(try_from_str), default_value_t = true, enso_env())]
     pub upload_artifacts: bool,
 
+    /// Profile the run: record how long the build steps, child processes, downloads and cache
+    /// lookups take. The Chrome trace-event JSON file is written to the given path and the
+    /// slowest steps are printed at exit.
+    #[clap(long, parse(try_from_str=normalize_path), enso_env())]
+    pub profile: Option<PathBuf>,
+
+    /// How many of the slowest steps are printed when profiling.
+    #[clap(long, default_value_t = DEFAULT_PROFILE_SLOWEST_COUNT, enso_env())]
+    pub profile_slowest: usize,
+
     #[clap(subcommand)]
     pub target: Target,
 }
@@ -216,6 +257,14 @@ pub enum SourceKind {
     Release,
 }
 
+/// One of the workflows generated by the `ci-gen` command.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum GeneratedWorkflow {
+    Nightly,
+    Gui,
+    Backend,
+}
+
 /// Strongly typed argument for an output directory of a given build target.
 #[derive(Args, Clone, Derivative)]
 #[derivative(Debug, PartialEq)]
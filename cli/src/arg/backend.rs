@@ -1,9 +1,15 @@
 use crate::arg::Source;
 use crate::source_args_hlp;
+use clap::ArgEnum;
 use clap::Args;
 use clap::Subcommand;
+use enso_build::engine::step::StepSelection;
+use enso_build::enso::Database;
+use enso_build::enso::IrCaches;
 use enso_build::prelude::*;
 use enso_build::project::backend::Backend;
+use ide_ci::future::AsyncPolicy;
+use std::time::Duration;
 
 #[derive(Args, Clone, Debug, PartialEq)]
 pub struct BuildInput {
@@ -27,7 +33,61 @@ pub enum Command {
         #[clap(flatten)]
         input: BuildInput,
     },
-    CiCheck {},
+    CiCheck {
+        /// How many times the failed test suites are rerun before the build fails.
+        #[clap(long, default_value_t = 0)]
+        test_retries: usize,
+        #[clap(flatten)]
+        steps:        StepSelection,
+    },
+    /// Run the standard library tests using the already built Engine distribution. If it is not
+    /// present, only the Engine distribution is built.
+    Test {
+        /// Test projects to run, named after their directories in the repository's `test`
+        /// directory. By default, the ones run on CI are used.
+        libraries:   Vec<String>,
+        /// Whether the tests should be run with IR caches enabled, disabled or both ways.
+        #[clap(long, arg_enum, default_value_t = IrCachesMode::No)]
+        ir_caches:   IrCachesMode,
+        /// Run only the test groups with names matching this regular expression.
+        #[clap(long)]
+        filter:      Option<String>,
+        /// Time limit for running the tests of a single library, e.g. `15min`.
+        #[clap(long, parse(try_from_str = humantime::parse_duration))]
+        timeout:     Option<Duration>,
+        /// Whether the test libraries are run one after another or all at once. Each library uses
+        /// its own Postgres and httpbin instances either way.
+        #[clap(long, arg_enum, default_value_t = AsyncPolicy::Sequential)]
+        parallelism: AsyncPolicy,
+        /// How many times the failed test groups are rerun. Tests that pass only when rerun are
+        /// reported as flaky.
+        #[clap(long, default_value_t = 0)]
+        retries:     usize,
+        /// Database to run the Table and Database tests against, either `sqlite` or
+        /// `postgres-<version>`. Can be given multiple times, the tests are run for each one. By
+        /// default, the latest Postgres is used on Linux and SQLite elsewhere.
+        #[clap(long = "database")]
+        databases:   Vec<Database>,
+    },
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum IrCachesMode {
+    Yes,
+    No,
+    /// Run the tests twice, first without and then with IR caches, as the CI does.
+    Both,
+}
+
+impl IrCachesMode {
+    pub fn runs(self) -> Vec<IrCaches> {
+        match self {
+            IrCachesMode::Yes => vec![IrCaches::Yes],
+            IrCachesMode::No => vec![IrCaches::No],
+            IrCachesMode::Both => vec![IrCaches::No, IrCaches::Yes],
+        }
+    }
 }
 
 #[derive(Args, Clone, Debug, PartialEq)]
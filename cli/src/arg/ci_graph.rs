@@ -0,0 +1,24 @@
+use crate::arg::GeneratedWorkflow;
+use clap::ArgEnum;
+use clap::Args;
+
+
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum GraphFormat {
+    /// Graphviz DOT language.
+    Dot,
+    /// Mermaid flowchart, as rendered by GitHub in markdown.
+    Mermaid,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Target {
+    /// Which of the generated workflows should be rendered.
+    #[clap(arg_enum)]
+    pub workflow: GeneratedWorkflow,
+
+    #[clap(long, arg_enum, default_value_t = GraphFormat::Mermaid)]
+    pub format: GraphFormat,
+}
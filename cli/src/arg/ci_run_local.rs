@@ -0,0 +1,36 @@
+use crate::prelude::*;
+
+use crate::arg::GeneratedWorkflow;
+use clap::Args;
+
+
+
+/// Parse the `NAME=VALUE` pair.
+pub fn parse_input(text: &str) -> Result<(String, String)> {
+    let (name, value) =
+        text.split_once('=').with_context(|| format!("Expected `NAME=VALUE`, got `{text}`."))?;
+    Ok((name.into(), value.into()))
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Target {
+    /// Which of the generated workflows should be run.
+    #[clap(arg_enum)]
+    pub workflow: GeneratedWorkflow,
+
+    /// Identifier of the job to run. All the jobs it needs will be run before it.
+    pub job: String,
+
+    /// Workflow input, given as `NAME=VALUE`. Inputs that are not given use their defaults.
+    #[clap(long = "input", parse(try_from_str = parse_input))]
+    pub inputs: Vec<(String, String)>,
+
+    /// Name of the secret to expose to the workflow. Its value is read from the environment
+    /// variable of the same name.
+    #[clap(long = "secret", default_value = "GITHUB_TOKEN")]
+    pub secrets: Vec<String>,
+
+    /// Do not run the steps, only report what would be run.
+    #[clap(long)]
+    pub dry_run: bool,
+}
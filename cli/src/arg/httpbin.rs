@@ -0,0 +1,13 @@
+use crate::prelude::*;
+
+use crate::arg::ArgExt;
+use clap::Args;
+
+
+
+#[derive(Args, Clone, Debug)]
+pub struct Target {
+    /// Port to listen on. With 0, any free port is used.
+    #[clap(long, default_value_t = enso_build::test_environment::HTTPBIN_PORT, enso_env())]
+    pub port: u16,
+}
@@ -0,0 +1,93 @@
+use crate::prelude::*;
+
+use crate::arg::ArgExt;
+use clap::Args;
+use ide_ci::programs::docker;
+use ide_ci::programs::docker::Credentials;
+
+
+
+/// Tag a local Docker image and push it to a registry.
+#[derive(Args, Clone, Debug)]
+pub struct Target {
+    /// The image to push, either its ID or a local reference like `runtime:latest`.
+    pub image: String,
+
+    /// Repository to push to, including the registry host, like `ghcr.io/enso-org/runtime`.
+    #[clap(long, enso_env())]
+    pub repository: String,
+
+    /// Tag the image is pushed as. Can be given multiple times.
+    #[clap(long = "tag", default_value = "latest")]
+    pub tags: Vec<String>,
+
+    /// User to log into the registry as. If not given, the Docker configuration of the current
+    /// user is used.
+    #[clap(long, enso_env())]
+    pub registry_user: Option<String>,
+
+    /// Password or access token of the registry user.
+    #[clap(long, enso_env(), hide_env_values = true, requires = "registry_user")]
+    pub registry_password: Option<String>,
+}
+
+impl Target {
+    /// Credentials for the registry of the repository, if the user was given.
+    pub fn credentials(&self) -> Result<Option<Credentials>> {
+        let username = match &self.registry_user {
+            Some(username) => username.clone(),
+            None => return Ok(None),
+        };
+        let registry = docker::registry_of(&self.repository).unwrap_or(docker::DOCKER_HUB);
+        let password = self.registry_password.clone().context("Missing the registry password.")?;
+        Ok(Some(Credentials { registry: registry.into(), username, password }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::FromArgMatches;
+
+    fn parse(args: &[&str]) -> Result<Target> {
+        let command = Target::augment_args(clap::Command::new("push-image"));
+        let matches =
+            command.try_get_matches_from(once("push-image").chain(args.iter().copied()))?;
+        Ok(Target::from_arg_matches(&matches)?)
+    }
+
+    #[test]
+    fn credentials() -> Result {
+        let target = parse(&["runtime:latest", "--repository", "ghcr.io/enso-org/runtime"])?;
+        assert_eq!(target.tags, ["latest"]);
+        assert!(target.credentials()?.is_none());
+
+        let target = parse(&[
+            "runtime:latest",
+            "--repository",
+            "ghcr.io/enso-org/runtime",
+            "--registry-user",
+            "enso-bot",
+            "--registry-password",
+            "secret",
+        ])?;
+        let credentials = target.credentials()?.context("Missing credentials.")?;
+        assert_eq!(credentials.registry, "ghcr.io");
+        assert_eq!(credentials.username, "enso-bot");
+        assert_eq!(credentials.password, "secret");
+
+        let docker_hub = Target { repository: "enso/runtime".into(), ..target.clone() };
+        assert_eq!(
+            docker_hub.credentials()?.map(|credentials| credentials.registry).as_deref(),
+            Some(docker::DOCKER_HUB)
+        );
+        let no_password = Target { registry_password: None, ..target };
+        assert!(no_password.credentials().is_err());
+
+        // The password alone is of no use.
+        assert!(
+            parse(&["image", "--repository", "enso/runtime", "--registry-password", "x"]).is_err()
+        );
+        Ok(())
+    }
+}
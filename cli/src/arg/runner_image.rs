@@ -0,0 +1,41 @@
+use crate::prelude::*;
+
+use crate::arg::normalize_path;
+use crate::arg::ArgExt;
+use clap::Args;
+
+
+
+#[derive(Args, Clone, Debug)]
+pub struct Target {
+    /// The configuration file describing the runners of each server.
+    #[clap(long, parse(try_from_str=normalize_path), enso_env())]
+    pub config: PathBuf,
+
+    /// The server whose runner's image is built, i.e. the key of its runners in the configuration
+    /// file.
+    #[clap(long, enso_env())]
+    pub server_name: String,
+
+    /// Name of the runner whose image is built.
+    #[clap(long, enso_env())]
+    pub runner: String,
+
+    /// Docker build context of the image, where its Dockerfile is written. By default, it is the
+    /// directory of the configuration file.
+    #[clap(long, parse(try_from_str=normalize_path), enso_env())]
+    pub context: Option<PathBuf>,
+}
+
+impl Target {
+    pub fn context(&self) -> Result<PathBuf> {
+        match &self.context {
+            Some(context) => Ok(context.clone()),
+            None => Ok(self
+                .config
+                .parent()
+                .context("The configuration file has no parent directory.")?
+                .to_path_buf()),
+        }
+    }
+}
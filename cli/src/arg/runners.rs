@@ -0,0 +1,72 @@
+use crate::prelude::*;
+
+use crate::arg::normalize_path;
+use crate::arg::ArgExt;
+use clap::ArgEnum;
+use clap::Args;
+use ide_ci::deploy::runner::reconcile;
+use ide_ci::deploy::runner::reconcile::Deployment;
+
+
+
+/// How the runners are run on this machine.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum DeploymentKind {
+    /// Each runner runs in its own Docker container.
+    Container,
+    /// Each runner runs as a systemd unit of the current user.
+    Systemd,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Target {
+    /// The configuration file describing the runners of each server.
+    #[clap(long, parse(try_from_str=normalize_path), enso_env())]
+    pub config: PathBuf,
+
+    /// The server this machine is, i.e. the key of its runners in the configuration file.
+    #[clap(long, enso_env())]
+    pub server_name: String,
+
+    #[clap(long, arg_enum, default_value_t = DeploymentKind::Container, enso_env())]
+    pub deployment: DeploymentKind,
+
+    /// Docker build context of the runner images, with their Dockerfiles. By default, it is the
+    /// directory of the configuration file.
+    #[clap(long, parse(try_from_str=normalize_path), enso_env())]
+    pub context: Option<PathBuf>,
+
+    /// Where the runners run as systemd units are installed.
+    #[clap(long, parse(try_from_str=normalize_path), maybe_default_os = reconcile::default_runners_dir(), enso_env())]
+    pub runners_dir: PathBuf,
+
+    /// Where the systemd units of the runners are written.
+    #[clap(long, parse(try_from_str=normalize_path), maybe_default_os = reconcile::default_unit_dir(), enso_env())]
+    pub unit_dir: PathBuf,
+
+    /// Only print the actions that would reconcile the runners.
+    #[clap(long)]
+    pub plan: bool,
+}
+
+impl Target {
+    pub fn deployment(&self) -> Result<Deployment> {
+        Ok(match self.deployment {
+            DeploymentKind::Container => {
+                let context = match &self.context {
+                    Some(context) => context.clone(),
+                    None => self
+                        .config
+                        .parent()
+                        .context("The configuration file has no parent directory.")?
+                        .to_path_buf(),
+                };
+                Deployment::Container { context }
+            }
+            DeploymentKind::Systemd => Deployment::Systemd {
+                runners_dir: self.runners_dir.clone(),
+                unit_dir:    self.unit_dir.clone(),
+            },
+        })
+    }
+}
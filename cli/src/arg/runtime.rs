@@ -0,0 +1,22 @@
+use enso_build::prelude::*;
+
+use crate::arg::Source;
+use crate::source_args_hlp;
+use clap::Args;
+use enso_build::project::backend::Backend;
+use enso_build::project::runtime::Runtime;
+
+source_args_hlp!(Runtime, "runtime", BuildInput);
+
+#[derive(Args, Clone, Debug, PartialEq)]
+pub struct BuildInput {
+    /// The Project Manager bundle put into the image.
+    #[clap(flatten)]
+    pub backend: Source<Backend>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Target {
+    #[clap(flatten)]
+    pub source: Source<Runtime>,
+}
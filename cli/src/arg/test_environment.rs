@@ -0,0 +1,23 @@
+use crate::prelude::*;
+
+use crate::arg::normalize_path;
+use crate::arg::ArgExt;
+use clap::Args;
+
+
+
+#[derive(Args, Clone, Debug)]
+pub struct Target {
+    /// Directory where the Compose file and the environment file for the tests are written.
+    #[clap(long, parse(try_from_str=normalize_path), default_value = "dist/test-environment", enso_env())]
+    pub output_path: PathBuf,
+
+    /// Tag of the Postgres image to use, like `14`.
+    #[clap(long, default_value = "latest", enso_env())]
+    pub postgres_version: String,
+
+    /// The image running the Project Manager. By default, it is the runtime image of the current
+    /// version, as built by the `runtime` command.
+    #[clap(long, enso_env())]
+    pub project_manager_image: Option<String>,
+}
@@ -3,6 +3,8 @@ use crate::prelude::*;
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
+use enso_build::engine::step::StepSelection;
+use enso_build::engine::BuildConfigurationOverrides;
 use enso_build::engine::BuildConfigurationResolved;
 use enso_build::engine::BuildOperation;
 use enso_build::engine::Operation;
@@ -95,42 +97,68 @@ pub fn parse_repo_context(value: &str) -> std::result::Result<Option<RepoContext
 pub struct Arguments {
     /// build kind (dev/nightly)
     #[clap(long, arg_enum, default_value_t = default_kind(), env = crate::BuildKind::NAME)]
-    pub kind:       BuildKind,
+    pub kind:        BuildKind,
     /// path to the local copy of the Enso Engine repository
     #[clap(long, maybe_default_os = crate::arg::default_repo_path(), enso_env())]
-    pub repo_path:  PathBuf,
+    pub repo_path:   PathBuf,
     /// identifier of the release to be targeted (necessary for `upload` and `finish` commands)
     #[clap(long, env = enso_build::env::ReleaseId::NAME)]
-    pub release_id: Option<u64>,
+    pub release_id:  Option<u64>,
     /// whether create bundles with Project Manager and Launcher
     #[clap(long)]
-    pub bundle:     Option<bool>,
+    pub bundle:      Option<bool>,
     /// repository that will be targeted for the release info purposes
     #[clap(long, default_value_t = crate::arg::default_repo_remote(), enso_env())]
-    pub repo:       RepoContext,
+    pub repo:        RepoContext,
+    /// Named build configuration preset, applied on top of the build kind's defaults.
+    #[clap(long)]
+    pub preset:      Option<String>,
+    /// YAML file with the `engine-presets` section. By default, the `build-config.yaml` in the
+    /// repository is used.
+    #[clap(long)]
+    pub preset_file: Option<PathBuf>,
+    /// Overrides of the individual build configuration flags, applied after the preset.
+    #[clap(flatten)]
+    pub overrides:   BuildConfigurationOverrides,
+    #[clap(flatten)]
+    pub steps:       StepSelection,
     #[clap(subcommand)]
-    pub command:    WhatToDo,
+    pub command:     WhatToDo,
 }
 
 impl Arguments {
-    pub fn build_configuration(&self) -> BuildConfigurationResolved {
+    pub fn build_configuration(&self) -> Result<BuildConfigurationResolved> {
         let mut config = match self.kind {
             BuildKind::Dev => DEV,
             BuildKind::Nightly => NIGHTLY,
         };
 
+        let mut overrides = BuildConfigurationOverrides::default();
+        if let Some(preset) = &self.preset {
+            let path = match &self.preset_file {
+                Some(path) => path.clone(),
+                None => self.repo_path.join(enso_build::config::FILENAME),
+            };
+            let presets = enso_build::config::load_yaml(&ide_ci::fs::read_to_string(&path)?)
+                .with_context(|| format!("Failed to load presets from {}.", path.display()))?;
+            overrides = *presets.engine_preset(preset)?;
+        }
+        let overrides = overrides.then(&self.overrides);
+        overrides.apply(&mut config);
+
         // Update build configuration with a custom arg overrides.
         if matches!(self.command, WhatToDo::Upload(_)) || self.bundle.contains(&true) {
             config.build_launcher_bundle = true;
             config.build_project_manager_bundle = true;
         }
+        overrides.validate(&config)?;
 
-        BuildConfigurationResolved::new(config)
+        Ok(BuildConfigurationResolved::new(config))
     }
 
     pub async fn run_context(&self) -> Result<RunContext> {
         // Get default build configuration for a given build kind.
-        let config = self.build_configuration();
+        let config = self.build_configuration()?;
         let octocrab = setup_octocrab().await?;
         let enso_root = self.repo_path.clone();
         debug!("Received target location: {}", enso_root.display());
@@ -161,7 +189,8 @@ impl Arguments {
             octocrab,
             cache: Cache::new_default().await?,
         };
-        Ok(RunContext { inner, config, paths, goodies, operation })
+        let steps = self.steps.clone();
+        Ok(RunContext { inner, config, paths, goodies, operation, steps })
     }
 
     pub fn release_operation(&self) -> Result<ReleaseOperation> {
@@ -1,22 +1,40 @@
+use crate::arg::ci_graph::GraphFormat;
+use crate::arg::GeneratedWorkflow;
 use crate::ci_gen::job::plain_job;
 use crate::ci_gen::job::RunsOn;
 use crate::prelude::*;
+use enso_build::version::BuildKind;
+use ide_ci::actions::workflow::definition::action;
 use ide_ci::actions::workflow::definition::checkout_repo_step;
+use ide_ci::actions::workflow::definition::expression;
 use ide_ci::actions::workflow::definition::run;
 use ide_ci::actions::workflow::definition::setup_artifact_api;
 use ide_ci::actions::workflow::definition::setup_conda;
 use ide_ci::actions::workflow::definition::setup_wasm_pack_step;
+use ide_ci::actions::workflow::definition::shell_os;
+use ide_ci::actions::workflow::definition::Access;
+use ide_ci::actions::workflow::definition::Action;
 use ide_ci::actions::workflow::definition::Concurrency;
 use ide_ci::actions::workflow::definition::Event;
+use ide_ci::actions::workflow::definition::Expression;
 use ide_ci::actions::workflow::definition::Job;
 use ide_ci::actions::workflow::definition::JobArchetype;
-use ide_ci::actions::workflow::definition::PullRequest;
+use ide_ci::actions::workflow::definition::Permission;
+use ide_ci::actions::workflow::definition::Permissions;
 use ide_ci::actions::workflow::definition::Push;
 use ide_ci::actions::workflow::definition::RunnerLabel;
 use ide_ci::actions::workflow::definition::Schedule;
 use ide_ci::actions::workflow::definition::Step;
 use ide_ci::actions::workflow::definition::Workflow;
-use ide_ci::actions::workflow::definition::WorkflowDispatch;
+use ide_ci::actions::workflow::definition::WorkflowCall;
+use ide_ci::actions::workflow::definition::WorkflowCallInput;
+use ide_ci::actions::workflow::definition::WorkflowCallInputType;
+use ide_ci::actions::workflow::definition::WorkflowCallOutput;
+use ide_ci::actions::workflow::graph::JobGraph;
+use ide_ci::actions::workflow::local::LocalRunner;
+use ide_ci::env::Variable;
+use std::collections::BTreeSet;
+use strum::IntoEnumIterator;
 
 pub mod job;
 pub mod step;
@@ -46,17 +64,65 @@ pub fn runs_on(os: OS) -> Vec<RunnerLabel> {
     }
 }
 
+/// Composite action with the setup shared by all jobs running the build script.
+///
+/// Paths of the generated files are relative to the repository root and must match the
+/// `build/ide-paths.yaml`.
+pub const SETUP_ACTION: &str = "./.github/actions/setup-build-script";
+pub const BUILD_WASM_WORKFLOW: &str = "./.github/workflows/build-wasm.yml";
+pub const BUILD_BACKEND_WORKFLOW: &str = "./.github/workflows/build-backend.yml";
+
+/// Input of the setup action, passing the token for the GitHub API.
+///
+/// Composite actions have no access to the `secrets` context, so it must be given explicitly.
+pub const GITHUB_TOKEN_INPUT: &str = "github-token";
+
+/// Input of the reusable workflows, selecting the system to run on.
+pub const OS_INPUT: &str = "os";
+
+/// Input of the reusable workflows, selecting the kind of the build, like `nightly`.
+///
+/// The called workflows do not see the caller's environment, so this is how they get the
+/// `ENSO_BUILD_KIND`.
+pub const BUILD_KIND_INPUT: &str = "build-kind";
+
+pub fn setup_action() -> Result<Action> {
+    let build_script_setup = |os| {
+        shell_os(os, "./run --help")
+            .with_name("Build Script Setup")
+            .with_env("GITHUB_TOKEN", expression::input(GITHUB_TOKEN_INPUT))
+    };
+    let steps = vec![
+        setup_conda(),
+        setup_wasm_pack_step(),
+        setup_artifact_api(),
+        build_script_setup(OS::Windows),
+        build_script_setup(OS::Linux),
+    ];
+    let mut action = Action::new_composite(
+        "Setup the build script",
+        "Install the required tools and build the build script.",
+        steps,
+    )?;
+    action.add_input(GITHUB_TOKEN_INPUT, action::Input::new("Token for the GitHub API.", true));
+    Ok(action)
+}
+
 pub fn setup_script_steps() -> Vec<Step> {
-    let mut ret =
-        vec![setup_conda(), setup_wasm_pack_step(), setup_artifact_api(), checkout_repo_step()];
-    ret.push(run("--help").with_name("Build Script Setup"));
-    ret
+    let setup = Step {
+        name: Some("Setup the build script".into()),
+        uses: Some(SETUP_ACTION.into()),
+        ..default()
+    }
+    .with_custom_argument(GITHUB_TOKEN_INPUT, expression::secret("GITHUB_TOKEN"));
+    // The checkout must go first, as the setup action is defined in the repository.
+    vec![checkout_repo_step(), setup]
 }
 
 pub fn setup_script_and_steps(command_line: impl AsRef<str>) -> Vec<Step> {
     let list_everything_on_failure = Step {
         name: Some("List files if failed".into()),
-        r#if: Some("failure()".into()),
+        r#if: Some(expression::failure()),
         run: Some("ls -R".into()),
         ..default()
     };
@@ -101,7 +167,7 @@ impl JobArchetype for PublishRelease {
         let mut ret = plain_job(&os, "Publish release", "release publish");
         ret.expose_secret_as("ARTEFACT_S3_ACCESS_KEY_ID", "AWS_ACCESS_KEY_ID");
         ret.expose_secret_as("ARTEFACT_S3_SECRET_ACCESS_KEY ", "AWS_SECRET_ACCESS_KEY");
-        ret.env("AWS_REGION", "us-west-1");
+        ret.env("AWS_REGION", Expression::literal("us-west-1"));
         ret
     }
 }
@@ -109,13 +175,14 @@ impl JobArchetype for PublishRelease {
 pub struct UploadIde;
 impl JobArchetype for UploadIde {
     fn job(os: OS) -> Job {
-        plain_job(&os, "Build IDE", "ide upload --wasm-source current-ci-run --backend-source release --backend-release ${{env.ENSO_RELEASE_ID}}")
+        let release_id = expression::env("ENSO_RELEASE_ID").wrapped();
+        plain_job(&os, "Build IDE", format!("ide upload --wasm-source current-ci-run --backend-source release --backend-release {release_id}"))
     }
 }
 
 pub fn nightly() -> Result<Workflow> {
     let on = Event {
-        workflow_dispatch: Some(WorkflowDispatch {}),
+        workflow_dispatch: Some(default()),
         // 5am (UTC) from Tuesday to Saturday (i.e. after every workday)
         schedule: vec![Schedule::new("0 5 * * 2-6")?],
         ..default()
@@ -131,16 +198,36 @@ pub fn nightly() -> Result<Workflow> {
         ..default()
     };
 
-    let prepare_job_id = workflow.add::<DraftRelease>(linux_only);
-    let build_wasm_job_id = workflow.add::<job::BuildWasm>(linux_only);
+    // The token is read-only by default. Only the jobs that modify the release may write.
+    workflow.permissions = Some(Permissions::default().with(Permission::Contents, Access::Read));
+    let release_writer = |timeout_minutes: u32| {
+        move |job: &mut Job| {
+            job.timeout_minutes = Some(timeout_minutes);
+            job.permit(Permission::Contents, Access::Write);
+        }
+    };
+
+    let prepare_job_id = workflow.add_customized::<DraftRelease>(linux_only, release_writer(10));
+    let build_wasm_job_id = add_reusable_customized::<job::BuildWasm>(
+        &mut workflow,
+        linux_only,
+        BUILD_WASM_WORKFLOW,
+        |job| {
+            job.input(BUILD_KIND_INPUT, Expression::literal(BuildKind::Nightly.to_string()));
+        },
+    );
     let mut packaging_job_ids = vec![];
     for os in TARGETED_SYSTEMS {
-        let backend_job_id = workflow.add_dependent::<job::UploadBackend>(os, [&prepare_job_id]);
-        let build_ide_job_id = workflow.add_dependent::<UploadIde>(os, [
-            &prepare_job_id,
-            &backend_job_id,
-            &build_wasm_job_id,
-        ]);
+        let backend_job_id = workflow.add_dependent_customized::<job::UploadBackend>(
+            os,
+            [&prepare_job_id],
+            release_writer(180),
+        );
+        let build_ide_job_id = workflow.add_dependent_customized::<UploadIde>(
+            os,
+            [&prepare_job_id, &backend_job_id, &build_wasm_job_id],
+            release_writer(90),
+        );
         packaging_job_ids.push(build_ide_job_id);
     }
 
@@ -149,12 +236,16 @@ pub fn nightly() -> Result<Workflow> {
         packaging_job_ids
     };
 
-    let _publish_job_id = workflow.add_dependent::<PublishRelease>(linux_only, publish_deps);
+    let _publish_job_id = workflow.add_dependent_customized::<PublishRelease>(
+        linux_only,
+        publish_deps,
+        release_writer(30),
+    );
     let global_env = [("ENSO_BUILD_KIND", "nightly"), ("RUST_BACKTRACE", "full")];
     for (var_name, value) in global_env {
-        workflow.env(var_name, value);
+        workflow.env(var_name, Expression::literal(value));
     }
-    workflow.env("ENSO_BUILD_SKIP_VERSION_CHECK", "true");
+    workflow.env("ENSO_BUILD_SKIP_VERSION_CHECK", true);
     Ok(workflow)
 }
 
@@ -162,17 +253,87 @@ pub fn typical_check_triggers() -> Event {
     let branches =
         ["develop", "unstable", "stable"].into_iter().map(ToString::to_string).collect_vec();
     Event {
-        pull_request: Some(PullRequest {}),
-        workflow_dispatch: Some(WorkflowDispatch {}),
+        pull_request: Some(default()),
+        workflow_dispatch: Some(default()),
         push: Some(Push { branches, ..default() }),
         ..default()
     }
 }
 
+/// Reusable workflow running the given job archetype on the system selected by its input.
+///
+/// There is a job for each of the targeted systems, all but the selected one are skipped.
+pub fn reusable_workflow<J: JobArchetype>(name: impl Into<String>) -> Workflow {
+    let mut workflow = Workflow { name: name.into(), ..default() };
+    workflow.env("ENSO_BUILD_SKIP_VERSION_CHECK", true);
+    let mut job_ids = vec![];
+    for os in TARGETED_SYSTEMS {
+        let job_id = workflow.add_customized::<J>(os, |job| {
+            job.run_if(expression::input(OS_INPUT).equal_to(Expression::literal(os.to_string())));
+        });
+        job_ids.push(job_id);
+    }
+
+    let mut call = WorkflowCall::default();
+    let os_input = WorkflowCallInput::new(
+        WorkflowCallInputType::String,
+        format!("System to run on, one of: {}.", TARGETED_SYSTEMS.iter().join(", ")),
+        true,
+    );
+    call.add_input(OS_INPUT, os_input);
+    let build_kind_input = WorkflowCallInput::new(
+        WorkflowCallInputType::String,
+        format!("Kind of the build, one of: {}.", BuildKind::iter().join(", ")),
+        false,
+    )
+    .with_default(BuildKind::Dev.to_string());
+    call.add_input(BUILD_KIND_INPUT, build_kind_input);
+    workflow.env(crate::BuildKind::NAME, expression::input(BUILD_KIND_INPUT));
+    // Only one of the jobs runs, so the outputs are taken from whichever did.
+    let outputs: BTreeSet<_> =
+        workflow.jobs.values().flat_map(|job| job.outputs.keys().cloned()).collect();
+    for output in outputs {
+        let sources = job_ids.iter().map(|job_id| expression::job_output(job_id, &output));
+        if let Some(value) = Expression::any(sources) {
+            let description = format!("The `{output}` output of the job.");
+            call.add_output(output, WorkflowCallOutput::new(description, value));
+        }
+    }
+    workflow.on = Event { workflow_call: Some(call), ..default() };
+    workflow
+}
+
+/// Add a job calling the reusable workflow generated for the given job archetype.
+///
+/// The job gets the same key as if the archetype was added directly, so it can be depended upon
+/// in the same way.
+pub fn add_reusable<J: JobArchetype>(
+    workflow: &mut Workflow,
+    os: OS,
+    reusable_workflow: &str,
+) -> String {
+    add_reusable_customized::<J>(workflow, os, reusable_workflow, |_| {})
+}
+
+/// Like [`add_reusable`], but the calling job can be adjusted, e.g. to pass more inputs.
+pub fn add_reusable_customized<J: JobArchetype>(
+    workflow: &mut Workflow,
+    os: OS,
+    reusable_workflow: &str,
+    f: impl FnOnce(&mut Job),
+) -> String {
+    let (key, job) = J::entry(os);
+    let mut call = Job::new_call(job.name, reusable_workflow)
+        .with_input(OS_INPUT, Expression::literal(os.to_string()));
+    f(&mut call);
+    workflow.jobs.insert(key.clone(), call);
+    key
+}
+
 pub fn gui() -> Result<Workflow> {
     let on = typical_check_triggers();
     let mut workflow = Workflow { name: "GUI CI".into(), on, ..default() };
-    workflow.env("ENSO_BUILD_SKIP_VERSION_CHECK", "true");
+    workflow.env("ENSO_BUILD_SKIP_VERSION_CHECK", true);
     workflow.add::<job::AssertChangelog>(PRIMARY_OS);
     workflow.add::<job::CancelWorkflow>(PRIMARY_OS);
     workflow.add::<job::Lint>(PRIMARY_OS);
@@ -183,8 +344,9 @@ pub fn gui() -> Result<Workflow> {
     });
 
     for os in TARGETED_SYSTEMS {
-        let wasm_job = workflow.add::<job::BuildWasm>(os);
-        let project_manager_job = workflow.add::<job::BuildBackend>(os);
+        let wasm_job = add_reusable::<job::BuildWasm>(&mut workflow, os, BUILD_WASM_WORKFLOW);
+        let project_manager_job =
+            add_reusable::<job::BuildBackend>(&mut workflow, os, BUILD_BACKEND_WORKFLOW);
         workflow.add_customized::<job::PackageIde>(os, |job| {
             job.needs.insert(wasm_job);
             job.needs.insert(project_manager_job);
@@ -196,17 +358,72 @@ pub fn gui() -> Result<Workflow> {
 pub fn backend() -> Result<Workflow> {
     let on = typical_check_triggers();
     let mut workflow = Workflow { name: "Engine CI".into(), on, ..default() };
-    workflow.env("ENSO_BUILD_SKIP_VERSION_CHECK", "true");
+    workflow.env("ENSO_BUILD_SKIP_VERSION_CHECK", true);
     workflow.add::<job::CancelWorkflow>(PRIMARY_OS);
     for os in TARGETED_SYSTEMS {
         workflow.add::<job::CiCheckBackend>(os);
     }
+    // Postgres containers can be run only on Linux.
+    workflow.add::<job::DatabaseTests>(OS::Linux);
     Ok(workflow)
 }
 
-pub fn generate(repo_root: &enso_build::paths::generated::RepoRootGithubWorkflows) -> Result {
-    repo_root.nightly_yml.write_as_yaml(&nightly()?)?;
-    repo_root.scala_new_yml.write_as_yaml(&backend()?)?;
-    repo_root.gui_yml.write_as_yaml(&gui()?)?;
+pub fn generate(repo_root: &enso_build::paths::generated::RepoRootGithub) -> Result {
+    repo_root.actions.setup_build_script.action_yml.write_as_yaml(&setup_action()?)?;
+    let workflows = &repo_root.workflows;
+    workflows.build_wasm_yml.write_as_yaml(&reusable_workflow::<job::BuildWasm>("Build WASM"))?;
+    workflows
+        .build_backend_yml
+        .write_as_yaml(&reusable_workflow::<job::BuildBackend>("Build Backend"))?;
+    workflows.nightly_yml.write_as_yaml(&nightly()?)?;
+    workflows.scala_new_yml.write_as_yaml(&backend()?)?;
+    workflows.gui_yml.write_as_yaml(&gui()?)?;
+    Ok(())
+}
+
+pub fn workflow(which: GeneratedWorkflow) -> Result<Workflow> {
+    match which {
+        GeneratedWorkflow::Nightly => nightly(),
+        GeneratedWorkflow::Gui => gui(),
+        GeneratedWorkflow::Backend => backend(),
+    }
+}
+
+/// Print the job graph of the generated workflow. See [`ide_ci::actions::workflow::graph`].
+pub fn graph(target: crate::arg::ci_graph::Target) -> Result {
+    let graph = JobGraph::new(&workflow(target.workflow)?)?;
+    match target.format {
+        GraphFormat::Dot => println!("{}", graph.to_dot()),
+        GraphFormat::Mermaid => println!("{}", graph.to_mermaid()),
+    }
+    Ok(())
+}
+
+/// Run the job of the generated workflow on this machine. See [`ide_ci::actions::workflow::local`].
+pub async fn run_local(repo_root: PathBuf, target: crate::arg::ci_run_local::Target) -> Result {
+    let workflow = workflow(target.workflow)?;
+    let mut runner = LocalRunner::new(repo_root);
+    runner.dry_run = target.dry_run;
+    runner.inputs = target.inputs.into_iter().collect();
+    for secret in target.secrets {
+        match std::env::var(&secret) {
+            Ok(value) => {
+                runner.secrets.insert(secret, value);
+            }
+            Err(_) => warn!("Secret {secret} is not set in the environment."),
+        }
+    }
+    runner.actions.insert(SETUP_ACTION.into(), setup_action()?);
+    runner
+        .workflows
+        .insert(BUILD_WASM_WORKFLOW.into(), reusable_workflow::<job::BuildWasm>("Build WASM"));
+    runner.workflows.insert(
+        BUILD_BACKEND_WORKFLOW.into(),
+        reusable_workflow::<job::BuildBackend>("Build Backend"),
+    );
+
+    let report = runner.run(&workflow, &target.job).await?;
+    println!("{report}");
+    ensure!(report.succeeded(), "Some of the jobs have failed.");
     Ok(())
 }
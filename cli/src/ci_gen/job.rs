@@ -1,8 +1,12 @@
 use crate::ci_gen::runs_on;
 use crate::ci_gen::step;
 use crate::prelude::*;
+use enso_build::enso::Database;
+use enso_build::paths::DATABASE_TEST_LIBRARIES;
 use ide_ci::actions::workflow::definition::cancel_workflow_action;
 use ide_ci::actions::workflow::definition::checkout_repo_step;
+use ide_ci::actions::workflow::definition::expression;
+use ide_ci::actions::workflow::definition::Expression;
 use ide_ci::actions::workflow::definition::Job;
 use ide_ci::actions::workflow::definition::JobArchetype;
 use ide_ci::actions::workflow::definition::RunnerLabel;
@@ -86,30 +90,56 @@ echo "::set-output name=list::'$list'"
         .to_string();
 
         let changed_files_id = "changed_files";
-        let changelog_was_changed =
-            format!("contains(steps.{changed_files_id}.outputs.list,'CHANGELOG.md')");
-        let omit_in_commit_msg =
-            "contains(github.event.head_commit.message,'[ci no changelog needed]')";
-        let omit_in_pr_body = "contains(github.event.pull_request.body,'[ci no changelog needed]')";
-        let is_dependabot = "github.event.pull_request.user.login == 'dependabot'";
+        let changelog_was_changed = expression::contains(
+            expression::step_output(changed_files_id, "list"),
+            Expression::literal("CHANGELOG.md"),
+        );
+        let no_changelog_marker = || Expression::literal("[ci no changelog needed]");
+        let event = || expression::github().property("event");
+        let omit_in_commit_msg = expression::contains(
+            event().property("head_commit").property("message"),
+            no_changelog_marker(),
+        );
+        let omit_in_pr_body = expression::contains(
+            event().property("pull_request").property("body"),
+            no_changelog_marker(),
+        );
+        let is_dependabot = event()
+            .property("pull_request")
+            .property("user")
+            .property("login")
+            .equal_to(Expression::literal("dependabot"));
+        let changelog_not_needed = Expression::any([
+            changelog_was_changed,
+            omit_in_commit_msg,
+            omit_in_pr_body,
+            is_dependabot,
+        ])
+        .unwrap(); // Cannot fail, as the list is not empty.
+        let base_ref = || expression::github().property("base_ref");
+        let is_checked_branch = Expression::any(
+            ["develop", "unstable", "stable"]
+                .map(|branch| base_ref().equal_to(Expression::literal(branch))),
+        )
+        .unwrap(); // Cannot fail, as the list is not empty.
 
         Job {
-                name: "Assert if CHANGELOG.md was updated (on pull request)".into(),
-                runs_on: runs_on(os),
-                steps: vec![
-                    checkout_repo_step(),
-                    Step {
-                        id: Some(changed_files_id.into()),
-                        run: Some(changed_files),
-                        ..default()
-                    },
-                    Step {
-                        run: Some(format!("if [[ ${{{{ {changelog_was_changed} || {omit_in_commit_msg} || {omit_in_pr_body} || {is_dependabot} }}}} == false ]]; then exit 1; fi")),
-                        r#if: Some("github.base_ref == 'develop' || github.base_ref == 'unstable' || github.base_ref == 'stable'".into()),
-                        ..default()
-                    }],
-                ..default()
-            }
+            name: "Assert if CHANGELOG.md was updated (on pull request)".into(),
+            runs_on: runs_on(os),
+            steps: vec![
+                checkout_repo_step(),
+                Step { id: Some(changed_files_id.into()), run: Some(changed_files), ..default() },
+                Step {
+                    run: Some(format!(
+                        "if [[ {} == false ]]; then exit 1; fi",
+                        changelog_not_needed.wrapped()
+                    )),
+                    r#if: Some(is_checked_branch),
+                    ..default()
+                },
+            ],
+            ..default()
+        }
     }
 }
 
@@ -164,10 +194,12 @@ impl JobArchetype for IntegrationTest {
 pub struct BuildWasm;
 impl JobArchetype for BuildWasm {
     fn job(os: OS) -> Job {
+        let upload_artifacts =
+            expression::runner().property("os").equal_to(Expression::literal("Linux")).wrapped();
         plain_job(
             &os,
             "Build GUI (WASM)",
-            " --upload-artifacts ${{ runner.os == 'Linux' }} wasm build",
+            format!(" --upload-artifacts {upload_artifacts} wasm build"),
         )
     }
 }
@@ -205,3 +237,31 @@ impl JobArchetype for CiCheckBackend {
         ret
     }
 }
+
+/// Postgres versions that the database tests are run against on CI, besides SQLite.
+pub const TESTED_POSTGRES_VERSIONS: [&str; 3] = ["12", "14", "latest"];
+
+/// Runs the database test libraries against each of the tested databases, as a matrix.
+pub struct DatabaseTests;
+impl JobArchetype for DatabaseTests {
+    fn job(os: OS) -> Job {
+        let databases = std::iter::once(Database::Sqlite)
+            .chain(TESTED_POSTGRES_VERSIONS.map(Database::postgres))
+            .map(|database| database.to_string())
+            .collect_vec();
+        let mut strategy = Strategy { fail_fast: Some(false), ..default() };
+        strategy.matrix.dimension("database", databases);
+
+        let database = expression::matrix("database").wrapped();
+        let command_line = format!(
+            "backend test {} --ir-caches both --database {database}",
+            DATABASE_TEST_LIBRARIES.join(" ")
+        );
+        let mut ret = plain_job(&os, format!("Database tests ({database})"), command_line);
+        ret.strategy = Some(strategy);
+        let report_format = format!("Database Tests ({os}, {{0}})");
+        let report_name = expression::format(report_format, [expression::matrix("database")]);
+        ret.steps.push(step::test_reporter_named(report_name));
+        ret
+    }
+}
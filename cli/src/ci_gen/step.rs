@@ -2,20 +2,28 @@ use crate::prelude::*;
 
 use enso_build::paths;
 use ide_ci::actions::workflow::definition::env_expression;
+use ide_ci::actions::workflow::definition::expression;
+use ide_ci::actions::workflow::definition::Expression;
 use ide_ci::actions::workflow::definition::Step;
 
 pub fn test_reporter(os: OS) -> Step {
+    test_reporter_named(Expression::literal(format!("Enso Standard Library Tests ({os})")))
+}
+
+/// Publishes the JUnit reports as a check run of the given name. The name must be unique within
+/// the workflow run.
+pub fn test_reporter_named(name: impl Into<Expression>) -> Step {
     Step {
         name: Some("Stdlib test report".into()),
         uses: Some("dorny/test-reporter@v1".into()),
-        r#if: Some("success() || failure()".into()),
+        r#if: Some(expression::success().or(expression::failure())),
         ..default()
     }
-    .with_custom_argument("reporter", "java-junit")
+    .with_custom_argument("reporter", Expression::literal("java-junit"))
     .with_custom_argument(
         "path",
-        format!("{}/**/*.xml", env_expression(&paths::ENSO_TEST_JUNIT_DIR)),
+        expression::format("{0}/**/*.xml", [env_expression(&paths::ENSO_TEST_JUNIT_DIR)]),
     )
-    .with_custom_argument("path-replace-backslashes", "true")
-    .with_custom_argument("name", format!("Enso Standard Library Tests ({os})"))
+    .with_custom_argument("path-replace-backslashes", true)
+    .with_custom_argument("name", name)
 }
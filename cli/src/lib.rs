@@ -41,6 +41,8 @@ use anyhow::Context;
 use clap::Parser;
 use derivative::Derivative;
 use enso_build::context::BuildContext;
+use enso_build::enso::BuiltEnso;
+use enso_build::enso::TestSelection;
 use enso_build::paths::TargetTriple;
 use enso_build::prettier;
 use enso_build::project;
@@ -54,12 +56,16 @@ use enso_build::project::ide;
 use enso_build::project::ide::Ide;
 use enso_build::project::project_manager;
 use enso_build::project::project_manager::ProjectManager;
+use enso_build::project::runtime;
+use enso_build::project::runtime::Runtime;
 use enso_build::project::wasm;
 use enso_build::project::wasm::Wasm;
 use enso_build::project::IsTarget;
 use enso_build::project::IsWatchable;
 use enso_build::project::IsWatcher;
 use enso_build::project::ProcessWrapper;
+use enso_build::runner_image;
+use enso_build::runner_image::Toolchains;
 use enso_build::setup_octocrab;
 use enso_build::source::BuildTargetJob;
 use enso_build::source::CiRunSource;
@@ -72,15 +78,24 @@ use enso_build::source::WatchTargetJob;
 use enso_build::source::WithDestination;
 use ide_ci::actions::workflow::is_in_env;
 use ide_ci::cache::Cache;
+use ide_ci::deploy::runner::reconcile;
+use ide_ci::deploy::runner::reconcile::Reconciler;
+use ide_ci::deploy::runner::DIRECTORY_WITH_RUNNER_PACKAGE;
 use ide_ci::fs::remove_if_exists;
 use ide_ci::github::release::upload_asset;
 use ide_ci::global;
+use ide_ci::log::profiling;
 use ide_ci::log::setup_logging;
+use ide_ci::models::test_report::TestReport;
 use ide_ci::ok_ready_boxed;
+use ide_ci::program::with_cwd::WithCwd;
 use ide_ci::programs::cargo;
+use ide_ci::programs::docker::ImageId;
 use ide_ci::programs::rustc;
 use ide_ci::programs::Cargo;
+use ide_ci::programs::Docker;
 use ide_ci::programs::Git;
+use ide_ci::programs::Sbt;
 use std::time::Duration;
 use tempfile::tempdir;
 use tokio::process::Child;
@@ -377,7 +392,7 @@ impl Processor {
                 }
                 .boxed()
             }
-            arg::backend::Command::CiCheck {} => {
+            arg::backend::Command::CiCheck { test_retries, steps } => {
                 let operation =
                     enso_build::engine::Operation::Build(enso_build::engine::BuildOperation {});
                 debug!("Operation to perform: {:?}", operation);
@@ -385,6 +400,7 @@ impl Processor {
                     enso_build::paths::Paths::new_triple(&self.source_root, self.triple.clone());
                 let config = enso_build::engine::BuildConfigurationFlags {
                     clean_repo: false,
+                    test_retries,
                     ..enso_build::engine::DEV
                 }
                 .into();
@@ -397,12 +413,71 @@ impl Processor {
                         octocrab,
                         cache: Cache::new_default().await?,
                     };
-                    let context =
-                        enso_build::engine::RunContext { inner, config, paths, goodies, operation };
+                    let context = enso_build::engine::RunContext {
+                        inner,
+                        config,
+                        paths,
+                        goodies,
+                        operation,
+                        steps,
+                    };
                     context.execute().await
                 }
                 .boxed()
             }
+            arg::backend::Command::Test {
+                libraries,
+                ir_caches,
+                filter,
+                timeout,
+                parallelism,
+                retries,
+                databases,
+            } => {
+                let paths =
+                    enso_build::paths::Paths::new_triple(&self.source_root, self.triple.clone());
+                async move {
+                    let mut selection = TestSelection { filter, timeout, ..default() };
+                    if !libraries.is_empty() {
+                        selection.libraries = libraries;
+                    }
+                    if !databases.is_empty() {
+                        selection.databases = databases;
+                    }
+                    let paths = paths?;
+                    selection.validate(&paths)?;
+                    // The test reporter step finds the JUnit reports through the environment.
+                    let _ = paths.emit_env_to_actions(); // Ignore error: we might not be run on CI.
+                    let enso = BuiltEnso { paths };
+                    if !enso.wrapper_script_path().exists() {
+                        info!("Engine distribution not found, building it.");
+                        WithCwd::new(Sbt, &enso.paths.repo_root)
+                            .call_arg("buildEngineDistribution")
+                            .await?;
+                    }
+
+                    ide_ci::fs::reset_dir(&enso.paths.test_results)?;
+                    let mut report = TestReport::default();
+                    let mut result = Ok(());
+                    for ir_caches in ir_caches.runs() {
+                        result = enso
+                            .run_tests_with_retries(
+                                &selection,
+                                ir_caches,
+                                parallelism,
+                                retries,
+                                &mut report,
+                            )
+                            .await;
+                        if result.is_err() {
+                            break;
+                        }
+                    }
+                    enso_build::engine::report_tests(&report, &enso.paths)?;
+                    result
+                }
+                .boxed()
+            }
         }
     }
 
@@ -519,7 +594,7 @@ impl Processor {
         let target = Ide { target_os: self.triple.os, target_arch: self.triple.arch };
         let build_job = target.build(input, output_path);
         async move {
-            let artifacts = build_job.await?;
+            let artifacts = build_job.instrument(info_span!("Building the IDE.")).await?;
             if is_in_env() {
                 artifacts.upload_as_ci_artifact().await?;
             }
@@ -604,6 +679,22 @@ impl Resolvable for Backend {
     }
 }
 
+impl Resolvable for Runtime {
+    fn prepare_target(_context: &Processor) -> Result<Self> {
+        Ok(Runtime)
+    }
+
+    fn resolve(
+        ctx: &Processor,
+        from: <Self as IsTargetSource>::BuildInput,
+    ) -> BoxFuture<'static, Result<<Self as IsTarget>::BuildInput>> {
+        // The image is always a Linux one, whatever the target OS.
+        let backend = ctx.resolve(Backend { target_os: OS::Linux }, from.backend);
+        let versions = ctx.triple.versions.clone();
+        async move { Ok(runtime::BuildInput { versions, backend: backend.await? }) }.boxed()
+    }
+}
+
 impl Resolvable for ProjectManager {
     fn prepare_target(_context: &Processor) -> Result<Self> {
         Ok(ProjectManager)
@@ -674,6 +765,23 @@ pub async fn main_internal(config: enso_build::config::Config) -> Result {
 
     debug!("Parsed CLI arguments: {cli:#?}");
 
+    if let Some(output) = &cli.profile {
+        let settings = profiling::Settings {
+            output:        output.clone(),
+            slowest_count: cli.profile_slowest,
+        };
+        profiling::enable(settings);
+    }
+    let result = run(config, cli).await;
+    // The profile is most useful when the build has failed, e.g. timed out.
+    if let Err(e) = profiling::finish() {
+        warn!("Failed to write the profiling results: {e:?}");
+    }
+    result
+}
+
+/// Perform the job requested through the command line.
+pub async fn run(config: enso_build::config::Config, cli: Cli) -> Result {
     if !cli.skip_version_check {
         config.check_programs().await?;
     }
@@ -694,6 +802,7 @@ pub async fn main_internal(config: enso_build::config::Config) -> Result {
         Target::Engine(engine) => ctx.handle_engine(engine).await?,
         Target::Backend(backend) => ctx.handle_backend(backend).await?,
         Target::Ide(ide) => ctx.handle_ide(ide).await?,
+        Target::Runtime(runtime) => ctx.get(runtime.source).void_ok().await?,
         // TODO: consider if out-of-source ./dist should be removed
         Target::GitClean => Git::new(ctx.repo_root()).cmd()?.nice_clean().run_ok().await?,
         Target::Lint => {
@@ -732,9 +841,86 @@ pub async fn main_internal(config: enso_build::config::Config) -> Result {
                 enso_build::release::publish_release(&*ctx).await?;
             }
         },
-        Target::CiGen => ci_gen::generate(
-            &enso_build::paths::generated::RepoRootGithubWorkflows::new(cli.repo_path),
-        )?,
+        Target::CiGen =>
+            ci_gen::generate(&enso_build::paths::generated::RepoRootGithub::new(cli.repo_path))?,
+        Target::CiRunLocal(run_local) => ci_gen::run_local(ctx.repo_root().path, run_local).await?,
+        Target::CiGraph(graph) => ci_gen::graph(graph)?,
+        Target::PushImage(push) => {
+            let credentials = push.credentials()?;
+            let image = ImageId(push.image.clone());
+            let pushed = Docker
+                .tag_and_push(&image, &push.repository, &push.tags, credentials.as_ref())
+                .await?;
+            for reference in pushed {
+                info!("Pushed {reference}.");
+            }
+        }
+        Target::TestEnvironment(environment) => {
+            let project_manager_image = environment
+                .project_manager_image
+                .unwrap_or_else(|| runtime::image_tag(&ctx.triple.versions.version));
+            let config = enso_build::test_environment::Configuration {
+                postgres_version: environment.postgres_version,
+                project_manager_image,
+            };
+            config.write(&environment.output_path)?;
+            info!(
+                "Start the services with `docker compose up` in {} and the httpbin stand-in with \
+                 the `httpbin` command, then set the environment from {} for the tests.",
+                environment.output_path.display(),
+                enso_build::test_environment::TEST_ENV_FILE
+            );
+        }
+        Target::Httpbin(httpbin) => {
+            let server = enso_build::httpbin::spawn(httpbin.port)?;
+            info!("Serving httpbin at {}. Press Ctrl+C to stop.", server.url);
+            tokio::signal::ctrl_c().await?;
+        }
+        Target::Runners(runners) => {
+            let config = ide_ci::fs::read_to_string(&runners.config)?;
+            let config = serde_yaml::from_str(&config).with_context(|| {
+                format!("Failed to parse the runners configuration {}.", runners.config.display())
+            })?;
+            let reconciler = Reconciler {
+                config,
+                server_name: runners.server_name.clone(),
+                os: TARGET_OS,
+                deployment: runners.deployment()?,
+            };
+            let plan = reconciler.plan(&ctx.octocrab).await?;
+            if runners.plan {
+                println!("{plan}");
+            } else {
+                reconciler.apply(&ctx.octocrab, &plan).await?;
+            }
+        }
+        Target::RunnerImage(image) => {
+            let runners_config = ide_ci::fs::read_to_string(&image.config)?;
+            let runners_config = serde_yaml::from_str(&runners_config).with_context(|| {
+                format!("Failed to parse the runners configuration {}.", image.config.display())
+            })?;
+            let runner =
+                reconcile::configured_runners(&runners_config, &image.server_name, OS::Linux)
+                    .into_iter()
+                    .find(|runner| runner.runner.name == image.runner)
+                    .with_context(|| {
+                        format!(
+                            "No runner {} is configured for {}.",
+                            image.runner, image.server_name
+                        )
+                    })?;
+            let build_sbt = ctx.repo_root().path.join("build.sbt");
+            let toolchains = Toolchains::deduce(&ctx.octocrab, build_sbt, &config).await?;
+            let context = image.context()?;
+            let dockerfile = runner_image::dockerfile(&runner, &toolchains)?;
+            ide_ci::fs::write(context.join(&runner.runner.dockerfile), dockerfile)?;
+            let package = context.join(DIRECTORY_WITH_RUNNER_PACKAGE);
+            if !package.exists() {
+                ide_ci::github::fetch_runner(&ctx.octocrab, runner.os, &package).await?;
+            }
+            let id = Docker.build(runner.build_options(&context)).await?;
+            info!("Built the image {} of the runner {}.", id.0, runner.image_tag());
+        }
     };
     info!("Completed main job.");
     global::complete_tasks().await?;